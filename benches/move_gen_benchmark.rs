@@ -18,10 +18,10 @@ pub fn moveGenBenchmark(c: &mut Criterion) {
     let gs = GameState::new(&mut z);
     let mut m: Moves = Moves::new();
     let mut p: Perft = Perft::new(3);
-    p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+    p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
     c.bench_function(
         "Move Generation",
-        |b| b.iter(|| p.perftRoot(black_box(&mut m), black_box(&mut z), black_box(gs.bitboards), black_box(gs.castle_rights), black_box(gs.hash_key), black_box(true), black_box(0)))
+        |b| b.iter(|| p.perftRoot(black_box(&mut m), black_box(&mut z), black_box(gs.position.bitboards), black_box(gs.position.castle_rights), black_box(gs.position.hash_key), black_box(true), black_box(0)))
     );
 }
 
@@ -4,6 +4,7 @@ File contains code for the chess bot
 
 
 import random
+import ChessProject # rust engine library
 
 
 """
@@ -15,21 +16,7 @@ def findRandomMove(valid_moves) -> str:
 
 
 """
-Helper to make first recursive call
+Starts searching for the best move on the engine's own thread, poll search.isDone() for the result
 """
-def findBestMove(game_state, m, z, tt, bmf) -> str:
-    global next_move, move_counter
-    next_move = None
-    move_counter = 0
-    findMoveNegaMaxAlphaBeta(game_state, m, z, tt, bmf)
-    return next_move
-
-
-"""
-Recursive NegaMax algo with alpha beta pruning
-"""
-def findMoveNegaMaxAlphaBeta(gs, m, z, tt, bmf) -> None:
-    global next_move, move_counter
-    bmf.searchPosition(m, z, tt, gs.bitboards, gs.castle_rights, gs.hash_key, gs.whites_turn)
-    move_counter = bmf.move_counter
-    next_move = bmf.pv_table[0][0]
+def startBestMoveSearch(game_state, search, depth, move_time_ms) -> None:
+    search.start(game_state, ChessProject.SearchLimits(depth=depth, move_time_ms=move_time_ms))
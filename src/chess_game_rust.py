@@ -21,6 +21,7 @@ FPS = 15
 IMAGES = {}
 PIECE_NAMES = ['bR', 'bN', 'bB', 'bQ', 'bK', 'bP', 'wR', 'wN', 'wB', 'wQ', 'wK', 'wP']
 DEPTH = 40
+MOVE_TIME_MS = 3000
 
 
 def loadImages() -> None:
@@ -52,11 +53,11 @@ def main() -> None:
     file_row_char_font = pg.font.SysFont('Arial', 12, True, False)
     z = ChessProject.Zobrist()
     gs = ChessProject.GameState(z)
-    bmf = ChessProject.BestMoveFinder(DEPTH)
     m = ChessProject.Moves()
-    tt = ChessProject.TransTable()
+    search = ChessProject.AsyncSearch() # searches on its own thread so the window keeps drawing
     ob = ChessProject.OpeningBook()
     valid_moves = m.getValidMoves(z, gs.bitboards, gs.castle_rights, gs.hash_key, gs.whites_turn)
+    game_status = gs.gameStatus(m, z)
     move_made = False # flag for when move is made
     animate = False
     opening_node = ob.trie.root # keep track where we are in opeing book
@@ -89,6 +90,7 @@ def main() -> None:
         for event in pg.event.get():
             if event.type == pg.QUIT:
                 running = False
+                search.stop()
 
             # mouse event cases
             elif event.type == pg.MOUSEBUTTONDOWN:
@@ -131,6 +133,19 @@ def main() -> None:
                         if not move_made:
                             player_clicks = [sq_selected]
 
+            # key event cases
+            elif event.type == pg.KEYDOWN:
+                if event.key == pg.K_z: # undo
+                    if ai_thinking:
+                        search.stop() # the move found is not played
+                        ai_thinking = False
+                    gs.undoMove()
+                    gs.in_book_opening = False # the opening book node no longer matches the game
+                    move_made = True
+                    animate = False
+                    game_over = False
+                    move_undone = True
+
         # AI move finder
         if not game_over and not is_human_turn and not move_undone:
             if not ai_thinking:
@@ -152,7 +167,6 @@ def main() -> None:
                                 print(f"In Book Opening, Move: {move}\n")
                             else: # out of opeing book
                                 gs.in_book_opening = False
-                                ai_move = ai_move_finder_rust.findBestMove(gs, m, z, tt, bmf)
                         else: # ai vs ai
                             if not opening_node.terminal:
                                 move, opening_node = random.choice(list(opening_node.children.items()))
@@ -160,9 +174,12 @@ def main() -> None:
                                 print(f"In Book Opening, Move: {move}\n")
                             else:
                                 gs.in_book_opening = False
-                                ai_move = ai_move_finder_rust.findBestMove(gs, m, z, tt, bmf)
-                else:
-                    ai_move = ai_move_finder_rust.findBestMove(gs, m, z, tt, bmf)
+                if ai_move == '': # not a book move
+                    ai_move_finder_rust.startBestMoveSearch(gs, search, DEPTH, MOVE_TIME_MS)
+
+            if ai_thinking and (ai_move != '' or search.isDone()):
+                if ai_move == '':
+                    ai_move = search.bestMove() or '' # None when there are no legal moves
                 print('Done thinking')
                 if ai_move == '':
                     ai_move = ai_move_finder_rust.findRandomMove(valid_moves)
@@ -175,15 +192,16 @@ def main() -> None:
             if animate:
                 animateMove(gs.move_log[-4:], screen, gs, clk, file_row_char_font)
             valid_moves = m.getValidMoves(z, gs.bitboards, gs.castle_rights, gs.hash_key, gs.whites_turn)
+            game_status = gs.gameStatus(m, z)
             move_made = False
             animate = False
             move_undone = False
 
         drawGameState(screen, gs, valid_moves, sq_selected, move_log_font, file_row_char_font, m)
 
-        if valid_moves == '':
+        if game_status != ChessProject.GameResult.Ongoing:
             game_over = True
-            drawEndGameText(screen, 'Stalemate' if m.stalemate else 'Black wins by checkmate' if gs.whites_turn else 'White wins by checkmate')
+            drawEndGameText(screen, endGameText(game_status, gs.whites_turn))
 
         clk.tick(FPS)
         pg.display.flip()
@@ -340,6 +358,22 @@ def animateMove(move, screen, gs, clk, file_row_char_font) -> None:
         clk.tick(150)
 
 
+"""
+Text shown when the game is over
+"""
+def endGameText(game_status, whites_turn) -> str:
+    if game_status == ChessProject.GameResult.Checkmate:
+        return 'Black wins by checkmate' if whites_turn else 'White wins by checkmate'
+    elif game_status == ChessProject.GameResult.Stalemate:
+        return 'Stalemate'
+    elif game_status in (ChessProject.GameResult.ThreefoldRepetition, ChessProject.GameResult.FivefoldRepetition):
+        return 'Draw by repetition'
+    elif game_status in (ChessProject.GameResult.FiftyMoveRule, ChessProject.GameResult.SeventyFiveMoveRule):
+        return 'Draw by fifty move rule'
+    else:
+        return 'Draw by insufficient material'
+
+
 def drawEndGameText(screen, text) -> None:
     font = pg.font.SysFont('Helvitca', 32, True, False)
     text_object = font.render(text, 0, pg.Color('Gray'))
@@ -0,0 +1,196 @@
+//! Module running searches on a background thread so python event loops keep running
+
+
+use pyo3::prelude::*;
+use std::sync::Arc;
+use std::thread::{
+    self,
+    JoinHandle,
+};
+use crate::{
+    best_move_finder::{
+        BestMoveFinder,
+        SearchOutput,
+    },
+    chess_move::Move,
+    game_state::GameState,
+    moves::Moves,
+    search_info::{
+        PyInfoCallback,
+        SearchListener,
+    },
+    search_context::SearchContext,
+    search_limits::{
+        SearchLimits,
+        StopFlag,
+    },
+    trans_table::TransTable,
+    zobrist::Zobrist,
+};
+
+
+/*
+Engine that searches on its own thread:
+    start(gs, limits), then poll isDone() / bestMove() or block on wait(), stop() ends the search early
+
+The engine keeps its own move generator, keys and transposition table between searches.
+*/
+#[pyclass(module = "ChessProject")]
+pub struct AsyncSearch {
+    context: Option<SearchContext>,
+    search_thread: Option<JoinHandle<SearchContext>>,
+    stop: StopFlag,
+    best_move: Option<Move>, // result of the last finished search
+    info_callback: Option<PyObject>,
+}
+
+
+#[pymethods]
+impl AsyncSearch {
+    #[new]
+    pub fn new() -> Self {
+        let stop: StopFlag = StopFlag::new();
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(SearchLimits::MAX_DEPTH);
+        bmf.output_mode = SearchOutput::Silent;
+        bmf.stop = Arc::clone(&stop.flag);
+        AsyncSearch {
+            context: Some(SearchContext {
+                bmf,
+                mm: Moves::new(),
+                z: Zobrist::new(),
+                tt: TransTable::new(TransTable::DEFAULT_SIZE_MB),
+            }),
+            search_thread: None,
+            stop,
+            best_move: None,
+            info_callback: None,
+        }
+    }
+
+
+    /// Start searching the current position of a game, a running search is stopped first
+    #[pyo3(name = "start")]
+    fn startPy(&mut self, py: Python, gs: &GameState, limits: SearchLimits) {
+        let listener: Option<Box<dyn SearchListener>> = self.info_callback.as_ref()
+            .map(|callback| Box::new(PyInfoCallback {callback: callback.clone_ref(py)}) as Box<dyn SearchListener>);
+        self.stop.set();
+        py.allow_threads(|| self.waitForSearch());
+        self.startSearch(gs, limits, listener);
+    }
+
+
+    /// True when no search is running
+    pub fn isDone(&self) -> bool {
+        self.search_thread.as_ref().is_none_or(|handle| handle.is_finished())
+    }
+
+
+    /// Best move of the last search as a move string, None while searching or without legal moves
+    #[pyo3(name = "bestMove")]
+    fn bestMoveStr(&mut self) -> Option<String> {
+        if !self.isDone() {
+            return None;
+        }
+        self.waitForSearch().map(|mv| mv.toMoveStr())
+    }
+
+
+    /// Block until the search finishes, other python threads keep running meanwhile
+    #[pyo3(name = "wait")]
+    fn waitPy(&mut self, py: Python) -> Option<String> {
+        py.allow_threads(|| self.waitForSearch()).map(|mv| mv.toMoveStr())
+    }
+
+
+    /// End the search early and return the best move of the last completed depth
+    #[pyo3(name = "stop")]
+    fn stopPy(&mut self, py: Python) -> Option<String> {
+        self.stop.set();
+        self.waitPy(py)
+    }
+
+
+    /// Python callable called with a SearchInfo after each completed iteration, used from the next start
+    #[setter(info_callback)]
+    fn setInfoCallback(&mut self, callback: Option<PyObject>) {
+        self.info_callback = callback;
+    }
+}
+
+
+impl AsyncSearch {
+    /// Search a game position on a new thread, must not be called while a search is running
+    pub fn startSearch(&mut self, gs: &GameState, limits: SearchLimits, listener: Option<Box<dyn SearchListener>>) {
+        let mut ctx: SearchContext = self.context.take().expect("search already running");
+        ctx.bmf.limits = limits;
+        ctx.bmf.listener = listener;
+        ctx.bmf.setGameHistory(gs.position.hashHistory());
+        self.best_move = None;
+        self.stop.clear();
+        let (bitboards, castle_rights, hash_key, whites_turn) = (gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        self.search_thread = Some(thread::spawn(move || {
+            ctx.bmf.searchPosition(&mut ctx.mm, &mut ctx.z, &ctx.tt, bitboards, castle_rights, hash_key, whites_turn);
+            ctx
+        }));
+    }
+
+
+    /// Block until the running search (if any) is done, returns the best move of the last search
+    pub fn waitForSearch(&mut self) -> Option<Move> {
+        if let Some(handle) = self.search_thread.take() {
+            let ctx: SearchContext = handle.join().expect("search thread panicked");
+            let best_move: Move = ctx.bmf.pv_table[0][0];
+            self.best_move = (best_move != Move::NULL).then_some(best_move);
+            self.context = Some(ctx);
+        }
+        self.best_move
+    }
+}
+
+
+impl Default for AsyncSearch {
+    fn default() -> Self {
+        AsyncSearch::new()
+    }
+}
+
+
+/// A dropped engine does not leave its search running
+impl Drop for AsyncSearch {
+    fn drop(&mut self) {
+        self.stop.set();
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{
+        Duration,
+        Instant,
+    };
+
+    #[test]
+    fn search_runs_in_background_until_stopped() {
+        let z: Zobrist = Zobrist::new();
+        let mut engine: AsyncSearch = AsyncSearch::new();
+        let mut gs: GameState = GameState::new(&z);
+        engine.startSearch(&gs, SearchLimits::new(SearchLimits::MAX_DEPTH, None, None, None, true), None);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!engine.isDone());
+        let start_time: Instant = Instant::now();
+        engine.stop.set();
+        assert!(engine.waitForSearch().is_some());
+        assert!(engine.isDone() && start_time.elapsed() < Duration::from_secs(2));
+
+        let m: Moves = Moves::new();
+        gs.importFEN(&m.masks, &z, String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")).unwrap();
+        engine.startSearch(&gs, SearchLimits::new(3, None, None, None, false), None);
+        assert!(engine.waitForSearch().unwrap().to_string() == "a1a8");
+        assert!(engine.bestMoveStr() == Some(String::from("7000")));
+    }
+}
@@ -2,124 +2,141 @@
 
 
 use pyo3::prelude::*;
-use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::sync::atomic::{
+    AtomicBool,
+    AtomicU64,
+    Ordering,
+};
 use std::time::{
     Duration,
     Instant,
 };
 use crate::{
+    chess_move::{
+        Move,
+        MoveList,
+    },
     moves::Moves,
     piece::Piece,
+    position::Position,
+    see::SEE_VALUES,
+    search_info::{
+        PyInfoCallback,
+        SearchInfo,
+        SearchListener,
+    },
+    search_limits::{
+        SearchLimits,
+        StopFlag,
+    },
+    time_manager::TimeManager,
     zobrist::Zobrist,
     trans_table::*,
+    eval_params::EvalParams,
+    eval_trace::{
+        EvalTerm,
+        EvalTrace,
+    },
 };
 
 
-#[pyclass(module = "ChessProject", get_all, set_all)]
+/// Formats the search progress can be reported in after each iteration
+#[pyclass(module = "ChessProject")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchOutput {
+    Console,
+    Uci,
+    XBoard,
+    Silent,
+}
+
+
+/// A root move with its score and principal variation
+#[derive(Clone, Debug, Default)]
+pub struct PvLine {
+    pub score: i32,
+    pub moves: Vec<Move>,
+}
+
+
+#[pyclass(module = "ChessProject")]
 pub struct BestMoveFinder {
-    search_depth: u32,
+    #[pyo3(get, set)]
+    pub limits: SearchLimits,
+    #[pyo3(get, set)]
     max_depth: u32,
+    #[pyo3(get, set)]
     mate_score: i32,
+    #[pyo3(get, set)]
     stale_score: i32,
-    move_counter: u32,
-    piece_scores: HashMap<char, i32>,
-    piece_position_scores: HashMap<char, [[i32; 8]; 8]>,
-    double_pawn_penalty: i32,
-    isolated_pawn_penalty: i32,
-    passed_pawn_bonus: [i32; 8],
-    semi_open_file_score: i32,
-    open_file_score: i32,
-    king_blocking_bonus: i32,
+    #[pyo3(get, set)]
+    pub move_counter: u32,
+    #[pyo3(get, set)]
+    pub eval_params: EvalParams, // evaluation weights, a copy is returned to python
+    #[pyo3(get, set)]
     mvv_lva: [[i32; 12]; 12], // [attacker][victim]
-    killer_moves: Vec<Vec<String>>, // [id][ply]
+    killer_moves: [[Move; 64]; 2], // [id][ply]
+    #[pyo3(get, set)]
     history_moves: [[i32; 64]; 12], // [piece][square]
-    pv_length: [u32; 64],
-    pv_table: Vec<Vec<String>>,
+    #[pyo3(get, set)]
+    pub pv_length: [u32; 64],
+    pub pv_table: [[Move; 64]; 64],
+    #[pyo3(get, set)]
     follow_pv: bool,
+    #[pyo3(get, set)]
     score_pv: bool,
+    #[pyo3(get, set)]
     full_depth_moves: u32,
+    #[pyo3(get, set)]
     reduction_limit: u32,
-    repetition_table: [u64; 1000],
-    repetition_idx: usize,
+    #[pyo3(get, set)]
+    pub repetition_table: [u64; 1000],
+    #[pyo3(get, set)]
+    pub repetition_idx: usize,
+    #[pyo3(get, set)]
+    pub output_mode: SearchOutput, // how search progress is reported
+    pub stop: Arc<AtomicBool>, // set by another thread to abort the search
+    pub listener: Option<Box<dyn SearchListener>>, // receives a SearchInfo after each iteration
+    #[pyo3(get, set)]
+    pub multi_pv: u32, // number of best root moves searched, each with its own PV
+    pub pv_lines: Vec<PvLine>, // best first, from the last completed iteration
+    excluded_root_moves: Vec<Move>, // root moves already given a PV line in the current iteration
+    #[pyo3(get, set)]
+    pub threads: u32, // search threads, the helpers only fill the shared transposition table
+    depth_offset: u32, // helper threads searching one ply deeper than the iteration depth
+    helper_nodes: Arc<AtomicU64>, // nodes of the Lazy SMP helpers, added by the helpers and read by the main thread
+    is_helper: bool,
+    published_nodes: u32, // part of move_counter a helper has already added to helper_nodes
+    null_move_allowed: bool, // false while verifying a null move cutoff
+    #[pyo3(get, set)]
+    pub quiescence_checks: bool, // try quiet checking moves at the first quiescence ply
+    seldepth: u32,
+    search_stopped: bool,
+    next_time_check: u32, // node count at which the clock is read next
 }
 
 
 #[pymethods]
 impl BestMoveFinder {
+    pub const TIME_CHECK_NODES: u32 = 2048; // nodes searched between reads of the clock
+    pub const NULL_MOVE_MIN_DEPTH: u32 = 3; // remaining depth needed to try a null move
+    pub const NULL_MOVE_VERIFY_DEPTH: u32 = 7; // remaining depth from which null move cutoffs are verified
+    pub const MAX_PHASE: i32 = 24; // game phase of the starting position, see gamePhase()
+
     #[new]
-    fn new(search_depth: u32) -> Self {
+    pub fn new(search_depth: u32) -> Self {
         BestMoveFinder {
-            search_depth: search_depth,
+            limits: SearchLimits {
+                time_manager: TimeManager::fixedTime(3000),
+                ..SearchLimits::new(search_depth, None, None, None, false)
+            },
             max_depth: 0,
             mate_score: 49000,
             stale_score: 0,
             move_counter: 0,
-            piece_scores: HashMap::from([
-                ('K', 10000),
-                ('Q', 1000),
-                ('R', 500),
-                ('B', 350),
-                ('N', 300),
-                ('P', 100),
-            ]),
-            piece_position_scores: HashMap::from([
-                ('K', [
-                    [0,   0,   0,   0,   0,   0,   0,   0],
-                    [0,   0,   5,   5,   5,   5,   0,   0],
-                    [0,   5,   5,  10,  10,   5,   5,   0],
-                    [0,   5,  10,  20,  20,  10,   5,   0],
-                    [0,   5,  10,  20,  20,  10,   5,   0],
-                    [0,   0,   5,  10,  10,   5,   0,   0],
-                    [0,   5,   5,  -5,  -5,   0,   5,   0],
-                    [0,   0,   5,   0, -15,   0,  10,   0],
-                ]),
-                ('R', [
-                    [50,  50,  50,  50,  50,  50,  50,  50],
-                    [50,  50,  50,  50,  50,  50,  50,  50],
-                    [ 0,   0,  10,  20,  20,  10,   0,   0],
-                    [ 0,   0,  10,  20,  20,  10,   0,   0],
-                    [ 0,   0,  10,  20,  20,  10,   0,   0],
-                    [ 0,   0,  10,  20,  20,  10,   0,   0],
-                    [ 0,   0,  10,  20,  20,  10,   0,   0],
-                    [ 0,   0,   0,  20,  20,   0,   0,   0],
-                ]),
-                ('B', [
-                    [0,   0,   0,   0,   0,   0,   0,   0],
-                    [0,   0,   0,   0,   0,   0,   0,   0],
-                    [0,   0,   0,  10,  10,   0,   0,   0],
-                    [0,   0,  10,  20,  20,  10,   0,   0],
-                    [0,   0,  10,  20,  20,  10,   0,   0],
-                    [0,  10,   5,  10,  10,   5,  10,   0],
-                    [0,  20,   5,   5,   5,   5,  20,   0],
-                    [0,   0, -10,   0,   0, -10,   0,   0],
-                ]),
-                ('N', [
-                    [-5,   0,   0,   0,   0,   0,   0,  -5],
-                    [-5,   0,   0,  10,  10,   0,   0,  -5],
-                    [-5,   5,  20,  20,  20,  20,   5,  -5],
-                    [-5,  10,  20,  30,  30,  20,  10,  -5],
-                    [-5,  10,  20,  30,  30,  20,  10,  -5],
-                    [-5,   5,  20,  10,  10,  20,   5,  -5],
-                    [-5,   0,   0,   0,   0,   0,   0,  -5],
-                    [-5, -10,   0,   0,   0,   0, -10,  -5],
-                ]),
-                ('P', [
-                    [90,  90,  90,  90,  90,  90,  90,  90],
-                    [30,  30,  30,  40,  40,  30,  30,  30],
-                    [20,  20,  20,  30,  30,  30,  20,  20],
-                    [15,  10,  10,  20,  20,  10,  10,  15],
-                    [10,   5,  10,  20,  20,   5,   5,  10],
-                    [ 5,   0,   0,   5,   5,   0,   0,   5],
-                    [ 0,   0,   0, -10, -10,   0,   0,   0],
-                    [ 0,   0,   0,   0,   0,   0,   0,   0],
-                ]),
-            ]),
-            double_pawn_penalty: -10,
-            isolated_pawn_penalty: -10,
-            passed_pawn_bonus: [0, 10, 30, 50, 75, 100, 150, 200],
-            semi_open_file_score: 10,
-            open_file_score: 15,
-            king_blocking_bonus: 5,
+            eval_params: EvalParams::new(),
             mvv_lva: [
                 // (Victims) Pawn Knight Bishop   Rook  Queen   King            (Attackers)
                 [105, 205, 305, 405, 505, 605,  105, 205, 305, 405, 505, 605], // Pawn
@@ -137,7 +154,7 @@ impl BestMoveFinder {
                 [100, 200, 300, 400, 500, 600,  100, 200, 300, 400, 500, 600],
             ],
             // killer & history moves
-            killer_moves: vec![vec![String::with_capacity(4); 64]; 2],
+            killer_moves: [[Move::NULL; 64]; 2],
             history_moves: [[0; 64]; 12],
             /*
                 ================================
@@ -161,7 +178,7 @@ impl BestMoveFinder {
                 5    0    0    0    0    0    m6
             */
             pv_length: [0; 64],
-            pv_table: vec![vec![String::with_capacity(4); 64]; 64],
+            pv_table: [[Move::NULL; 64]; 64],
             follow_pv: false,
             score_pv: false,
             // LMR
@@ -170,10 +187,81 @@ impl BestMoveFinder {
             // Repetition Detection
             repetition_table: [0; 1000],
             repetition_idx: 0,
+            // Search Control
+            output_mode: SearchOutput::Console,
+            stop: Arc::new(AtomicBool::new(false)),
+            listener: None,
+            multi_pv: 1,
+            pv_lines: Vec::new(),
+            excluded_root_moves: Vec::new(),
+            threads: 1,
+            depth_offset: 0,
+            helper_nodes: Arc::new(AtomicU64::new(0)),
+            is_helper: false,
+            published_nodes: 0,
+            null_move_allowed: true,
+            quiescence_checks: true,
+            seldepth: 0,
+            search_stopped: false,
+            next_time_check: 0,
         }
     }
 
 
+    /// PV table as 4 character move strings, empty strings where no move is stored
+    #[getter(pv_table)]
+    fn getPvTable(&self) -> Vec<Vec<String>> {
+        self.pv_table.iter()
+            .map(|line| line.iter().map(|mv| mv.toMoveStr()).collect())
+            .collect()
+    }
+
+
+    /*
+    Handle on the stop flag, setting it from another thread ends the running search.
+
+    The finder is borrowed while it searches, so from python the flag has to be
+    taken (or passed to searchPosition) before the search starts.
+    */
+    #[getter(stop_flag)]
+    fn getStopFlag(&self) -> StopFlag {
+        StopFlag {
+            flag: Arc::clone(&self.stop),
+        }
+    }
+
+
+    #[setter(stop_flag)]
+    fn setStopFlag(&mut self, stop_flag: StopFlag) {
+        self.stop = stop_flag.flag;
+    }
+
+
+    /// PV lines of the last completed iteration as (move, score, pv) in coordinate notation, best first
+    #[getter(pv_lines)]
+    fn getPvLines(&self) -> Vec<(String, i32, Vec<String>)> {
+        self.pv_lines.iter()
+            .map(|line| (line.moves[0].to_string(), line.score, SearchInfo::pvMoves(&line.moves)))
+            .collect()
+    }
+
+
+    /// Python callable called with a SearchInfo after each completed iteration, None to remove it
+    #[setter(info_callback)]
+    fn setInfoCallback(&mut self, callback: Option<PyObject>) {
+        self.listener = callback.map(|callback| Box::new(PyInfoCallback {callback}) as Box<dyn SearchListener>);
+    }
+
+
+    /// Killer moves as 4 character move strings, empty strings where no move is stored
+    #[getter(killer_moves)]
+    fn getKillerMoves(&self) -> Vec<Vec<String>> {
+        self.killer_moves.iter()
+            .map(|killers| killers.iter().map(|mv| mv.toMoveStr()).collect())
+            .collect()
+    }
+
+
     /// Detect if the current game state has been reached before
     fn isRepetition(&self, hash_key: u64) -> bool {
         for i in 0..self.repetition_idx {
@@ -185,84 +273,100 @@ impl BestMoveFinder {
     }
 
 
+    /// Load the hash keys of the positions played before the root so repetitions are detected
+    pub fn setGameHistory(&mut self, hash_keys: Vec<u64>) {
+        // the table must leave room for the search plies
+        let history: &[u64] = &hash_keys[hash_keys.len().saturating_sub(self.repetition_table.len() / 2)..];
+        self.repetition_table[..history.len()].copy_from_slice(history);
+        self.repetition_idx = history.len();
+    }
+
+
     /*
-    Populates the PV table with the best move sequence.
-    Follows an iterative deepening framework.
+    Python entry point of searchPosition, other python threads keep running while searching.
+
+    A stop_flag given here replaces the finder's flag, setting it from another
+    thread ends this search and later ones. The flag is cleared first so a search
+    cancelled earlier does not end this one, it is done while holding the GIL so a
+    stop from another python thread is never lost.
     */
-    fn searchPosition(
+    #[pyo3(name = "searchPosition", signature = (mm, z, tt, bitboards, castle_rights, hash_key, whites_turn, stop_flag=None))]
+    fn searchPositionPy(
         &mut self,
+        py: Python,
         mm: &mut Moves,
         z: &mut Zobrist,
-        tt: &mut TransTable,
+        tt: &TransTable,
         bitboards: [u64; 13],
         castle_rights: [bool; 4],
         hash_key: u64,
         whites_turn: bool,
+        stop_flag: Option<StopFlag>,
     ) {
-        self.pv_length = [0; 64];
-        self.pv_table = vec![vec![String::with_capacity(4); 64]; 64];
-        self.follow_pv = false; self.score_pv = false;
-        let start_time: Instant = Instant::now();
-        self.repetition_idx += 1;
-        self.move_counter = 0;
-
-        // iterative deepening
-        for current_depth in 1..=self.search_depth {
-            // enable PV following
-            self.follow_pv = true;
-            self.max_depth = current_depth;
-            let score: i32 = self.negaMaxAlphaBeta(-50000, 50000, mm, z, tt, bitboards, castle_rights, hash_key, whites_turn, 0);
-            if score >= -49000 && score < -48000 {
-                println!("Depth: {}, Move: {}, Score: {}, Mate in {}", self.max_depth, move_to_algebra!(self.pv_table[0][0]), score, (score + 49000) / 2 + 1);
-            } else if score <= 49000 && score > 48000 {
-                println!("Depth: {}, Move: {}, Score: {}, Mate in {}", self.max_depth, move_to_algebra!(self.pv_table[0][0]), score, (49000 - score) / 2 + 1);
-            } else {
-                println!("Depth: {}, Move: {}, Score: {}", self.max_depth, move_to_algebra!(self.pv_table[0][0]), score);
-            }
-            println!("Total moves analyzed: {}, Duration: {:?}", self.move_counter, start_time.elapsed());
-            print!("Best Move Sequence: ");
-            for depth in 0..(self.pv_length[0]) {
-                print!("{:?} ", move_to_algebra!(self.pv_table[0][depth as usize]));
-            }
-            println!("\n");
-            if start_time.elapsed() > Duration::from_secs(3) {
-                break
-            }
+        if let Some(stop_flag) = stop_flag {
+            self.setStopFlag(stop_flag);
         }
+        self.stop.store(false, Ordering::Relaxed);
+        py.allow_threads(|| self.searchPosition(mm, z, tt, bitboards, castle_rights, hash_key, whites_turn));
     }
 
 
-    /// Infinite depth search that stops when no attacking (non-quiet) moves left
+    /*
+    Infinite depth search that stops when no attacking (non-quiet) moves left.
+
+    When in check every evasion is searched and there is no stand pat, so mates
+    at the horizon are found. With quiescence_checks, quiet checking moves are
+    also tried at the first quiescence ply.
+    */
     fn quiescenceSearch(
         &mut self,
         mut alpha: i32,
         beta: i32,
         mm: &mut Moves,
         z: &mut Zobrist,
-        bitboards: [u64; 13],
-        castle_rights: [bool; 4],
-        hash_key: u64,
-        whites_turn: bool,
+        tt: &TransTable,
+        pos: &mut Position,
         depth: u32,
+        try_checks: bool,
     ) -> i32 {
         // look deeper for non-quiet moves (attacking)
         self.move_counter += 1;
-        let eval: i32 = (if whites_turn {1} else {-1}) * self.evaluateBoard(mm, bitboards);
-        if eval >= beta {
-            return beta;
-        }
-        if eval > alpha {
-            alpha = eval;
-        }
-        let mut moves: String = mm.getPossibleMoves(bitboards, castle_rights, whites_turn);
-        moves = self.sortMoves(mm, z, &moves, bitboards, hash_key, whites_turn, depth);
-        for i in (0..moves.len()).step_by(4) {
-            let (bitboards_t, hash_key_t) = mm.getUpdatedBitboards(z, &moves[i..i+4], bitboards, hash_key, whites_turn);
-            let (castle_rights_t, hash_key_t) = mm.getUpdatedCastleRights(z, &moves[i..i+4], castle_rights, bitboards, hash_key_t);
-            if mm.isAttackingMove(bitboards, bitboards_t, whites_turn) {
+        self.seldepth = self.seldepth.max(depth);
+        let in_check: bool = mm.isKingAttacked(pos.bitboards, pos.whites_turn);
+        let eval: i32 = (if pos.whites_turn {1} else {-1}) * self.evaluateBoard(mm, pos.bitboards);
+        if depth >= 63 {
+            return eval; // prevent killer table overflow
+        }
+        if !in_check {
+            if eval >= beta {
+                return beta;
+            }
+            if eval > alpha {
+                alpha = eval;
+            }
+        }
+        let hash_move: Move = tt.probe(pos.hash_key).map_or(Move::NULL, |entry| entry.best_move);
+        let mut moves: MoveList = mm.getPossibleMoves(pos.bitboards, pos.castle_rights, pos.whites_turn);
+        moves = self.sortMoves(mm, z, &moves, pos, depth, hash_move);
+        if in_check && moves.is_empty() {
+            return -self.mate_score + depth as i32;
+        }
+        for &mv in moves.iter() {
+            let quiet_check_candidate: bool = !in_check && !mv.isCapture() && try_checks;
+            if !in_check && mv.isCapture() && mm.see(pos.bitboards, mv) < 0 {
+                continue; // losing captures are assumed not to beat the stand pat score
+            }
+            if in_check || mv.isCapture() || quiet_check_candidate {
                 self.repetition_idx += 1;
-                self.repetition_table[self.repetition_idx] = hash_key;
-                let score: i32 = -self.quiescenceSearch(-beta, -alpha, mm, z, bitboards_t, castle_rights_t, hash_key_t, !whites_turn, depth+1);
+                self.repetition_table[self.repetition_idx] = pos.hash_key;
+                pos.makeMove(z, mv);
+                if quiet_check_candidate && !mm.isKingAttacked(pos.bitboards, pos.whites_turn) {
+                    pos.unmakeMove();
+                    self.repetition_idx -= 1;
+                    continue;
+                }
+                let score: i32 = -self.quiescenceSearch(-beta, -alpha, mm, z, tt, pos, depth+1, false);
+                pos.unmakeMove();
                 self.repetition_idx -= 1;
                 if score >= beta {
                     return beta;
@@ -279,11 +383,18 @@ impl BestMoveFinder {
     /*
     Performs the base negaMax search with the following optimization techniques:
         - Alpha-Beta pruning
-        - PV / killer / history move ordering
+        - Hash / PV / killer / history move ordering
         - PVS (Principle Variation Search)
         - LMR (Late Move Reduction)
-        - NMP (Null Move Pruning) but not activated
+        - NMP (Null Move Pruning) with verification of deep cutoffs
+        - Check extensions
         - Transposition Table
+        - Abort on external stop request or when the node or hard time limit is reached
+
+    depth is the distance from the root in plies, the PV table and mate scores use it.
+    reduction holds the plies cut by LMR and null moves minus the plies added by
+    check extensions on the way to this node, it only changes the remaining depth
+    so mate distances stay exact.
     */
     fn negaMaxAlphaBeta(
         &mut self,
@@ -291,85 +402,126 @@ impl BestMoveFinder {
         beta: i32,
         mm: &mut Moves,
         z: &mut Zobrist,
-        tt: &mut TransTable,
-        bitboards: [u64; 13],
-        castle_rights: [bool; 4],
-        hash_key: u64,
-        whites_turn: bool,
+        tt: &TransTable,
+        pos: &mut Position,
         depth: u32,
+        mut reduction: i32,
     ) -> i32 {
+        let (bitboards, hash_key, whites_turn) = (pos.bitboards, pos.hash_key, pos.whites_turn);
+        // stop request or a limit reached, first iteration always completes so a move is available
+        if self.max_depth > 1 && self.stop.load(Ordering::Relaxed) {
+            self.search_stopped = true;
+        }
+        if self.max_depth > 1 && self.limits.nodes.is_some_and(|nodes| self.move_counter as u64 >= nodes) {
+            self.search_stopped = true;
+        }
+        if self.max_depth > 1 && self.move_counter >= self.next_time_check {
+            self.next_time_check = self.move_counter + BestMoveFinder::TIME_CHECK_NODES;
+            self.search_stopped |= self.limits.time_manager.hardLimitReached();
+            self.publishNodes();
+        }
+        if self.search_stopped {
+            return 0;
+        }
         if depth > 0 && self.isRepetition(hash_key) {
             return 0; // draw score
         }
-        let table_score: i32 = tt.readEntry(alpha, beta, hash_key, self.max_depth as i32 - depth as i32, depth);
+        // check extension: search one ply deeper when in check so forcing lines are not cut at the horizon
+        let in_check: bool = mm.isKingAttacked(bitboards, whites_turn);
+        if in_check && depth < 2 * self.max_depth {
+            reduction -= 1;
+        }
+        let (table_score, hash_move): (i32, Move) = tt.readEntry(alpha, beta, hash_key, self.max_depth as i32 - depth as i32 - reduction, depth);
         let is_pv_node: bool = beta - alpha > 1;
         if table_score != TransTable::NO_HASH_ENTRY && !is_pv_node {
             return table_score; // board state searched before
         }
         let mut hash_flag: HashFlag = HashFlag::Alpha;
+        let mut best_move: Move = Move::NULL; // stored in the table for move ordering
         // init current depths PV table entry length
         self.pv_length[depth as usize] = depth;
-        if depth >= self.max_depth {
-            return self.quiescenceSearch(alpha, beta, mm, z, bitboards, castle_rights, hash_key, whites_turn, depth+1);
+        self.seldepth = self.seldepth.max(depth);
+        if depth as i32 + reduction >= self.max_depth as i32 {
+            return self.quiescenceSearch(alpha, beta, mm, z, tt, pos, depth, self.quiescence_checks);
         }
-        if depth >= 64 {
-            // prevent PV table overflow
+        if depth >= 63 {
+            // prevent PV table overflow, extensions can go past the iteration depth
             self.move_counter += 1;
             return (if whites_turn {1} else {-1}) * self.evaluateBoard(mm, bitboards);
         }
         self.move_counter += 1;
 
 
-        // null move pruning: problems arise from wrong mating depth
-        // if self.max_depth - depth >= 3 && !mm.isKingAttacked(bitboards, whites_turn) && depth > 0 {
-        //     self.repetition_idx += 1;
-        //     self.repetition_table[self.repetition_idx] = hash_key;
-
-        //     let mut hash_key_t: u64 = hash_key;
-        //     let mut bitboards_t: [u64; 13] = bitboards;
-        //     if bitboards[Piece::EP] != 0 {
-        //         let col: usize = bitboards[Piece::EP].leading_zeros() as usize;
-        //         let row: usize = if whites_turn {2} else {5};
-        //         hash_key_t ^= z.enpassant_keys[row * 8 + col];
-        //     }
-        //     bitboards_t[Piece::EP] = 0;
-        //     hash_key_t ^= z.side_key;
-
-        //     // search moves with reduced depth to find beta cutoff
-        //     let score: i32 = -self.negaMaxAlphaBeta(-beta, -beta+1, mm, z, tt, bitboards_t, castle_rights, hash_key_t, !whites_turn, depth+1+2);
-
-        //     self.repetition_idx -= 1;
-        //     if score >= beta {
-        //         return beta;
-        //     }
-        // }
+        // null move pruning: pass the turn, if a reduced search still fails high the node is cut
+        let remaining_depth: u32 = (self.max_depth as i32 - depth as i32 - reduction) as u32;
+        if self.null_move_allowed
+            && !is_pv_node
+            && depth > 0
+            && remaining_depth >= BestMoveFinder::NULL_MOVE_MIN_DEPTH
+            && pos.lastMove() != Some(Move::NULL)
+            && !in_check
+            && BestMoveFinder::hasNonPawnMaterial(bitboards, whites_turn)
+        {
+            // adaptive reduction: reduce more with more depth left
+            let null_reduction: i32 = if remaining_depth > 6 {3} else {2};
+            self.repetition_idx += 1;
+            self.repetition_table[self.repetition_idx] = hash_key;
+            pos.makeNullMove(z);
+            let score: i32 = -self.negaMaxAlphaBeta(-beta, -beta+1, mm, z, tt, pos, depth+1, reduction+null_reduction);
+            pos.unmakeMove();
+            self.repetition_idx -= 1;
+            if self.search_stopped {
+                return 0;
+            }
+            if score >= beta {
+                /*
+                Verify deep cutoffs by searching this node at the reduced depth without
+                null moves, in zugzwang passing is the best move and the cutoff is wrong.
+                */
+                if remaining_depth < BestMoveFinder::NULL_MOVE_VERIFY_DEPTH {
+                    return beta; // a null move score is a bound, never a proven mate
+                }
+                self.null_move_allowed = false;
+                let verified_score: i32 = self.negaMaxAlphaBeta(beta-1, beta, mm, z, tt, pos, depth, reduction+null_reduction);
+                self.null_move_allowed = true;
+                if self.search_stopped {
+                    return 0;
+                }
+                if verified_score >= beta {
+                    return beta;
+                }
+            }
+        }
 
 
         let mut best_score: i32 = -self.mate_score;
-        let mut moves: String = mm.getPossibleMoves(bitboards, castle_rights, whites_turn);
+        let mut moves: MoveList = mm.getPossibleMoves(bitboards, pos.castle_rights, whites_turn);
         if self.follow_pv {
             // now following PV line so enable PV move scoring
             self.enablePVScoring(&moves, depth);
         }
-        moves = self.sortMoves(mm, z, &moves, bitboards, hash_key, whites_turn, depth);
+        moves = self.sortMoves(mm, z, &moves, pos, depth, hash_move);
         let mut moves_searched: u32 = 0;
         let mut valid_move_found: bool = false;
-        for i in (0..moves.len()).step_by(4) {
-            let (bitboards_t, hash_key_t) = mm.getUpdatedBitboards(z, &moves[i..i+4], bitboards, hash_key, whites_turn);
-            let (castle_rights_t, hash_key_t) = mm.getUpdatedCastleRights(z, &moves[i..i+4], castle_rights, bitboards, hash_key_t);
+        for &mv in moves.iter() {
+            if depth == 0 && self.excluded_root_moves.contains(&mv) {
+                valid_move_found = true; // still a valid move for mate and stalemate detection
+                continue;
+            }
             self.repetition_idx += 1;
             self.repetition_table[self.repetition_idx] = hash_key;
+            pos.makeMove(z, mv);
             valid_move_found = true;
             let mut score: i32;
 
             if moves_searched == 0 {
                 // normal alpha beta search (full depth)
-                score = -self.negaMaxAlphaBeta(-beta, -alpha, mm, z, tt, bitboards_t, castle_rights_t, hash_key_t, !whites_turn, depth+1);
+                score = -self.negaMaxAlphaBeta(-beta, -alpha, mm, z, tt, pos, depth+1, reduction);
             } else {
                 // consider Late Move Reduction (LMR)
-                if moves_searched >= self.full_depth_moves && depth >= self.reduction_limit && !mm.isAttackingMove(bitboards, bitboards_t, whites_turn) && moves[i..i+4].chars().nth(3).unwrap() != 'P' {
+                if moves_searched >= self.full_depth_moves && depth >= self.reduction_limit && !mv.isCapture() && !mv.isPromotion() {
                     // search current move with reduced depth
-                    score = -self.negaMaxAlphaBeta(-alpha-1, -alpha, mm, z, tt, bitboards_t, castle_rights_t, hash_key_t, !whites_turn, depth+2);
+                    score = -self.negaMaxAlphaBeta(-alpha-1, -alpha, mm, z, tt, pos, depth+1, reduction+1);
                 } else {
                     score = alpha + 1; // ensure full-depth search is done if no LMR
                 }
@@ -381,7 +533,7 @@ impl BestMoveFinder {
                     It's possible to do this a bit faster than a search that worries that one
                     of the remaining moves might be good.
                     */
-                    score = -self.negaMaxAlphaBeta(-alpha-1, -alpha, mm, z, tt, bitboards_t, castle_rights_t, hash_key_t, !whites_turn, depth+1);
+                    score = -self.negaMaxAlphaBeta(-alpha-1, -alpha, mm, z, tt, pos, depth+1, reduction);
                     /*
                     If the algorithm finds out that it was wrong, and that one of the
                     subsequent moves was better than the first PV move, it has to search again,
@@ -391,12 +543,16 @@ impl BestMoveFinder {
                     */
                     if score > alpha && score < beta {
                         // LMR fails, re-search at full depth and full window
-                        score = -self.negaMaxAlphaBeta(-beta, -alpha, mm, z, tt, bitboards_t, castle_rights_t, hash_key_t, !whites_turn, depth+1);
+                        score = -self.negaMaxAlphaBeta(-beta, -alpha, mm, z, tt, pos, depth+1, reduction);
                     }
                 }
             }
 
+            pos.unmakeMove();
             self.repetition_idx -= 1;
+            if self.search_stopped {
+                return 0; // score of an aborted subtree is meaningless
+            }
             moves_searched += 1;
 
             if score > best_score {
@@ -407,37 +563,37 @@ impl BestMoveFinder {
                 hash_flag = HashFlag::Exact;
 
                 // write history move if quiet move
-                let (attacker, victim) = get_move_pieces!(bitboards, moves[i..i+4]);
+                let (attacker, victim) = get_move_pieces!(bitboards, mv);
                 if victim == Piece::EP {
-                    let (_, _, r2, c2) = move_to_u32s!(moves[i..i+4]);
-                    self.history_moves[attacker][(r2 * 8 + c2) as usize] += (self.max_depth - depth) as i32;
+                    self.history_moves[attacker][mv.endSquare() as usize] += remaining_depth as i32;
                 }
 
                 alpha = best_score;
+                best_move = mv;
                 // write PV move to table
-                self.pv_table[depth as usize][depth as usize] = moves[i..i+4].to_string();
+                self.pv_table[depth as usize][depth as usize] = mv;
                 // loop over the next depth in table to propagate next moves up a row
                 for next_depth in (depth+1)..self.pv_length[(depth+1) as usize] {
                     // copy move from deeper depth into a current depth's line
-                    self.pv_table[depth as usize][next_depth as usize] = self.pv_table[(depth+1) as usize][next_depth as usize].clone();
+                    self.pv_table[depth as usize][next_depth as usize] = self.pv_table[(depth+1) as usize][next_depth as usize];
                 }
                 // adjust PV table length to account for propagated values in current depth row
                 self.pv_length[depth as usize] = self.pv_length[(depth+1) as usize];
             }
             if alpha >= beta {
-                tt.writeEntry(beta, hash_key, self.max_depth - depth, depth, HashFlag::Beta as i32);
+                tt.writeEntry(beta, hash_key, remaining_depth, depth, HashFlag::Beta as i32, mv);
 
                 // write killer moves if quiet move
-                if get_move_pieces!(bitboards, moves[i..i+4]).1 == Piece::EP {
-                    self.killer_moves[1][depth as usize] = self.killer_moves[0][depth as usize].clone();
-                    self.killer_moves[0][depth as usize] = moves[i..i+4].to_string();
+                if get_move_pieces!(bitboards, mv).1 == Piece::EP {
+                    self.killer_moves[1][depth as usize] = self.killer_moves[0][depth as usize];
+                    self.killer_moves[0][depth as usize] = mv;
                 }
 
                 return beta;
             }
         }
         if !valid_move_found {
-            if mm.isKingAttacked(bitboards, whites_turn) {
+            if in_check {
                 mm.checkmate = true;
                 return -self.mate_score + depth as i32;
             } else {
@@ -448,7 +604,10 @@ impl BestMoveFinder {
             mm.checkmate = false;
             mm.stalemate = false;
         }
-        tt.writeEntry(alpha, hash_key, self.max_depth - depth, depth, hash_flag as i32);
+        // a root score with excluded moves is not the score of the position
+        if depth > 0 || self.excluded_root_moves.is_empty() {
+            tt.writeEntry(alpha, hash_key, remaining_depth, depth, hash_flag as i32, best_move);
+        }
         alpha
     }
 
@@ -461,61 +620,77 @@ impl BestMoveFinder {
         - Semi / Open File
         - Bishop / Queen mobility
         - King saftey
+
+    Piece values and positions have a middlegame and an endgame score, the two are
+    interpolated by the game phase so e.g. the king moves from shelter to the centre
+    as material comes off. King safety only counts in the middlegame.
     */
-    fn evaluateBoard(&self, mm: &mut Moves, bitboards: [u64; 13]) -> i32 {
+    pub fn evaluateBoard(&self, mm: &mut Moves, bitboards: [u64; 13]) -> i32 {
+        self.traceEvaluation(mm, bitboards).total()
+    }
+
+
+    /// Evaluate the board with the score of each term kept per side, see evaluateBoard()
+    pub fn traceEvaluation(&self, mm: &mut Moves, bitboards: [u64; 13]) -> EvalTrace {
         // TODO better way to do doubled pawns with shifting
-        let mut score: i32 = 0;
+        let params: &EvalParams = &self.eval_params;
+        let mut trace: EvalTrace = EvalTrace::default();
         for i in 0..64 {
             if get_bit!(bitboards[Piece::WP], i) == 1 {
-                score += self.piece_scores[&'P'] + self.piece_position_scores[&'P'][i / 8][i % 8];
+                trace.addTapered(EvalTerm::Material, true, params.piece_scores[&'P'], params.piece_scores_eg[&'P']);
+                trace.addTapered(EvalTerm::PieceSquare, true, params.piece_position_scores[&'P'][i / 8][i % 8], params.piece_position_scores_eg[&'P'][i / 8][i % 8]);
                 // double/isolated/past pawn scoring
                 let double_pawns: i32 = (bitboards[Piece::WP] & mm.masks.file_masks[i % 8]).count_ones() as i32;
                 if double_pawns > 1 {
-                    score += double_pawns * self.double_pawn_penalty;
+                    trace.add(EvalTerm::DoubledPawns, true, double_pawns * params.double_pawn_penalty);
                 }
                 if bitboards[Piece::WP] & mm.masks.isolated_masks[i % 8] == 0 {
-                    score += self.isolated_pawn_penalty;
+                    trace.add(EvalTerm::IsolatedPawns, true, params.isolated_pawn_penalty);
                 }
                 if bitboards[Piece::BP] & mm.masks.w_passed_pawn_masks[i] == 0 {
-                    score += self.passed_pawn_bonus[7 - (i / 8)];
+                    trace.add(EvalTerm::PassedPawns, true, params.passed_pawn_bonus[7 - (i / 8)]);
                 }
             }
             if get_bit!(bitboards[Piece::WN], i) == 1 {
-                score += self.piece_scores[&'N'] + self.piece_position_scores[&'N'][i / 8][i % 8];
+                trace.addTapered(EvalTerm::Material, true, params.piece_scores[&'N'], params.piece_scores_eg[&'N']);
+                trace.addTapered(EvalTerm::PieceSquare, true, params.piece_position_scores[&'N'][i / 8][i % 8], params.piece_position_scores_eg[&'N'][i / 8][i % 8]);
             }
             if get_bit!(bitboards[Piece::WB], i) == 1 {
-                score += self.piece_scores[&'B'] + self.piece_position_scores[&'B'][i / 8][i % 8];
+                trace.addTapered(EvalTerm::Material, true, params.piece_scores[&'B'], params.piece_scores_eg[&'B']);
+                trace.addTapered(EvalTerm::PieceSquare, true, params.piece_position_scores[&'B'][i / 8][i % 8], params.piece_position_scores_eg[&'B'][i / 8][i % 8]);
                 // bishop mobility scoring
                 mm.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
-                score += (mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::WB].leading_zeros() as usize)
-                    & !or_array_elems!(Piece::whitePiecesWithEnemyKing(), bitboards)).count_ones() as i32; // avoid illegal bK capture
+                trace.add(EvalTerm::Mobility, true, (mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::WB].leading_zeros() as usize)
+                    & !or_array_elems!(Piece::whitePiecesWithEnemyKing(), bitboards)).count_ones() as i32); // avoid illegal bK capture
             }
             if get_bit!(bitboards[Piece::WR], i) == 1 {
-                score += self.piece_scores[&'R'] + self.piece_position_scores[&'R'][i / 8][i % 8];
+                trace.addTapered(EvalTerm::Material, true, params.piece_scores[&'R'], params.piece_scores_eg[&'R']);
+                trace.addTapered(EvalTerm::PieceSquare, true, params.piece_position_scores[&'R'][i / 8][i % 8], params.piece_position_scores_eg[&'R'][i / 8][i % 8]);
                 // rook semi/open file scoring
                 if bitboards[Piece::WP] & mm.masks.file_masks[i % 8] == 0 {
-                    score += self.semi_open_file_score;
+                    trace.add(EvalTerm::OpenFiles, true, params.semi_open_file_score);
                 }
                 if or_array_elems!([Piece::WP, Piece::BP], bitboards) & mm.masks.file_masks[i % 8] == 0 {
-                    score += self.open_file_score;
+                    trace.add(EvalTerm::OpenFiles, true, params.open_file_score);
                 }
             }
             if get_bit!(bitboards[Piece::WQ], i) == 1 {
-                score += self.piece_scores[&'Q'];
+                trace.addTapered(EvalTerm::Material, true, params.piece_scores[&'Q'], params.piece_scores_eg[&'Q']);
                 // queen mobility scoring
                 mm.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
-                score += ((mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::WQ].leading_zeros() as usize)
+                trace.add(EvalTerm::Mobility, true, ((mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::WQ].leading_zeros() as usize)
                     | (mm.possibleHAndVMoves(bitboards[Piece::WQ].leading_zeros() as usize)))
-                    & !or_array_elems!(Piece::whitePiecesWithEnemyKing(), bitboards)).count_ones() as i32; // avoid illegal bK capture
+                    & !or_array_elems!(Piece::whitePiecesWithEnemyKing(), bitboards)).count_ones() as i32); // avoid illegal bK capture
             }
             if get_bit!(bitboards[Piece::WK], i) == 1 {
-                score += self.piece_scores[&'K'] + self.piece_position_scores[&'K'][i / 8][i % 8];
+                trace.addTapered(EvalTerm::Material, true, params.piece_scores[&'K'], params.piece_scores_eg[&'K']);
+                trace.addTapered(EvalTerm::PieceSquare, true, params.piece_position_scores[&'K'][i / 8][i % 8], params.piece_position_scores_eg[&'K'][i / 8][i % 8]);
                 // king semi/open file scoring
                 if bitboards[Piece::WP] & mm.masks.file_masks[i % 8] == 0 {
-                    score -= self.semi_open_file_score;
+                    trace.addTapered(EvalTerm::KingShelter, true, -params.semi_open_file_score, 0);
                 }
                 if or_array_elems!([Piece::WP, Piece::BP], bitboards) & mm.masks.file_masks[i % 8] == 0 {
-                    score -= self.open_file_score;
+                    trace.addTapered(EvalTerm::KingShelter, true, -params.open_file_score, 0);
                 }
                 // king protection scoring
                 let king_idx: usize = bitboards[Piece::WK].leading_zeros() as usize;
@@ -533,58 +708,64 @@ impl BestMoveFinder {
                 } else {
                     pop_bits!(moves, mm.masks.file_ab);
                 }
-                score += self.king_blocking_bonus * (moves & or_array_elems!(Piece::whitePiecesNoKing(), bitboards)).count_ones() as i32;
+                let blockers: i32 = (moves & or_array_elems!(Piece::whitePiecesNoKing(), bitboards)).count_ones() as i32;
+                trace.addTapered(EvalTerm::KingShelter, true, params.king_blocking_bonus * blockers, 0);
             }
             if get_bit!(bitboards[Piece::BP], i) == 1 {
-                score -= self.piece_scores[&'P'] + self.piece_position_scores[&'P'][7 - (i / 8)][i % 8];
+                trace.addTapered(EvalTerm::Material, false, params.piece_scores[&'P'], params.piece_scores_eg[&'P']);
+                trace.addTapered(EvalTerm::PieceSquare, false, params.piece_position_scores[&'P'][7 - (i / 8)][i % 8], params.piece_position_scores_eg[&'P'][7 - (i / 8)][i % 8]);
                 // double/isolated/past pawn scoring
                 let double_pawns: i32 = (bitboards[Piece::BP] & mm.masks.file_masks[i % 8]).count_ones() as i32;
                 if double_pawns > 1 {
-                    score -= double_pawns * self.double_pawn_penalty;
+                    trace.add(EvalTerm::DoubledPawns, false, double_pawns * params.double_pawn_penalty);
                 }
                 if bitboards[Piece::BP] & mm.masks.isolated_masks[i % 8] == 0 {
-                    score -= self.isolated_pawn_penalty;
+                    trace.add(EvalTerm::IsolatedPawns, false, params.isolated_pawn_penalty);
                 }
                 if bitboards[Piece::WP] & mm.masks.b_passed_pawn_masks[i] == 0 {
-                    score -= self.passed_pawn_bonus[i / 8];
+                    trace.add(EvalTerm::PassedPawns, false, params.passed_pawn_bonus[i / 8]);
                 }
             }
             if get_bit!(bitboards[Piece::BN], i) == 1 {
-                score -= self.piece_scores[&'N'] + self.piece_position_scores[&'N'][7 - (i / 8)][i % 8];
+                trace.addTapered(EvalTerm::Material, false, params.piece_scores[&'N'], params.piece_scores_eg[&'N']);
+                trace.addTapered(EvalTerm::PieceSquare, false, params.piece_position_scores[&'N'][7 - (i / 8)][i % 8], params.piece_position_scores_eg[&'N'][7 - (i / 8)][i % 8]);
             }
             if get_bit!(bitboards[Piece::BB], i) == 1 {
-                score -= self.piece_scores[&'B'] + self.piece_position_scores[&'B'][7 - (i / 8)][i % 8];
+                trace.addTapered(EvalTerm::Material, false, params.piece_scores[&'B'], params.piece_scores_eg[&'B']);
+                trace.addTapered(EvalTerm::PieceSquare, false, params.piece_position_scores[&'B'][7 - (i / 8)][i % 8], params.piece_position_scores_eg[&'B'][7 - (i / 8)][i % 8]);
                 // bishop mobility scoring
                 mm.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
-                score -= (mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::BB].leading_zeros() as usize)
-                    & !or_array_elems!(Piece::blackPiecesWithEnemyKing(), bitboards)).count_ones() as i32; // avoid illegal bK capture
+                trace.add(EvalTerm::Mobility, false, (mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::BB].leading_zeros() as usize)
+                    & !or_array_elems!(Piece::blackPiecesWithEnemyKing(), bitboards)).count_ones() as i32); // avoid illegal bK capture
             }
             if get_bit!(bitboards[Piece::BR], i) == 1 {
-                score -= self.piece_scores[&'R'] + self.piece_position_scores[&'R'][7 - (i / 8)][i % 8];
+                trace.addTapered(EvalTerm::Material, false, params.piece_scores[&'R'], params.piece_scores_eg[&'R']);
+                trace.addTapered(EvalTerm::PieceSquare, false, params.piece_position_scores[&'R'][7 - (i / 8)][i % 8], params.piece_position_scores_eg[&'R'][7 - (i / 8)][i % 8]);
                 // rook semi/open file scoring
                 if bitboards[Piece::BP] & mm.masks.file_masks[i % 8] == 0 {
-                    score -= self.semi_open_file_score;
+                    trace.add(EvalTerm::OpenFiles, false, params.semi_open_file_score);
                 }
                 if or_array_elems!([Piece::WP, Piece::BP], bitboards) & mm.masks.file_masks[i % 8] == 0 {
-                    score -= self.open_file_score;
+                    trace.add(EvalTerm::OpenFiles, false, params.open_file_score);
                 }
             }
             if get_bit!(bitboards[Piece::BQ], i) == 1 {
-                score -= self.piece_scores[&'Q'];
+                trace.addTapered(EvalTerm::Material, false, params.piece_scores[&'Q'], params.piece_scores_eg[&'Q']);
                 // queen mobility scoring
                 mm.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
-                score -= ((mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::BQ].leading_zeros() as usize)
+                trace.add(EvalTerm::Mobility, false, ((mm.possibleDiagAndAntiDiagMoves(bitboards[Piece::BQ].leading_zeros() as usize)
                     | (mm.possibleHAndVMoves(bitboards[Piece::BQ].leading_zeros() as usize)))
-                    & !or_array_elems!(Piece::blackPiecesWithEnemyKing(), bitboards)).count_ones() as i32; // avoid illegal bK capture
+                    & !or_array_elems!(Piece::blackPiecesWithEnemyKing(), bitboards)).count_ones() as i32); // avoid illegal bK capture
             }
             if get_bit!(bitboards[Piece::BK], i) == 1 {
-                score -= self.piece_scores[&'K'] + self.piece_position_scores[&'K'][7 - (i / 8)][i % 8];
+                trace.addTapered(EvalTerm::Material, false, params.piece_scores[&'K'], params.piece_scores_eg[&'K']);
+                trace.addTapered(EvalTerm::PieceSquare, false, params.piece_position_scores[&'K'][7 - (i / 8)][i % 8], params.piece_position_scores_eg[&'K'][7 - (i / 8)][i % 8]);
                 // king semi/open file scoring
                 if bitboards[Piece::BP] & mm.masks.file_masks[i % 8] == 0 {
-                    score += self.semi_open_file_score;
+                    trace.addTapered(EvalTerm::KingShelter, false, -params.semi_open_file_score, 0);
                 }
                 if or_array_elems!([Piece::WP, Piece::BP], bitboards) & mm.masks.file_masks[i % 8] == 0 {
-                    score += self.open_file_score;
+                    trace.addTapered(EvalTerm::KingShelter, false, -params.open_file_score, 0);
                 }
                 // king protection scoring
                 let king_idx: usize = bitboards[Piece::BK].leading_zeros() as usize;
@@ -602,20 +783,213 @@ impl BestMoveFinder {
                 } else {
                     pop_bits!(moves, mm.masks.file_ab);
                 }
-                score -= self.king_blocking_bonus * (moves & or_array_elems!(Piece::blackPiecesNoKing(), bitboards)).count_ones() as i32;
+                let blockers: i32 = (moves & or_array_elems!(Piece::blackPiecesNoKing(), bitboards)).count_ones() as i32;
+                trace.addTapered(EvalTerm::KingShelter, false, params.king_blocking_bonus * blockers, 0);
+            }
+        }
+        trace.phase = self.gamePhase(bitboards);
+        trace
+    }
+
+
+    /*
+    Game phase from the non-pawn material left on the board, MAX_PHASE with the
+    starting material (or more after promotions) down to 0 with only kings and pawns
+    */
+    fn gamePhase(&self, bitboards: [u64; 13]) -> i32 {
+        let pieces: [(char, Piece, Piece); 4] = [
+            ('N', Piece::WN, Piece::BN),
+            ('B', Piece::WB, Piece::BB),
+            ('R', Piece::WR, Piece::BR),
+            ('Q', Piece::WQ, Piece::BQ),
+        ];
+        let phase: i32 = pieces.iter()
+            .map(|&(piece, white, black)| self.eval_params.phase_weights[&piece] * (bitboards[white] | bitboards[black]).count_ones() as i32)
+            .sum();
+        phase.clamp(0, BestMoveFinder::MAX_PHASE) // weights from a parameter file may be zero or negative
+    }
+}
+
+
+/// Search helpers that are not exposed to python
+impl BestMoveFinder {
+    /// Quiescence search score of a position from white's side, used to tune the evaluation
+    pub fn quiescenceScore(&mut self, mm: &mut Moves, z: &mut Zobrist, tt: &TransTable, pos: &mut Position) -> i32 {
+        let score: i32 = self.quiescenceSearch(-self.mate_score, self.mate_score, mm, z, tt, pos, 0, false);
+        if pos.whites_turn {score} else {-score}
+    }
+
+
+    /*
+    Populates the PV table with the best move sequence.
+
+    With threads above 1 the search uses Lazy SMP: helper threads search the same
+    root with their own killer, history and PV tables and share only the
+    transposition table, odd helpers search one ply deeper. The helpers stop
+    once this thread's search is done and its result is the one kept.
+    */
+    pub fn searchPosition(
+        &mut self,
+        mm: &mut Moves,
+        z: &mut Zobrist,
+        tt: &TransTable,
+        bitboards: [u64; 13],
+        castle_rights: [bool; 4],
+        hash_key: u64,
+        whites_turn: bool,
+    ) {
+        tt.newSearch();
+        self.helper_nodes.store(0, Ordering::Relaxed);
+        if self.threads <= 1 {
+            self.iterativeDeepening(mm, z, tt, bitboards, castle_rights, hash_key, whites_turn);
+            return;
+        }
+        let helper_stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let mut helpers: Vec<(BestMoveFinder, Moves, Zobrist)> = (1..self.threads)
+            .map(|id| (self.helperFinder(id, &helper_stop), mm.clone(), z.clone()))
+            .collect();
+        thread::scope(|scope| {
+            for (helper, helper_mm, helper_z) in helpers.iter_mut() {
+                scope.spawn(move || {
+                    helper.iterativeDeepening(helper_mm, helper_z, tt, bitboards, castle_rights, hash_key, whites_turn);
+                    helper.publishNodes();
+                });
+            }
+            self.iterativeDeepening(mm, z, tt, bitboards, castle_rights, hash_key, whites_turn);
+            helper_stop.store(true, Ordering::Relaxed);
+        });
+        // once the search is done the node count covers every thread
+        self.move_counter += self.helper_nodes.load(Ordering::Relaxed) as u32;
+    }
+
+
+    /// Add the nodes a helper searched since it last reported them to the shared count
+    fn publishNodes(&mut self) {
+        if self.is_helper {
+            self.helper_nodes.fetch_add((self.move_counter - self.published_nodes) as u64, Ordering::Relaxed);
+            self.published_nodes = self.move_counter;
+        }
+    }
+
+
+    /// Silent copy of this finder's evaluation and game history for a Lazy SMP helper thread
+    fn helperFinder(&self, id: u32, stop: &Arc<AtomicBool>) -> BestMoveFinder {
+        BestMoveFinder {
+            limits: SearchLimits::new(self.limits.depth, None, None, None, true),
+            mate_score: self.mate_score,
+            stale_score: self.stale_score,
+            eval_params: self.eval_params.clone(),
+            full_depth_moves: self.full_depth_moves,
+            quiescence_checks: self.quiescence_checks,
+            reduction_limit: self.reduction_limit,
+            repetition_table: self.repetition_table,
+            repetition_idx: self.repetition_idx,
+            output_mode: SearchOutput::Silent,
+            stop: Arc::clone(stop),
+            depth_offset: id % 2,
+            helper_nodes: Arc::clone(&self.helper_nodes),
+            is_helper: true,
+            ..BestMoveFinder::new(self.limits.depth)
+        }
+    }
+
+
+    /*
+    Iterative deepening search of a single thread.
+
+    With multi_pv above 1 each iteration searches the root again for the best
+    move not found yet, the lines are kept best first in pv_lines.
+    */
+    fn iterativeDeepening(
+        &mut self,
+        mm: &mut Moves,
+        z: &mut Zobrist,
+        tt: &TransTable,
+        bitboards: [u64; 13],
+        castle_rights: [bool; 4],
+        hash_key: u64,
+        whites_turn: bool,
+    ) {
+        self.pv_length = [0; 64];
+        self.pv_table = [[Move::NULL; 64]; 64];
+        self.follow_pv = false; self.score_pv = false;
+        self.pv_lines.clear();
+        let start_time: Instant = Instant::now();
+        self.repetition_table[self.repetition_idx] = hash_key;
+        self.move_counter = 0;
+        self.published_nodes = 0;
+        self.search_stopped = false;
+        self.next_time_check = BestMoveFinder::TIME_CHECK_NODES;
+        self.limits.time_manager.start();
+        let mut best_line: (u32, [Move; 64]) = (0, [Move::NULL; 64]);
+        let mut pos: Position = Position::new(bitboards, castle_rights, hash_key, whites_turn);
+
+        // iterative deepening
+        for current_depth in 1..=self.limits.depth {
+            self.max_depth = (current_depth + self.depth_offset).min(SearchLimits::MAX_DEPTH);
+            self.seldepth = 0;
+            self.excluded_root_moves.clear();
+            let mut lines: Vec<PvLine> = Vec::new();
+            for _ in 0..self.multi_pv.max(1) {
+                // enable PV following
+                self.follow_pv = true;
+                let score: i32 = self.negaMaxAlphaBeta(-50000, 50000, mm, z, tt, &mut pos, 0, 0);
+                if self.search_stopped {
+                    // unfinished iteration, fall back to the last completed PV lines
+                    (self.pv_length[0], self.pv_table[0]) = best_line;
+                    return;
+                }
+                if self.pv_length[0] == 0 {
+                    break; // no valid root moves left
+                }
+                lines.push(PvLine {
+                    score,
+                    moves: self.pv_table[0][..self.pv_length[0] as usize].to_vec(),
+                });
+                self.excluded_root_moves.push(self.pv_table[0][0]);
+            }
+            if lines.is_empty() {
+                break; // checkmate or stalemate at the root
+            }
+            lines.sort_by_key(|line| -line.score);
+            // the PV table holds the best line for callers reading the best move
+            self.pv_table[0][..lines[0].moves.len()].copy_from_slice(&lines[0].moves);
+            self.pv_length[0] = lines[0].moves.len() as u32;
+            best_line = (self.pv_length[0], self.pv_table[0]);
+            self.pv_lines = lines;
+
+            let infos: Vec<SearchInfo> = self.pv_lines.iter().enumerate()
+                .map(|(idx, line)| self.searchInfo(idx as u32 + 1, line, start_time, tt))
+                .collect();
+            for info in infos.iter() {
+                match self.output_mode {
+                    SearchOutput::Console => printConsoleInfo(info),
+                    SearchOutput::Uci => printUciInfo(info),
+                    SearchOutput::XBoard => printXBoardThinking(info),
+                    SearchOutput::Silent => (),
+                }
+                if let Some(listener) = self.listener.as_mut() {
+                    listener.onInfo(info);
+                }
+            }
+            if self.limits.time_manager.softLimitReached() || self.stop.load(Ordering::Relaxed) {
+                break
+            }
+            // mate in N moves found for the side to move
+            if infos[0].mate.is_some_and(|moves| moves > 0 && self.limits.mate.is_some_and(|mate| moves <= mate as i32)) {
+                break
             }
         }
-        score
     }
 
 
     /// Flag if PV scoring should be used.
-    fn enablePVScoring(&mut self, moves: &str, depth: u32) {
+    fn enablePVScoring(&mut self, moves: &MoveList, depth: u32) {
         // disable PV following
         self.follow_pv = false;
-        for i in (0..moves.len()).step_by(4) {
+        for &mv in moves.iter() {
             // make sure to hit a PV move
-            if self.pv_table[0][depth as usize] == moves[i..i+4] {
+            if self.pv_table[0][depth as usize] == mv {
                 // enable move scoring
                 self.score_pv = true;
                 // enable further PV following
@@ -625,35 +999,57 @@ impl BestMoveFinder {
     }
 
 
+    /// False in pawn endings where passing can be better than any move (zugzwang)
+    fn hasNonPawnMaterial(bitboards: [u64; 13], whites_turn: bool) -> bool {
+        let pieces: [Piece; 4] = if whites_turn {
+            [Piece::WN, Piece::WB, Piece::WR, Piece::WQ]
+        } else {
+            [Piece::BN, Piece::BB, Piece::BR, Piece::BQ]
+        };
+        pieces.iter().any(|&piece| bitboards[piece] != 0)
+    }
+
+
     /*
     Score a move based on the following methods:
+        - Hash move from the transposition table
         - PV
         - MVVLVA (most valuable victim, least valuable attacker)
+        - SEE (static exchange evaluation) moves captures losing material last
         - Killer / History moves
     */
     fn scoreMove(
         &mut self,
+        mm: &mut Moves,
         bitboards: [u64; 13],
-        move_str: &str,
+        mv: Move,
         depth: u32,
+        hash_move: Move,
     ) -> i32 {
         if self.score_pv {
-            if self.pv_table[0][depth as usize] == move_str {
+            if self.pv_table[0][depth as usize] == mv {
                 self.score_pv = false;
-                return 20000; // give PV move the highest score to search it first
+                return 20000; // give PV move a high score to search it early
             }
         }
-        let (_, _, r2, c2) = move_to_u32s!(move_str);
-        let (attacker, victim) = get_move_pieces!(bitboards, move_str);
+        if mv == hash_move {
+            return 30000; // best move found by an earlier search of this position goes first
+        }
+        let (attacker, victim) = get_move_pieces!(bitboards, mv);
         if victim != Piece::EP { // attacking move
+            // captures losing material in the exchange go after the quiet moves
+            let can_lose: bool = SEE_VALUES[attacker as usize % 6] > SEE_VALUES[victim as usize % 6];
+            if can_lose && mm.see(bitboards, mv) < 0 {
+                return self.mvv_lva[attacker][victim] - 10000;
+            }
             return self.mvv_lva[attacker][victim] + 10000;
         } else { // quiet move
-            if self.killer_moves[0][depth as usize] == move_str {
+            if self.killer_moves[0][depth as usize] == mv {
                 return 9000;
-            } else if self.killer_moves[1][depth as usize] == move_str {
+            } else if self.killer_moves[1][depth as usize] == mv {
                 return 8000;
             } else {
-                return self.history_moves[attacker][(r2 * 8 + c2) as usize];
+                return self.history_moves[attacker][mv.endSquare() as usize];
             }
         }
     }
@@ -664,42 +1060,117 @@ impl BestMoveFinder {
 
     Function Optimization Details:
 
-    1. Pre-allocation: use 'with_capacity'
-        - creates a heap item with given capacity but with zero length
-        - until capacity is reached, push() calls won't reallocate, making push() essentially free
+    1. Stack allocation: scores and moves are kept in fixed size arrays
+        - no heap allocation per searched node
+        - MoveList::MAX_MOVES is an upper bound on the number of moves in a position
 
     2. In-place Sorting: use 'sort_unstable_by'
         - sort is unstable (i.e., may reorder equal elements)
         - in-place (i.e., does not allocate)
         - O(n * log(n)) worst-case
-
-    3. Resulted in 1.24 times speedup on average (24% faster) than no pre-allocation or in-place sorting
     */
     fn sortMoves(
         &mut self,
         mm: &mut Moves,
         z: &mut Zobrist,
-        moves: &str,
-        bitboards: [u64; 13],
-        hash_key: u64,
-        whites_turn: bool,
+        moves: &MoveList,
+        pos: &mut Position,
         depth: u32,
-    ) -> String {
-        let mut move_scores: Vec<(i32, &str)> = Vec::with_capacity(moves.len() / 4);
-        for i in (0..moves.len()).step_by(4) {
-            let move_slice: &str = &moves[i..i + 4];
-            let (bitboards_t, _) = mm.getUpdatedBitboards(z, move_slice, bitboards, hash_key, whites_turn);
-            if mm.isValidMove(bitboards_t, whites_turn) {
-                move_scores.push((self.scoreMove(bitboards, move_slice, depth), move_slice));
+        hash_move: Move,
+    ) -> MoveList {
+        let whites_turn: bool = pos.whites_turn;
+        let mut move_scores: [(i32, Move); MoveList::MAX_MOVES] = [(0, Move::NULL); MoveList::MAX_MOVES];
+        let mut valid_count: usize = 0;
+        for &mv in moves.iter() {
+            pos.makeMove(z, mv);
+            let valid: bool = mm.isValidMove(pos.bitboards, whites_turn);
+            pos.unmakeMove();
+            if valid {
+                move_scores[valid_count] = (self.scoreMove(mm, pos.bitboards, mv, depth, hash_move), mv);
+                valid_count += 1;
             }
         }
-        move_scores.sort_unstable_by(|a: &(i32, &str), b: &(i32, &str)| b.0.cmp(&a.0));
-        let mut sorted_moves: String = String::with_capacity(moves.len());
-        for (_, m) in move_scores {
-            sorted_moves.push_str(m);
+        move_scores[..valid_count].sort_unstable_by(|a: &(i32, Move), b: &(i32, Move)| b.0.cmp(&a.0));
+        let mut sorted_moves: MoveList = MoveList::new();
+        for &(_, mv) in &move_scores[..valid_count] {
+            sorted_moves.push(mv);
         }
         sorted_moves
     }
+
+
+    /// Progress of the iteration that just completed for one of its PV lines, rank 1 is the best line
+    fn searchInfo(&self, multipv: u32, line: &PvLine, start_time: Instant, tt: &TransTable) -> SearchInfo {
+        let elapsed: Duration = start_time.elapsed();
+        let nodes: u64 = self.move_counter as u64 + self.helper_nodes.load(Ordering::Relaxed);
+        SearchInfo {
+            depth: self.max_depth,
+            seldepth: self.seldepth,
+            multipv,
+            score: line.score,
+            mate: SearchInfo::mateMoves(line.score, self.mate_score),
+            nodes,
+            nps: (nodes as f64 / elapsed.as_secs_f64().max(0.001)) as u64,
+            time_ms: elapsed.as_millis() as u64,
+            hashfull: tt.hashfull(),
+            pv: SearchInfo::pvMoves(&line.moves),
+        }
+    }
+}
+
+
+/// Print the result of a completed iteration in a human readable form
+fn printConsoleInfo(info: &SearchInfo) {
+    match info.mate {
+        Some(moves) => println!("Depth: {}, Move: {}, Score: {}, Mate in {}", info.depth, info.pv.first().map_or("0000", |mv| mv), info.score, moves.abs()),
+        None => println!("Depth: {}, Move: {}, Score: {}", info.depth, info.pv.first().map_or("0000", |mv| mv), info.score),
+    }
+    println!("Total moves analyzed: {}, Duration: {:?}", info.nodes, Duration::from_millis(info.time_ms));
+    println!("Best Move Sequence: {}\n", info.pv.join(" "));
+}
+
+
+/// Print the result of a completed iteration as a UCI info line
+fn printUciInfo(info: &SearchInfo) {
+    let score_str: String = match info.mate {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", info.score),
+    };
+    println!(
+        "info depth {} seldepth {} multipv {} score {} nodes {} time {} nps {} hashfull {} pv {}",
+        info.depth,
+        info.seldepth,
+        info.multipv,
+        score_str,
+        info.nodes,
+        info.time_ms,
+        info.nps,
+        info.hashfull,
+        info.pv.join(" "),
+    );
+}
+
+
+/*
+Print the result of a completed iteration as xboard thinking output:
+ply score time(centiseconds) nodes pv
+
+Mate scores are reported as 100000 + N for mate in N moves
+*/
+fn printXBoardThinking(info: &SearchInfo) {
+    let xboard_score: i32 = match info.mate {
+        Some(moves) if moves > 0 => 100000 + moves,
+        Some(moves) => -100000 + moves,
+        None => info.score,
+    };
+    println!(
+        "{} {} {} {} {}",
+        info.depth,
+        xboard_score,
+        info.time_ms / 10,
+        info.nodes,
+        info.pv.join(" "),
+    );
 }
 
 
@@ -716,19 +1187,23 @@ mod tests {
         let mut z: Zobrist = Zobrist::new();
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
-        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 "));
+        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 ")).unwrap();
         let mut bmf: BestMoveFinder = BestMoveFinder::new(2);
-        let moves: String = m.getPossibleMoves(gs.bitboards, gs.castle_rights, gs.whites_turn);
-        let mut actual_scores: Vec<i32> = vec![10105, 10105, 10303, 10101, 10201, 10104, 10104, 10104];
-        for i in (0..moves.len()).step_by(4) {
-            let (bitboards_t, _) = m.getUpdatedBitboards(&mut z, &moves[i..i+4], gs.bitboards, gs.hash_key, gs.whites_turn);
-            if m.isValidMove(bitboards_t, gs.whites_turn) {
-                let score = bmf.scoreMove(gs.bitboards, &moves[i..i+4], 0);
+        let moves: MoveList = m.getPossibleMoves(gs.position.bitboards, gs.position.castle_rights, gs.position.whites_turn);
+        // the queen and knight captures lose material in the exchange
+        let mut actual_scores: Vec<i32> = vec![10105, 10105, 10303, -9899, -9799, -9896, -9896, -9896];
+        for &mv in moves.iter() {
+            gs.position.makeMove(&z, mv);
+            let valid: bool = m.isValidMove(gs.position.bitboards, !gs.position.whites_turn);
+            gs.position.unmakeMove();
+            if valid {
+                let score = bmf.scoreMove(&mut m, gs.position.bitboards, mv, 0, Move::NULL);
                 if score != 0 {
                     assert!(score == actual_scores.remove(0));
                 }
             }
         }
+        assert!(actual_scores.is_empty());
     }
 
     #[test]
@@ -736,15 +1211,250 @@ mod tests {
         let mut z: Zobrist = Zobrist::new();
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
-        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 "));
+        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 ")).unwrap();
         let mut bmf: BestMoveFinder = BestMoveFinder::new(2);
-        let moves: String = m.getPossibleMoves(gs.bitboards, gs.castle_rights, gs.whites_turn);
-        let sorted_moves: String = bmf.sortMoves(&mut m, &mut z, &moves, gs.bitboards, gs.hash_key, gs.whites_turn, 0);
+        let moves: MoveList = m.getPossibleMoves(gs.position.bitboards, gs.position.castle_rights, gs.position.whites_turn);
+        let sorted_moves: MoveList = bmf.sortMoves(&mut m, &mut z, &moves, &mut gs.position, 0, Move::NULL);
         let mut score: i32 = i32::MAX;
-        for i in (0..sorted_moves.len()).step_by(4) {
-            let current_score: i32 = bmf.scoreMove(gs.bitboards, &sorted_moves[i..i+4], 0);
+        for &mv in sorted_moves.iter() {
+            let current_score: i32 = bmf.scoreMove(&mut m, gs.position.bitboards, mv, 0, Move::NULL);
             assert!(current_score <= score);
             score = current_score;
         }
     }
+
+    #[test]
+    fn hash_move_is_sorted_first() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut gs = GameState::new(&mut z);
+        let mut m: Moves = Moves::new();
+        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1 ")).unwrap();
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(2);
+        let moves: MoveList = m.getPossibleMoves(gs.position.bitboards, gs.position.castle_rights, gs.position.whites_turn);
+        let quiet_move: Move = moves.iter().copied().find(|mv| mv.to_string() == "a2a3").unwrap();
+        let sorted_moves: MoveList = bmf.sortMoves(&mut m, &mut z, &moves, &mut gs.position, 0, quiet_move);
+        assert!(sorted_moves.iter().next() == Some(&quiet_move));
+
+        // the search stores the move it played in the table
+        let tt: TransTable = TransTable::new(TransTable::DEFAULT_SIZE_MB);
+        bmf.output_mode = SearchOutput::Silent;
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        assert!(tt.probe(gs.position.hash_key).unwrap().best_move == bmf.pv_table[0][0]);
+    }
+
+    #[test]
+    fn null_move_pruning_keeps_mate_distances() {
+        // missed mate in 1 and KR vs K / KQ vs K mates from interesting_positions.txt
+        for (fen, best_move, mate) in [
+            ("5k2/8/5PK1/8/7p/2R3P1/8/7r w - - 1 88", "c3c8", 1),
+            ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8", 1),
+            ("3k4/8/4K3/8/8/8/8/7R w - - 0 1", "h1c1", 2),
+            ("4k3/Q7/8/4K3/8/8/8/8 w - - 0 1", "e5e6", 2),
+            ("8/5Q2/k7/2K5/8/8/8/8 w - - 0 1", "f7h7", 2),
+        ] {
+            let mut z: Zobrist = Zobrist::new();
+            let mut m: Moves = Moves::new();
+            let tt: TransTable = TransTable::new(16);
+            let mut gs: GameState = GameState::new(&z);
+            gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+            let mut bmf: BestMoveFinder = BestMoveFinder::new(6);
+            bmf.output_mode = SearchOutput::Silent;
+            bmf.limits.time_manager = TimeManager::infinite();
+            bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+            assert!(bmf.pv_table[0][0].to_string() == best_move);
+            assert!(SearchInfo::mateMoves(bmf.pv_lines[0].score, bmf.mate_score) == Some(mate));
+            // the root entry holds the same mate distance
+            assert!(tt.probe(gs.position.hash_key).unwrap().score == bmf.pv_lines[0].score);
+        }
+    }
+
+    #[test]
+    fn checks_are_searched_past_the_horizon() {
+        let search = |fen: &str, depth: u32, quiescence_checks: bool| -> (String, i32) {
+            let mut z: Zobrist = Zobrist::new();
+            let mut m: Moves = Moves::new();
+            let tt: TransTable = TransTable::new(16);
+            let mut gs: GameState = GameState::new(&z);
+            gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+            let mut bmf: BestMoveFinder = BestMoveFinder::new(depth);
+            bmf.output_mode = SearchOutput::Silent;
+            bmf.limits.time_manager = TimeManager::infinite();
+            bmf.quiescence_checks = quiescence_checks;
+            bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+            (bmf.pv_table[0][0].to_string(), bmf.pv_lines[0].score)
+        };
+        // check extensions see mates delivered at the last ply, depth 1 used to play d1d7 and h5f7 without the mate score
+        assert!(search("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 1, true) == (String::from("d1d8"), 48999));
+        assert!(search("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4", 1, true) == (String::from("h5f7"), 48999));
+
+        // taking the knight allows a back rank mate by a quiet check in quiescence
+        assert!(search("3r2k1/2n2ppp/8/8/8/2Q5/5PPP/6K1 w - - 0 1", 1, false).0 == "c3c7");
+        assert!(search("3r2k1/2n2ppp/8/8/8/2Q5/5PPP/6K1 w - - 0 1", 1, true).0 != "c3c7");
+        assert!(search("3r2k1/2n2ppp/8/8/8/2Q5/5PPP/6K1 w - - 0 1", 2, true).0 != "c3c7");
+    }
+
+    #[test]
+    fn null_move_pruning_reduces_nodes() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        gs.importFEN(&m.masks, &z, String::from("r2q1rk1/ppp2ppp/2n1bn2/2b1p3/3pP3/3P1NPP/PPP1NPB1/R1BQ1RK1 b - - 0 9")).unwrap();
+        let mut nodes: Vec<u32> = Vec::new();
+        for null_move_allowed in [false, true] {
+            let tt: TransTable = TransTable::new(16);
+            let mut bmf: BestMoveFinder = BestMoveFinder::new(5);
+            bmf.output_mode = SearchOutput::Silent;
+            bmf.limits.time_manager = TimeManager::infinite();
+            bmf.null_move_allowed = null_move_allowed;
+            bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+            nodes.push(bmf.move_counter);
+        }
+        assert!(nodes[1] < nodes[0]);
+    }
+
+    #[test]
+    fn hard_time_limit_aborts_search() {
+        let mut z: Zobrist = Zobrist::new();
+        let gs = GameState::new(&mut z);
+        let mut m: Moves = Moves::new();
+        let tt: TransTable = TransTable::new(TransTable::DEFAULT_SIZE_MB);
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(63);
+        bmf.output_mode = SearchOutput::Silent;
+        bmf.limits.time_manager = TimeManager::fixedTime(130);
+        let start_time: Instant = Instant::now();
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        assert!(start_time.elapsed() < Duration::from_secs(2));
+        assert!(bmf.max_depth < 63);
+        assert!(bmf.pv_table[0][0] != Move::NULL);
+    }
+
+    #[test]
+    fn search_limits_and_stop_flag_end_search() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut gs = GameState::new(&mut z);
+        let mut m: Moves = Moves::new();
+        let tt: TransTable = TransTable::new(TransTable::DEFAULT_SIZE_MB);
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(63);
+        bmf.output_mode = SearchOutput::Silent;
+
+        bmf.limits = SearchLimits::new(63, Some(20_000), None, None, false);
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        assert!(bmf.move_counter < 30_000 && bmf.pv_table[0][0] != Move::NULL);
+
+        // the check extension sees mate in 1 at depth 1, without the mate limit the search would go on to depth 63
+        gs.importFEN(&m.masks, &mut z, String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")).unwrap();
+        bmf.limits = SearchLimits::new(63, None, None, Some(1), false);
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        assert!(bmf.max_depth == 1 && bmf.pv_table[0][0].to_string() == "a1a8");
+
+        gs = GameState::new(&mut z);
+        bmf.limits = SearchLimits::new(63, None, None, None, true);
+        let stop_flag: StopFlag = bmf.getStopFlag();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            stop_flag.set();
+        });
+        let start_time: Instant = Instant::now();
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        stopper.join().unwrap();
+        assert!(start_time.elapsed() < Duration::from_secs(2));
+        assert!(bmf.getStopFlag().isSet() && bmf.pv_table[0][0] != Move::NULL);
+
+        // a later search from python is not ended by the earlier stop
+        bmf.limits = SearchLimits::new(3, None, None, None, false);
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            bmf.searchPositionPy(py, &mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn, None);
+        });
+        assert!(!bmf.getStopFlag().isSet() && bmf.max_depth == 3);
+
+        // a flag passed to searchPosition ends the running search
+        bmf.limits = SearchLimits::new(63, None, None, None, true);
+        let stop_flag: StopFlag = StopFlag::new();
+        let stopper_flag: StopFlag = stop_flag.clone();
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            stopper_flag.set();
+        });
+        let start_time: Instant = Instant::now();
+        Python::with_gil(|py| {
+            bmf.searchPositionPy(py, &mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn, Some(stop_flag));
+        });
+        stopper.join().unwrap();
+        assert!(start_time.elapsed() < Duration::from_secs(2) && bmf.pv_table[0][0] != Move::NULL);
+    }
+
+    #[test]
+    fn multi_pv_lines_are_distinct_and_ranked() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let tt: TransTable = TransTable::new(TransTable::DEFAULT_SIZE_MB);
+        let mut gs: GameState = GameState::new(&z);
+        gs.importFEN(&m.masks, &z, String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")).unwrap();
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(3);
+        bmf.output_mode = SearchOutput::Silent;
+        bmf.multi_pv = 3;
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+
+        assert!(bmf.pv_lines.len() == 3);
+        assert!(bmf.pv_lines[0].moves[0].to_string() == "a1a8" && bmf.pv_table[0][0] == bmf.pv_lines[0].moves[0]);
+        assert!(bmf.pv_lines.windows(2).all(|lines| lines[0].score >= lines[1].score));
+        assert!(bmf.pv_lines[1].moves[0] != bmf.pv_lines[0].moves[0] && bmf.pv_lines[2].moves[0] != bmf.pv_lines[1].moves[0]);
+        assert!(bmf.pv_lines[2].moves[0] != bmf.pv_lines[0].moves[0]);
+
+        // fewer valid moves than lines
+        gs.importFEN(&m.masks, &z, String::from("7k/8/8/8/8/8/8/K7 w - - 0 1")).unwrap();
+        bmf.multi_pv = 10;
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        assert!(bmf.pv_lines.len() == 3);
+    }
+
+    #[test]
+    fn lazy_smp_search_shares_table() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let tt: TransTable = TransTable::new(TransTable::DEFAULT_SIZE_MB);
+        let mut gs: GameState = GameState::new(&z);
+        gs.importFEN(&m.masks, &z, String::from("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4")).unwrap();
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(4);
+        bmf.output_mode = SearchOutput::Silent;
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        let single_thread_nodes: u32 = bmf.move_counter;
+
+        let tt: TransTable = TransTable::new(TransTable::DEFAULT_SIZE_MB);
+        bmf.threads = 4;
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        assert!(bmf.pv_table[0][0].to_string() == "h5f7");
+        assert!(tt.probe(gs.position.hash_key).is_some());
+        // the helpers' nodes are counted too
+        assert!(bmf.move_counter > single_thread_nodes);
+    }
+
+    #[test]
+    fn evaluation_is_tapered_by_game_phase() {
+        let z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        let bmf: BestMoveFinder = BestMoveFinder::new(2);
+        let mut evaluate = |fen: &str| -> (i32, i32) {
+            gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+            (bmf.gamePhase(gs.position.bitboards), bmf.evaluateBoard(&mut m, gs.position.bitboards))
+        };
+        // the castled king is safer with all pieces on the board
+        let (phase, castled) = evaluate("rnbq1rk1/ppppppbp/5np1/8/8/5NP1/PPPPPPBP/RNBQ1RK1 w - - 0 1");
+        let (_, exposed) = evaluate("rnbq1rk1/ppppppbp/5np1/8/8/4KNP1/PPPPPPBP/RNBQ1R2 w - - 0 1");
+        assert!(phase == BestMoveFinder::MAX_PHASE && castled > exposed);
+        // the central king is stronger in a pawn ending (KP vs K from interesting_positions.txt)
+        let (phase, cornered) = evaluate("8/8/8/8/8/8/PK5k/8 w - - 0 1");
+        let (_, central) = evaluate("8/8/8/8/3K4/8/P6k/8 w - - 0 1");
+        assert!(phase == 0 && central > cornered);
+        let (phase, _) = evaluate("3qk3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        assert!(phase == 8);
+        // out of range weights still give a phase between the endgame and the middlegame
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(2);
+        bmf.eval_params.setParam("phase_weights.Q", -10).unwrap();
+        assert!(bmf.gamePhase(gs.position.bitboards) == 0);
+        bmf.eval_params.setParam("phase_weights.Q", 20).unwrap();
+        assert!(bmf.gamePhase(gs.position.bitboards) == BestMoveFinder::MAX_PHASE);
+    }
 }
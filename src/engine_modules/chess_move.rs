@@ -0,0 +1,298 @@
+//! Module holding the compact move representation
+
+
+use std::fmt;
+use std::ops::{
+    Deref,
+    DerefMut,
+};
+use crate::piece::Piece;
+
+
+/*
+A move packed into 16 bits:
+
+    bits 0-5    start square (0 is top-left)
+    bits 6-11   end square
+    bits 12-15  flag (capture, double push, enpassant, castle, promotion piece)
+
+Promotion flags have bit 3 set and bit 2 set when the promotion is also a capture.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Move(u16);
+
+
+impl Move {
+    pub const NULL: Move = Move(0); // a8a8 is never a legal move
+
+    // flags
+    pub const QUIET: u16 = 0;
+    pub const DOUBLE_PUSH: u16 = 1;
+    pub const KING_CASTLE: u16 = 2;
+    pub const QUEEN_CASTLE: u16 = 3;
+    pub const CAPTURE: u16 = 4;
+    pub const EN_PASSANT: u16 = 5;
+    pub const PROMO_N: u16 = 8;
+    pub const PROMO_B: u16 = 9;
+    pub const PROMO_R: u16 = 10;
+    pub const PROMO_Q: u16 = 11;
+
+
+    pub fn new(start_sq: u32, end_sq: u32, flag: u16) -> Self {
+        Move((flag << 12) | ((end_sq as u16) << 6) | start_sq as u16)
+    }
+
+
+    /// Move from its packed 16 bit form, see bits()
+    pub fn fromBits(bits: u16) -> Self {
+        Move(bits)
+    }
+
+
+    /// Packed 16 bit form, used to store moves compactly
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+
+    pub fn startSquare(&self) -> u32 {
+        (self.0 & 0x3f) as u32
+    }
+
+
+    pub fn endSquare(&self) -> u32 {
+        ((self.0 >> 6) & 0x3f) as u32
+    }
+
+
+    pub fn flag(&self) -> u16 {
+        self.0 >> 12
+    }
+
+
+    /// True for captures including enpassant and capturing promotions
+    pub fn isCapture(&self) -> bool {
+        self.flag() & Move::CAPTURE != 0
+    }
+
+
+    pub fn isPromotion(&self) -> bool {
+        self.flag() & Move::PROMO_N != 0
+    }
+
+
+    pub fn isEnPassant(&self) -> bool {
+        self.flag() == Move::EN_PASSANT
+    }
+
+
+    pub fn isDoublePush(&self) -> bool {
+        self.flag() == Move::DOUBLE_PUSH
+    }
+
+
+    pub fn isCastle(&self) -> bool {
+        self.flag() == Move::KING_CASTLE || self.flag() == Move::QUEEN_CASTLE
+    }
+
+
+    /// Piece a pawn promotes to, Piece::EP if the move is not a promotion
+    pub fn promotionPiece(&self) -> Piece {
+        if !self.isPromotion() {
+            return Piece::EP;
+        }
+        let white: bool = self.endSquare() / 8 == 0;
+        match (self.flag() & 0b11, white) {
+            (0, true) => Piece::WN,
+            (1, true) => Piece::WB,
+            (2, true) => Piece::WR,
+            (3, true) => Piece::WQ,
+            (0, false) => Piece::BN,
+            (1, false) => Piece::BB,
+            (2, false) => Piece::BR,
+            _ => Piece::BQ,
+        }
+    }
+
+
+    /*
+    Convert to the 4 character move string used by the python interface:
+        regular "r1c1r2c2", promotion "c1c2" + piece + 'P', enpassant "c1c2" + 'w'/'b' + 'E'
+
+    The null move is converted to an empty string.
+    */
+    pub fn toMoveStr(&self) -> String {
+        let (start_sq, end_sq) = (self.startSquare(), self.endSquare());
+        if *self == Move::NULL {
+            String::new()
+        } else if self.isEnPassant() {
+            format!("{}{}{}E", start_sq % 8, end_sq % 8, if start_sq / 8 == 3 {'w'} else {'b'})
+        } else if self.isPromotion() {
+            let piece_char: char = match self.flag() & 0b11 {
+                0 => 'N',
+                1 => 'B',
+                2 => 'R',
+                _ => 'Q',
+            };
+            let piece_char: char = if end_sq / 8 == 0 {piece_char} else {piece_char.to_ascii_lowercase()};
+            format!("{}{}{}P", start_sq % 8, end_sq % 8, piece_char)
+        } else {
+            format!("{}{}{}{}", start_sq / 8, start_sq % 8, end_sq / 8, end_sq % 8)
+        }
+    }
+
+
+    /// Convert a 4 character move string to a move, the board is needed to recover the flags
+    pub fn fromMoveStr(move_str: &str, bitboards: &[u64; 13]) -> Self {
+        let (r1, c1, r2, c2) = move_to_u32s!(move_str);
+        let start_sq: u32 = r1 * 8 + c1;
+        let end_sq: u32 = r2 * 8 + c2;
+        let capture: u16 = if get_bit!(or_array_elems!(Piece::allPieces(), bitboards), end_sq) == 1 {Move::CAPTURE} else {0};
+        let flag: u16 = match move_str.chars().nth(3).unwrap() {
+            'E' => Move::EN_PASSANT,
+            'P' => capture | match move_str.chars().nth(2).unwrap().to_ascii_uppercase() {
+                'N' => Move::PROMO_N,
+                'B' => Move::PROMO_B,
+                'R' => Move::PROMO_R,
+                _ => Move::PROMO_Q,
+            },
+            _ => {
+                let king_move: bool = get_bit!(bitboards[Piece::WK] | bitboards[Piece::BK], start_sq) == 1;
+                let pawn_move: bool = get_bit!(bitboards[Piece::WP] | bitboards[Piece::BP], start_sq) == 1;
+                if king_move && c1 == 4 && c2 == 6 {
+                    Move::KING_CASTLE
+                } else if king_move && c1 == 4 && c2 == 2 {
+                    Move::QUEEN_CASTLE
+                } else if pawn_move && r1.abs_diff(r2) == 2 {
+                    Move::DOUBLE_PUSH
+                } else {
+                    capture
+                }
+            },
+        };
+        Move::new(start_sq, end_sq, flag)
+    }
+}
+
+
+/// Coordinate notation used by UCI and xboard ("e2e4", "e7e8q"), "0000" for the null move
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if *self == Move::NULL {
+            return write!(f, "0000");
+        }
+        let (start_sq, end_sq) = (self.startSquare(), self.endSquare());
+        write!(
+            f,
+            "{}{}{}{}",
+            (b'a' + (start_sq % 8) as u8) as char,
+            8 - start_sq / 8,
+            (b'a' + (end_sq % 8) as u8) as char,
+            8 - end_sq / 8,
+        )?;
+        match self.flag() & 0b1011 {
+            Move::PROMO_N => write!(f, "n"),
+            Move::PROMO_B => write!(f, "b"),
+            Move::PROMO_R => write!(f, "r"),
+            Move::PROMO_Q => write!(f, "q"),
+            _ => Ok(()),
+        }
+    }
+}
+
+
+/// Fixed capacity move list that lives on the stack
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MoveList::MAX_MOVES],
+    len: usize,
+}
+
+
+impl MoveList {
+    pub const MAX_MOVES: usize = 256; // the most moves known in a legal position is 218
+
+    pub fn new() -> Self {
+        MoveList {
+            moves: [Move::NULL; MoveList::MAX_MOVES],
+            len: 0,
+        }
+    }
+
+
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+
+    /// Concatenate the python move strings of all moves
+    pub fn toMoveStr(&self) -> String {
+        self.iter().map(|mv| mv.toMoveStr()).collect()
+    }
+}
+
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+
+/// Allows a move list to be used as a slice of the moves it holds
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
+
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.moves[..self.len]
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_modules::{
+        game_state::GameState,
+        moves::Moves,
+        zobrist::Zobrist,
+    };
+
+    #[test]
+    fn move_fields_are_packed() {
+        let mv: Move = Move::new(52, 36, Move::DOUBLE_PUSH);
+        assert!(mv.startSquare() == 52 && mv.endSquare() == 36);
+        assert!(mv.isDoublePush() && !mv.isCapture());
+        assert!(mv.to_string() == "e2e4");
+        let mv: Move = Move::new(12, 5, Move::PROMO_R | Move::CAPTURE);
+        assert!(mv.isPromotion() && mv.isCapture());
+        assert!(mv.promotionPiece() == Piece::WR);
+        assert!(mv.to_string() == "e7f8r");
+    }
+
+    #[test]
+    fn move_str_round_trip() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut gs: GameState = GameState::new(&z);
+        let mut m: Moves = Moves::new();
+        for fen in ["r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", "8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 1"] {
+            gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+            let moves: MoveList = m.getValidMoves(&mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+            assert!(!moves.is_empty());
+            for &mv in moves.iter() {
+                assert!(Move::fromMoveStr(&mv.toMoveStr(), &gs.position.bitboards) == mv);
+            }
+        }
+    }
+}
@@ -0,0 +1,500 @@
+//! Module holding the evaluation weights and reading / writing them as text files
+
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+};
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EvalParamsError {
+    Io(String),
+    BadLine(usize, String), // line number starting at 1
+    UnknownParam(String),
+    BadValue(String, String), // param, value
+    WrongLength(String, usize, usize), // param, expected, found
+}
+
+
+impl fmt::Display for EvalParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalParamsError::Io(err) => write!(f, "could not access parameter file, {}", err),
+            EvalParamsError::BadLine(line, text) => write!(f, "line {} is not 'name = value', found '{}'", line, text),
+            EvalParamsError::UnknownParam(name) => write!(f, "unknown parameter '{}'", name),
+            EvalParamsError::BadValue(name, value) => write!(f, "bad value '{}' for parameter '{}'", value, name),
+            EvalParamsError::WrongLength(name, expected, found) => {
+                write!(f, "parameter '{}' needs {} values, found {}", name, expected, found)
+            },
+        }
+    }
+}
+
+
+impl std::error::Error for EvalParamsError {}
+
+
+/// Bad parameter files raise a ValueError in python
+impl From<EvalParamsError> for PyErr {
+    fn from(err: EvalParamsError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+
+/*
+Weights used by BestMoveFinder::evaluateBoard.
+
+Piece values and piece-square tables come in a middlegame and an endgame (_eg)
+version. Tables are indexed [row][col] from white's side, row 0 is rank 8, and
+are mirrored for black.
+
+Every single weight has a name used by the parameter files, UCI options and
+getParam / setParam:
+    double_pawn_penalty, passed_pawn_bonus.3, piece_scores.Q,
+    piece_position_scores_eg.K.e4 ...
+*/
+#[pyclass(module = "ChessProject", get_all, set_all)]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EvalParams {
+    pub piece_scores: HashMap<char, i32>, // middlegame
+    pub piece_scores_eg: HashMap<char, i32>,
+    pub piece_position_scores: HashMap<char, [[i32; 8]; 8]>, // middlegame
+    pub piece_position_scores_eg: HashMap<char, [[i32; 8]; 8]>,
+    pub phase_weights: HashMap<char, i32>, // contribution of each piece to the game phase
+    pub double_pawn_penalty: i32,
+    pub isolated_pawn_penalty: i32,
+    pub passed_pawn_bonus: [i32; 8], // by rank from the pawn's side, index 0 is its first rank
+    pub semi_open_file_score: i32,
+    pub open_file_score: i32,
+    pub king_blocking_bonus: i32,
+}
+
+
+#[pymethods]
+impl EvalParams {
+    pub const PIECE_ORDER: &'static str = "KQRBNP"; // order of the per piece weights in files
+
+    #[new]
+    pub fn new() -> Self {
+        EvalParams {
+            piece_scores: HashMap::from([
+                ('K', 10000),
+                ('Q', 1000),
+                ('R', 500),
+                ('B', 350),
+                ('N', 300),
+                ('P', 100),
+            ]),
+            piece_scores_eg: HashMap::from([
+                ('K', 10000),
+                ('Q', 950),
+                ('R', 550),
+                ('B', 330),
+                ('N', 280),
+                ('P', 120),
+            ]),
+            piece_position_scores: HashMap::from([
+                ('K', [
+                    [-30, -40, -40, -50, -50, -40, -40, -30],
+                    [-30, -40, -40, -50, -50, -40, -40, -30],
+                    [-30, -40, -40, -50, -50, -40, -40, -30],
+                    [-30, -40, -40, -50, -50, -40, -40, -30],
+                    [-20, -30, -30, -40, -40, -30, -30, -20],
+                    [-10, -20, -20, -20, -20, -20, -20, -10],
+                    [  0,   0, -10, -15, -15, -10,   0,   0],
+                    [  0,  10,   5, -10, -15,   0,  10,   0],
+                ]),
+                ('R', [
+                    [50,  50,  50,  50,  50,  50,  50,  50],
+                    [50,  50,  50,  50,  50,  50,  50,  50],
+                    [ 0,   0,  10,  20,  20,  10,   0,   0],
+                    [ 0,   0,  10,  20,  20,  10,   0,   0],
+                    [ 0,   0,  10,  20,  20,  10,   0,   0],
+                    [ 0,   0,  10,  20,  20,  10,   0,   0],
+                    [ 0,   0,  10,  20,  20,  10,   0,   0],
+                    [ 0,   0,   0,  20,  20,   0,   0,   0],
+                ]),
+                ('B', [
+                    [0,   0,   0,   0,   0,   0,   0,   0],
+                    [0,   0,   0,   0,   0,   0,   0,   0],
+                    [0,   0,   0,  10,  10,   0,   0,   0],
+                    [0,   0,  10,  20,  20,  10,   0,   0],
+                    [0,   0,  10,  20,  20,  10,   0,   0],
+                    [0,  10,   5,  10,  10,   5,  10,   0],
+                    [0,  20,   5,   5,   5,   5,  20,   0],
+                    [0,   0, -10,   0,   0, -10,   0,   0],
+                ]),
+                ('N', [
+                    [-5,   0,   0,   0,   0,   0,   0,  -5],
+                    [-5,   0,   0,  10,  10,   0,   0,  -5],
+                    [-5,   5,  20,  20,  20,  20,   5,  -5],
+                    [-5,  10,  20,  30,  30,  20,  10,  -5],
+                    [-5,  10,  20,  30,  30,  20,  10,  -5],
+                    [-5,   5,  20,  10,  10,  20,   5,  -5],
+                    [-5,   0,   0,   0,   0,   0,   0,  -5],
+                    [-5, -10,   0,   0,   0,   0, -10,  -5],
+                ]),
+                ('P', [
+                    [90,  90,  90,  90,  90,  90,  90,  90],
+                    [30,  30,  30,  40,  40,  30,  30,  30],
+                    [20,  20,  20,  30,  30,  30,  20,  20],
+                    [15,  10,  10,  20,  20,  10,  10,  15],
+                    [10,   5,  10,  20,  20,   5,   5,  10],
+                    [ 5,   0,   0,   5,   5,   0,   0,   5],
+                    [ 0,   0,   0, -10, -10,   0,   0,   0],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                ]),
+            ]),
+            piece_position_scores_eg: HashMap::from([
+                ('K', [
+                    [-50, -30, -30, -30, -30, -30, -30, -50],
+                    [-30, -10,   0,   0,   0,   0, -10, -30],
+                    [-30,   0,  20,  30,  30,  20,   0, -30],
+                    [-30,   0,  30,  40,  40,  30,   0, -30],
+                    [-30,   0,  30,  40,  40,  30,   0, -30],
+                    [-30,   0,  20,  30,  30,  20,   0, -30],
+                    [-30, -10,   0,   0,   0,   0, -10, -30],
+                    [-50, -30, -30, -30, -30, -30, -30, -50],
+                ]),
+                ('R', [
+                    [10,  10,  10,  10,  10,  10,  10,  10],
+                    [20,  20,  20,  20,  20,  20,  20,  20],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                ]),
+                ('B', [
+                    [-10,  -5,  -5,  -5,  -5,  -5,  -5, -10],
+                    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+                    [ -5,   0,   5,  10,  10,   5,   0,  -5],
+                    [ -5,   0,  10,  15,  15,  10,   0,  -5],
+                    [ -5,   0,  10,  15,  15,  10,   0,  -5],
+                    [ -5,   0,   5,  10,  10,   5,   0,  -5],
+                    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+                    [-10,  -5,  -5,  -5,  -5,  -5,  -5, -10],
+                ]),
+                ('N', [
+                    [-30, -20, -10, -10, -10, -10, -20, -30],
+                    [-20,  -5,   0,   5,   5,   0,  -5, -20],
+                    [-10,   0,  10,  15,  15,  10,   0, -10],
+                    [-10,   5,  15,  20,  20,  15,   5, -10],
+                    [-10,   5,  15,  20,  20,  15,   5, -10],
+                    [-10,   0,  10,  15,  15,  10,   0, -10],
+                    [-20,  -5,   0,   0,   0,   0,  -5, -20],
+                    [-30, -20, -10, -10, -10, -10, -20, -30],
+                ]),
+                ('P', [
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                    [90,  90,  90,  90,  90,  90,  90,  90],
+                    [60,  60,  60,  60,  60,  60,  60,  60],
+                    [35,  35,  35,  35,  35,  35,  35,  35],
+                    [20,  20,  20,  20,  20,  20,  20,  20],
+                    [10,  10,  10,  10,  10,  10,  10,  10],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                    [ 0,   0,   0,   0,   0,   0,   0,   0],
+                ]),
+            ]),
+            phase_weights: HashMap::from([
+                ('Q', 4),
+                ('R', 2),
+                ('B', 1),
+                ('N', 1),
+            ]),
+            double_pawn_penalty: -10,
+            isolated_pawn_penalty: -10,
+            passed_pawn_bonus: [0, 10, 30, 50, 75, 100, 150, 200],
+            semi_open_file_score: 10,
+            open_file_score: 15,
+            king_blocking_bonus: 5,
+        }
+    }
+
+
+    /// Read a parameter file, weights missing from the file keep their default value
+    #[staticmethod]
+    pub fn load(path: &str) -> Result<Self, EvalParamsError> {
+        let text: String = fs::read_to_string(path).map_err(|err| EvalParamsError::Io(err.to_string()))?;
+        EvalParams::fromText(&text)
+    }
+
+
+    /// Write every weight to a parameter file that load() reads back
+    pub fn save(&self, path: &str) -> Result<(), EvalParamsError> {
+        fs::write(path, self.toText()).map_err(|err| EvalParamsError::Io(err.to_string()))
+    }
+
+
+    /*
+    Parse the parameter file format, a small subset of TOML:
+
+        # comment
+        double_pawn_penalty = -10
+        piece_scores.Q = 1000
+        passed_pawn_bonus = [0, 10, 30, 50, 75, 100, 150, 200]
+        piece_position_scores.K = [
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            ...
+        ]
+
+    A list sets every weight whose name starts with the list name in the order
+    of paramNames(), lists may span several lines.
+    */
+    #[staticmethod]
+    pub fn fromText(text: &str) -> Result<Self, EvalParamsError> {
+        let mut params: EvalParams = EvalParams::new();
+        let mut lines = text.lines().enumerate();
+        while let Some((line_idx, line)) = lines.next() {
+            let line: &str = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = line.split_once('=')
+                .ok_or(EvalParamsError::BadLine(line_idx + 1, line.to_string()))?;
+            let (name, mut value) = (name.trim(), value.trim().to_string());
+            if !value.starts_with('[') {
+                let value: i32 = value.parse::<i32>()
+                    .map_err(|_| EvalParamsError::BadValue(name.to_string(), value.clone()))?;
+                params.setParam(name, value)?;
+                continue;
+            }
+            // read the rest of a list spanning several lines
+            while !value.contains(']') {
+                match lines.next() {
+                    Some((_, next_line)) => {
+                        value.push(' ');
+                        value.push_str(next_line.split('#').next().unwrap().trim());
+                    },
+                    None => return Err(EvalParamsError::BadValue(name.to_string(), value)),
+                }
+            }
+            let values: Vec<i32> = value.trim_start_matches('[').trim_end_matches(']')
+                .split(',')
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<i32>().map_err(|_| EvalParamsError::BadValue(name.to_string(), v.to_string())))
+                .collect::<Result<Vec<i32>, EvalParamsError>>()?;
+            let prefix: String = format!("{}.", name);
+            let names: Vec<String> = params.paramNames().into_iter().filter(|n| n.starts_with(&prefix)).collect();
+            if names.is_empty() {
+                return Err(EvalParamsError::UnknownParam(name.to_string()));
+            }
+            if names.len() != values.len() {
+                return Err(EvalParamsError::WrongLength(name.to_string(), names.len(), values.len()));
+            }
+            for (param_name, param_value) in names.iter().zip(values) {
+                params.setParam(param_name, param_value)?;
+            }
+        }
+        Ok(params)
+    }
+
+
+    /// Parameter file contents, see fromText()
+    pub fn toText(&self) -> String {
+        let mut text: String = String::from("# ChessProject evaluation parameters, missing weights keep their default value\n");
+        let scalars: [(&str, i32); 5] = [
+            ("double_pawn_penalty", self.double_pawn_penalty),
+            ("isolated_pawn_penalty", self.isolated_pawn_penalty),
+            ("semi_open_file_score", self.semi_open_file_score),
+            ("open_file_score", self.open_file_score),
+            ("king_blocking_bonus", self.king_blocking_bonus),
+        ];
+        for (name, value) in scalars {
+            text += &format!("{} = {}\n", name, value);
+        }
+        let bonus: Vec<String> = self.passed_pawn_bonus.iter().map(|v| v.to_string()).collect();
+        text += &format!("passed_pawn_bonus = [{}]\n", bonus.join(", "));
+        for (name, values) in [
+            ("piece_scores", &self.piece_scores),
+            ("piece_scores_eg", &self.piece_scores_eg),
+            ("phase_weights", &self.phase_weights),
+        ] {
+            text += "\n";
+            for piece in EvalParams::PIECE_ORDER.chars().filter(|piece| values.contains_key(piece)) {
+                text += &format!("{}.{} = {}\n", name, piece, values[&piece]);
+            }
+        }
+        for (name, tables) in [
+            ("piece_position_scores", &self.piece_position_scores),
+            ("piece_position_scores_eg", &self.piece_position_scores_eg),
+        ] {
+            for piece in EvalParams::PIECE_ORDER.chars().filter(|piece| tables.contains_key(piece)) {
+                text += &format!("\n{}.{} = [\n", name, piece);
+                for row in tables[&piece] {
+                    let row: Vec<String> = row.iter().map(|v| format!("{:>4}", v)).collect();
+                    text += &format!("    {},\n", row.join(","));
+                }
+                text += "]\n";
+            }
+        }
+        text
+    }
+
+
+    /// Names of all single weights in file order
+    pub fn paramNames(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![
+            String::from("double_pawn_penalty"),
+            String::from("isolated_pawn_penalty"),
+            String::from("semi_open_file_score"),
+            String::from("open_file_score"),
+            String::from("king_blocking_bonus"),
+        ];
+        names.extend((0..8).map(|rank| format!("passed_pawn_bonus.{}", rank)));
+        for (name, values) in [
+            ("piece_scores", &self.piece_scores),
+            ("piece_scores_eg", &self.piece_scores_eg),
+            ("phase_weights", &self.phase_weights),
+        ] {
+            names.extend(EvalParams::PIECE_ORDER.chars().filter(|piece| values.contains_key(piece)).map(|piece| format!("{}.{}", name, piece)));
+        }
+        for (name, tables) in [
+            ("piece_position_scores", &self.piece_position_scores),
+            ("piece_position_scores_eg", &self.piece_position_scores_eg),
+        ] {
+            for piece in EvalParams::PIECE_ORDER.chars().filter(|piece| tables.contains_key(piece)) {
+                names.extend((0..64).map(|sq| format!("{}.{}.{}", name, piece, squareName(sq))));
+            }
+        }
+        names
+    }
+
+
+    /// Value of a single weight, None for unknown names
+    pub fn getParam(&self, name: &str) -> Option<i32> {
+        let parts: Vec<&str> = name.split('.').collect();
+        let piece: Option<char> = parts.get(1).and_then(|part| part.chars().next()).filter(|_| parts[1].len() == 1);
+        match parts[..] {
+            ["double_pawn_penalty"] => Some(self.double_pawn_penalty),
+            ["isolated_pawn_penalty"] => Some(self.isolated_pawn_penalty),
+            ["semi_open_file_score"] => Some(self.semi_open_file_score),
+            ["open_file_score"] => Some(self.open_file_score),
+            ["king_blocking_bonus"] => Some(self.king_blocking_bonus),
+            ["passed_pawn_bonus", rank] => rank.parse::<usize>().ok().and_then(|rank| self.passed_pawn_bonus.get(rank).copied()),
+            ["piece_scores", _] => self.piece_scores.get(&piece?).copied(),
+            ["piece_scores_eg", _] => self.piece_scores_eg.get(&piece?).copied(),
+            ["phase_weights", _] => self.phase_weights.get(&piece?).copied(),
+            ["piece_position_scores", _, square] => {
+                let sq: usize = squareIdx(square)?;
+                self.piece_position_scores.get(&piece?).map(|table| table[sq / 8][sq % 8])
+            },
+            ["piece_position_scores_eg", _, square] => {
+                let sq: usize = squareIdx(square)?;
+                self.piece_position_scores_eg.get(&piece?).map(|table| table[sq / 8][sq % 8])
+            },
+            _ => None,
+        }
+    }
+
+
+    pub fn setParam(&mut self, name: &str, value: i32) -> Result<(), EvalParamsError> {
+        let param: &mut i32 = self.paramMut(name).ok_or(EvalParamsError::UnknownParam(name.to_string()))?;
+        *param = value;
+        Ok(())
+    }
+}
+
+
+impl EvalParams {
+    /// Mutable reference to the weight with the given name, see getParam()
+    fn paramMut(&mut self, name: &str) -> Option<&mut i32> {
+        let parts: Vec<&str> = name.split('.').collect();
+        let piece: Option<char> = parts.get(1).and_then(|part| part.chars().next()).filter(|_| parts[1].len() == 1);
+        match parts[..] {
+            ["double_pawn_penalty"] => Some(&mut self.double_pawn_penalty),
+            ["isolated_pawn_penalty"] => Some(&mut self.isolated_pawn_penalty),
+            ["semi_open_file_score"] => Some(&mut self.semi_open_file_score),
+            ["open_file_score"] => Some(&mut self.open_file_score),
+            ["king_blocking_bonus"] => Some(&mut self.king_blocking_bonus),
+            ["passed_pawn_bonus", rank] => rank.parse::<usize>().ok().and_then(|rank| self.passed_pawn_bonus.get_mut(rank)),
+            ["piece_scores", _] => self.piece_scores.get_mut(&piece?),
+            ["piece_scores_eg", _] => self.piece_scores_eg.get_mut(&piece?),
+            ["phase_weights", _] => self.phase_weights.get_mut(&piece?),
+            ["piece_position_scores", _, square] => {
+                let sq: usize = squareIdx(square)?;
+                self.piece_position_scores.get_mut(&piece?).map(|table| &mut table[sq / 8][sq % 8])
+            },
+            ["piece_position_scores_eg", _, square] => {
+                let sq: usize = squareIdx(square)?;
+                self.piece_position_scores_eg.get_mut(&piece?).map(|table| &mut table[sq / 8][sq % 8])
+            },
+            _ => None,
+        }
+    }
+}
+
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        EvalParams::new()
+    }
+}
+
+
+/// Square name of a table index, 0 is a8
+fn squareName(sq: usize) -> String {
+    format!("{}{}", (b'a' + (sq % 8) as u8) as char, 8 - sq / 8)
+}
+
+
+/// Table index of a square name ("e4")
+fn squareIdx(square: &str) -> Option<usize> {
+    let chars: Vec<char> = square.chars().collect();
+    match chars[..] {
+        [file @ 'a'..='h', rank @ '1'..='8'] => Some((8 - rank.to_digit(10)? as usize) * 8 + (file as u8 - b'a') as usize),
+        _ => None,
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        let mut params: EvalParams = EvalParams::new();
+        params.setParam("piece_position_scores_eg.K.e4", 55).unwrap();
+        params.setParam("passed_pawn_bonus.6", 170).unwrap();
+        params.setParam("piece_scores.N", 310).unwrap();
+        let text: String = params.toText();
+        assert!(EvalParams::fromText(&text).unwrap() == params);
+        assert!(params.piece_position_scores_eg[&'K'][4][4] == 55);
+        assert!(params.getParam("piece_position_scores_eg.K.e4") == Some(55));
+        assert!(params.paramNames().iter().all(|name| params.getParam(name).is_some()));
+    }
+
+    #[test]
+    fn partial_files_keep_defaults() {
+        let params: EvalParams = EvalParams::fromText("
+            # only a few weights
+            double_pawn_penalty = -25   # stronger
+            passed_pawn_bonus = [0, 5,
+                10, 20, 40, 80, 120, 160]
+        ").unwrap();
+        assert!(params.double_pawn_penalty == -25);
+        assert!(params.passed_pawn_bonus == [0, 5, 10, 20, 40, 80, 120, 160]);
+        assert!(params.isolated_pawn_penalty == EvalParams::new().isolated_pawn_penalty);
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        assert!(EvalParams::fromText("double_pawn_penalty -10") == Err(EvalParamsError::BadLine(1, String::from("double_pawn_penalty -10"))));
+        assert!(EvalParams::fromText("rook_on_seventh = 20") == Err(EvalParamsError::UnknownParam(String::from("rook_on_seventh"))));
+        assert!(EvalParams::fromText("piece_scores.Z = 20") == Err(EvalParamsError::UnknownParam(String::from("piece_scores.Z"))));
+        assert!(EvalParams::fromText("open_file_score = ten") == Err(EvalParamsError::BadValue(String::from("open_file_score"), String::from("ten"))));
+        assert!(EvalParams::fromText("passed_pawn_bonus = [1, 2]") == Err(EvalParamsError::WrongLength(String::from("passed_pawn_bonus"), 8, 2)));
+        assert!(EvalParams::load("no/such/file.toml").is_err());
+    }
+}
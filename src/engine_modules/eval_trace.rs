@@ -0,0 +1,172 @@
+//! Module holding the term by term breakdown of a static evaluation
+
+
+use pyo3::prelude::*;
+use std::fmt;
+use crate::best_move_finder::BestMoveFinder;
+
+
+/// Groups of evaluation features reported separately by EvalTrace
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EvalTerm {
+    Material,
+    PieceSquare,
+    DoubledPawns,
+    IsolatedPawns,
+    PassedPawns,
+    OpenFiles, // rooks on semi-open and open files
+    Mobility, // bishops and queens
+    KingShelter, // pieces next to the king and files open in front of it
+}
+
+
+impl EvalTerm {
+    pub const COUNT: usize = 8;
+
+    pub fn all() -> [EvalTerm; EvalTerm::COUNT] {
+        [
+            EvalTerm::Material,
+            EvalTerm::PieceSquare,
+            EvalTerm::DoubledPawns,
+            EvalTerm::IsolatedPawns,
+            EvalTerm::PassedPawns,
+            EvalTerm::OpenFiles,
+            EvalTerm::Mobility,
+            EvalTerm::KingShelter,
+        ]
+    }
+
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvalTerm::Material => "material",
+            EvalTerm::PieceSquare => "piece_square",
+            EvalTerm::DoubledPawns => "doubled_pawns",
+            EvalTerm::IsolatedPawns => "isolated_pawns",
+            EvalTerm::PassedPawns => "passed_pawns",
+            EvalTerm::OpenFiles => "open_files",
+            EvalTerm::Mobility => "mobility",
+            EvalTerm::KingShelter => "king_shelter",
+        }
+    }
+}
+
+
+/*
+Breakdown of BestMoveFinder::evaluateBoard.
+
+Each term keeps a middlegame and an endgame score per side, [term][0] for white
+and [term][1] for black, both from that side's point of view so a positive
+score is good for the side. Terms that do not depend on the game phase have the
+same middlegame and endgame score.
+*/
+#[pyclass(module = "ChessProject", get_all)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub phase: i32, // 0 with only kings and pawns up to BestMoveFinder::MAX_PHASE
+    pub mg: [[i32; 2]; EvalTerm::COUNT],
+    pub eg: [[i32; 2]; EvalTerm::COUNT],
+}
+
+
+#[pymethods]
+impl EvalTrace {
+    /// Evaluation from white's side, the value returned by evaluateBoard
+    pub fn total(&self) -> i32 {
+        let mg: i32 = self.mg.iter().map(|side| side[0] - side[1]).sum();
+        let eg: i32 = self.eg.iter().map(|side| side[0] - side[1]).sum();
+        (mg * self.phase + eg * (BestMoveFinder::MAX_PHASE - self.phase)) / BestMoveFinder::MAX_PHASE
+    }
+
+
+    /// Phase interpolated (name, white, black) scores of every term
+    pub fn terms(&self) -> Vec<(String, i32, i32)> {
+        EvalTerm::all().iter()
+            .map(|&term| {
+                let [white, black] = self.term(term);
+                (term.name().to_string(), white, black)
+            })
+            .collect()
+    }
+
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+
+    fn __repr__(&self) -> String {
+        format!("EvalTrace(phase={}, total={}, terms={:?})", self.phase, self.total(), self.terms())
+    }
+}
+
+
+impl EvalTrace {
+    /// Score of a term that is the same in every game phase
+    pub fn add(&mut self, term: EvalTerm, white: bool, score: i32) {
+        self.addTapered(term, white, score, score);
+    }
+
+
+    pub fn addTapered(&mut self, term: EvalTerm, white: bool, mg: i32, eg: i32) {
+        let side: usize = if white {0} else {1};
+        self.mg[term as usize][side] += mg;
+        self.eg[term as usize][side] += eg;
+    }
+
+
+    /// Phase interpolated [white, black] scores of a term
+    pub fn term(&self, term: EvalTerm) -> [i32; 2] {
+        let (mg, eg) = (self.mg[term as usize], self.eg[term as usize]);
+        [0, 1].map(|side| (mg[side] * self.phase + eg[side] * (BestMoveFinder::MAX_PHASE - self.phase)) / BestMoveFinder::MAX_PHASE)
+    }
+}
+
+
+/// Table of the terms, totals may differ from the sum of the rows by rounding
+impl fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<16}{:>8}{:>8}{:>8}", "term", "white", "black", "total")?;
+        for (name, white, black) in self.terms() {
+            writeln!(f, "{:<16}{:>8}{:>8}{:>8}", name, white, black, white - black)?;
+        }
+        write!(f, "{:<16}{:>24}", format!("phase {}/{}", self.phase, BestMoveFinder::MAX_PHASE), self.total())
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_modules::{
+        game_state::GameState,
+        moves::Moves,
+        zobrist::Zobrist,
+    };
+
+    #[test]
+    fn trace_matches_evaluation() {
+        let z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        let bmf: BestMoveFinder = BestMoveFinder::new(2);
+
+        // symmetric start position
+        let trace: EvalTrace = bmf.traceEvaluation(&mut m, gs.position.bitboards);
+        assert!(trace.phase == BestMoveFinder::MAX_PHASE && trace.total() == 0);
+        assert!(trace.terms().iter().all(|(_, white, black)| white == black));
+
+        // "moves king when should not" from interesting_positions.txt, white has doubled c-pawns
+        gs.importFEN(&m.masks, &z, String::from("rnb1kb1r/ppp2ppp/5n2/8/3N4/2P5/P1P2PPP/RNB1KB1R w KQkq - 0 1")).unwrap();
+        let trace: EvalTrace = bmf.traceEvaluation(&mut m, gs.position.bitboards);
+        assert!(trace.total() == bmf.evaluateBoard(&mut m, gs.position.bitboards));
+        assert!(trace.term(EvalTerm::DoubledPawns) == [4 * bmf.eval_params.double_pawn_penalty, 0]);
+        assert!(trace.term(EvalTerm::Material)[0] == trace.term(EvalTerm::Material)[1]);
+        let table: String = trace.to_string();
+        assert!(EvalTerm::all().iter().all(|term| table.contains(term.name())));
+        assert!(table.lines().count() == EvalTerm::COUNT + 2);
+    }
+}
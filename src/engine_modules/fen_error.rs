@@ -0,0 +1,48 @@
+//! Module holding the errors found while importing a FEN string
+
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fmt;
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FenError {
+    BadPieceChar(char),
+    WrongRankCount(usize),
+    WrongRankLength(usize), // rank number as printed on the board (8 is the first rank in a FEN)
+    MissingField(&'static str),
+    BadSide(String),
+    BadCastling(String),
+    BadEpSquare(String),
+    BadMoveCounter(String),
+    IllegalPosition(&'static str),
+}
+
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::BadPieceChar(piece_char) => write!(f, "bad piece character '{}'", piece_char),
+            FenError::WrongRankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::WrongRankLength(rank) => write!(f, "rank {} does not have 8 squares", rank),
+            FenError::MissingField(field) => write!(f, "missing {} field", field),
+            FenError::BadSide(side) => write!(f, "bad side to move '{}'", side),
+            FenError::BadCastling(castling) => write!(f, "bad castling rights '{}'", castling),
+            FenError::BadEpSquare(ep) => write!(f, "bad en passant square '{}'", ep),
+            FenError::BadMoveCounter(counter) => write!(f, "bad move counter '{}'", counter),
+            FenError::IllegalPosition(reason) => write!(f, "illegal position, {}", reason),
+        }
+    }
+}
+
+
+impl std::error::Error for FenError {}
+
+
+/// Bad FENs raise a ValueError in python
+impl From<FenError> for PyErr {
+    fn from(err: FenError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
@@ -0,0 +1,26 @@
+//! Module for enumeration of the ways a game can end
+
+
+use pyo3::prelude::*;
+
+
+#[pyclass(module = "ChessProject")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    Ongoing,
+    Checkmate, // side to move is mated
+    Stalemate,
+    FivefoldRepetition,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    ThreefoldRepetition, // claimable draw
+    FiftyMoveRule, // claimable draw
+}
+
+
+#[pymethods]
+impl GameResult {
+    pub fn isDraw(&self) -> bool {
+        !matches!(self, GameResult::Ongoing | GameResult::Checkmate)
+    }
+}
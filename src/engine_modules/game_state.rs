@@ -4,23 +4,29 @@
 use pyo3::prelude::*;
 use crate::{
     castle_rights::CastleRights,
+    chess_move::{
+        Move,
+        MoveList,
+    },
+    fen_error::FenError,
+    game_result::GameResult,
     special_bitboards::SpecialBitBoards,
     moves::Moves,
     piece::Piece,
+    position::Position,
+    san::SanError,
     zobrist::Zobrist,
 };
 
 
 #[pyclass(module = "ChessProject", get_all, set_all)]
+#[derive(Clone)]
 pub struct GameState {
     board: [[char; 8]; 8],
-    pub bitboards: [u64; 13],
-    pub castle_rights: [bool; 4],
-    pub whites_turn: bool,
+    pub position: Position,
     move_log: String,
     recent_piece_moved: char,
     recent_piece_captured: char,
-    pub hash_key: u64,
     pub in_book_opening: bool,
 }
 
@@ -40,17 +46,14 @@ impl GameState {
                 ['P', 'P', 'P', 'P', 'P', 'P', 'P', 'P'],
                 ['R', 'N', 'B', 'Q', 'K', 'B', 'N', 'R'],
             ],
-            bitboards: [0; 13],
-            castle_rights: [true; 4],
-            whites_turn: true,
+            position: Position::new([0; 13], [true; 4], 0, true),
             move_log: String::new(),
             recent_piece_moved: ' ',
             recent_piece_captured: ' ',
-            hash_key: 0,
             in_book_opening: true,
         };
         gs.arrayToI64();
-        gs.hash_key = z.generateHashKey(gs.bitboards, gs.castle_rights, gs.whites_turn);
+        gs.position.hash_key = z.generateHashKey(gs.position.bitboards, gs.position.castle_rights, gs.position.whites_turn);
         return gs;
     }
 
@@ -61,18 +64,18 @@ impl GameState {
             let mut bin_str: String = String::from("0000000000000000000000000000000000000000000000000000000000000000");
             bin_str.replace_range(i..i+1, "1");
             match self.board[i / 8][i % 8] {
-                'P' => self.bitboards[Piece::WP] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'N' => self.bitboards[Piece::WN] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'B' => self.bitboards[Piece::WB] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'R' => self.bitboards[Piece::WR] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'Q' => self.bitboards[Piece::WQ] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'K' => self.bitboards[Piece::WK] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'p' => self.bitboards[Piece::BP] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'n' => self.bitboards[Piece::BN] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'b' => self.bitboards[Piece::BB] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'r' => self.bitboards[Piece::BR] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'q' => self.bitboards[Piece::BQ] += u64::from_str_radix(&bin_str, 2).unwrap(),
-                'k' => self.bitboards[Piece::BK] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'P' => self.position.bitboards[Piece::WP] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'N' => self.position.bitboards[Piece::WN] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'B' => self.position.bitboards[Piece::WB] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'R' => self.position.bitboards[Piece::WR] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'Q' => self.position.bitboards[Piece::WQ] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'K' => self.position.bitboards[Piece::WK] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'p' => self.position.bitboards[Piece::BP] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'n' => self.position.bitboards[Piece::BN] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'b' => self.position.bitboards[Piece::BB] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'r' => self.position.bitboards[Piece::BR] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'q' => self.position.bitboards[Piece::BQ] += u64::from_str_radix(&bin_str, 2).unwrap(),
+                'k' => self.position.bitboards[Piece::BK] += u64::from_str_radix(&bin_str, 2).unwrap(),
                 _ => (),
             }
         }
@@ -83,29 +86,29 @@ impl GameState {
     pub fn drawGameArray(&self) {
         let mut new_board: [[char; 8]; 8] = [[' '; 8]; 8];
         for i in 0..64 {
-            if get_bit!(self.bitboards[Piece::WP], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::WP], i) == 1 {
                 new_board[i / 8][i % 8] = 'P';
-            } else if get_bit!(self.bitboards[Piece::WN], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::WN], i) == 1 {
                 new_board[i / 8][i % 8] = 'N';
-            } else if get_bit!(self.bitboards[Piece::WB], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::WB], i) == 1 {
                 new_board[i / 8][i % 8] = 'B';
-            } else if get_bit!(self.bitboards[Piece::WR], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::WR], i) == 1 {
                 new_board[i / 8][i % 8] = 'R';
-            } else if get_bit!(self.bitboards[Piece::WQ], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::WQ], i) == 1 {
                 new_board[i / 8][i % 8] = 'Q';
-            } else if get_bit!(self.bitboards[Piece::WK], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::WK], i) == 1 {
                 new_board[i / 8][i % 8] = 'K';
-            } else if get_bit!(self.bitboards[Piece::BP], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::BP], i) == 1 {
                 new_board[i / 8][i % 8] = 'p';
-            } else if get_bit!(self.bitboards[Piece::BN], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::BN], i) == 1 {
                 new_board[i / 8][i % 8] = 'n';
-            } else if get_bit!(self.bitboards[Piece::BB], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::BB], i) == 1 {
                 new_board[i / 8][i % 8] = 'b';
-            } else if get_bit!(self.bitboards[Piece::BR], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::BR], i) == 1 {
                 new_board[i / 8][i % 8] = 'r';
-            } else if get_bit!(self.bitboards[Piece::BQ], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::BQ], i) == 1 {
                 new_board[i / 8][i % 8] = 'q';
-            } else if get_bit!(self.bitboards[Piece::BK], i) == 1 {
+            } else if get_bit!(self.position.bitboards[Piece::BK], i) == 1 {
                 new_board[i / 8][i % 8] = 'k';
             } else {
                 new_board[i / 8][i % 8] = '-';
@@ -125,172 +128,520 @@ impl GameState {
     fn updateBoardArray(&mut self) {
         self.board = [[' '; 8]; 8];
         for i in 0..64 {
-            if get_bit!(self.bitboards[Piece::WP], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::WP], i) == 1 {
                 self.board[i / 8][i % 8] = 'P';
             }
-            if get_bit!(self.bitboards[Piece::WN], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::WN], i) == 1 {
                 self.board[i / 8][i % 8] = 'N';
             }
-            if get_bit!(self.bitboards[Piece::WB], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::WB], i) == 1 {
                 self.board[i / 8][i % 8] = 'B';
             }
-            if get_bit!(self.bitboards[Piece::WR], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::WR], i) == 1 {
                 self.board[i / 8][i % 8] = 'R';
             }
-            if get_bit!(self.bitboards[Piece::WQ], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::WQ], i) == 1 {
                 self.board[i / 8][i % 8] = 'Q';
             }
-            if get_bit!(self.bitboards[Piece::WK], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::WK], i) == 1 {
                 self.board[i / 8][i % 8] = 'K';
             }
-            if get_bit!(self.bitboards[Piece::BP], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::BP], i) == 1 {
                 self.board[i / 8][i % 8] = 'p';
             }
-            if get_bit!(self.bitboards[Piece::BN], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::BN], i) == 1 {
                 self.board[i / 8][i % 8] = 'n';
             }
-            if get_bit!(self.bitboards[Piece::BB], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::BB], i) == 1 {
                 self.board[i / 8][i % 8] = 'b';
             }
-            if get_bit!(self.bitboards[Piece::BR], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::BR], i) == 1 {
                 self.board[i / 8][i % 8] = 'r';
             }
-            if get_bit!(self.bitboards[Piece::BQ], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::BQ], i) == 1 {
                 self.board[i / 8][i % 8] = 'q';
             }
-            if get_bit!(self.bitboards[Piece::BK], i) == 1 {
+            if get_bit!(self.position.bitboards[Piece::BK], i) == 1 {
                 self.board[i / 8][i % 8] = 'k';
             }
         }
     }
 
 
-    /// Set the game state to a FEN string
+    /*
+    Set the game state to a FEN string.
+
+    The game state is left untouched when the FEN is invalid. The halfmove clock
+    and fullmove number are optional since many test positions leave them out.
+    */
     pub fn importFEN(
         &mut self,
         sb: &SpecialBitBoards,
         z: &Zobrist,
         fen_str: String,
-    ) {
-        self.bitboards = [0; 13];
-        self.castle_rights = [false; 4];
-        let mut char_idx: usize = 0;
-        let mut board_idx: u32 = 0;
-        while fen_str.chars().nth(char_idx).unwrap() != ' ' {
-            match fen_str.chars().nth(char_idx).unwrap() {
-                'P' => {
-                    set_bit!(self.bitboards[Piece::WP], board_idx);
-                    board_idx += 1;
-                },
-                'N' => {
-                    set_bit!(self.bitboards[Piece::WN], board_idx);
-                    board_idx += 1;
-                },
-                'B' => {
-                    set_bit!(self.bitboards[Piece::WB], board_idx);
-                    board_idx += 1;
-                },
-                'R' => {
-                    set_bit!(self.bitboards[Piece::WR], board_idx);
-                    board_idx += 1;
-                },
-                'Q' => {
-                    set_bit!(self.bitboards[Piece::WQ], board_idx);
-                    board_idx += 1;
-                },
-                'K' => {
-                    set_bit!(self.bitboards[Piece::WK], board_idx);
-                    board_idx += 1;
-                },
-                'p' => {
-                    set_bit!(self.bitboards[Piece::BP], board_idx);
-                    board_idx += 1;
-                },
-                'n' => {
-                    set_bit!(self.bitboards[Piece::BN], board_idx);
-                    board_idx += 1;
-                },
-                'b' => {
-                    set_bit!(self.bitboards[Piece::BB], board_idx);
-                    board_idx += 1;
-                },
-                'r' => {
-                    set_bit!(self.bitboards[Piece::BR], board_idx);
-                    board_idx += 1;
-                },
-                'q' => {
-                    set_bit!(self.bitboards[Piece::BQ], board_idx);
-                    board_idx += 1;
-                },
-                'k' => {
-                    set_bit!(self.bitboards[Piece::BK], board_idx);
-                    board_idx += 1;
-                },
-                '1' => board_idx += 1,
-                '2' => board_idx += 2,
-                '3' => board_idx += 3,
-                '4' => board_idx += 4,
-                '5' => board_idx += 5,
-                '6' => board_idx += 6,
-                '7' => board_idx += 7,
-                '8' => board_idx += 8,
-                _ => (),
+    ) -> Result<(), FenError> {
+        let mut fields = fen_str.split_whitespace();
+        let placement: &str = fields.next().ok_or(FenError::MissingField("piece placement"))?;
+        let side: &str = fields.next().ok_or(FenError::MissingField("side to move"))?;
+        let castling: &str = fields.next().ok_or(FenError::MissingField("castling"))?;
+        let ep: &str = fields.next().ok_or(FenError::MissingField("en passant"))?;
+
+        let mut bitboards: [u64; 13] = [0; 13];
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+        for (row, rank) in ranks.iter().enumerate() {
+            let mut col: u32 = 0;
+            let mut prev_empty: bool = false;
+            for piece_char in rank.chars() {
+                if ('1'..='8').contains(&piece_char) {
+                    // a run of empty squares is a single digit between pieces
+                    if prev_empty {
+                        return Err(FenError::WrongRankLength(8 - row));
+                    }
+                    col += piece_char as u32 - '0' as u32;
+                    prev_empty = true;
+                    continue;
+                }
+                prev_empty = false;
+                let piece: Piece = Piece::allPieces().into_iter()
+                    .find(|piece| *piece == piece_char)
+                    .ok_or(FenError::BadPieceChar(piece_char))?;
+                if col < 8 {
+                    set_bit!(bitboards[piece], row as u32 * 8 + col);
+                }
+                col += 1;
+            }
+            if col != 8 {
+                return Err(FenError::WrongRankLength(8 - row));
             }
-            char_idx += 1;
         }
 
-        char_idx += 1;
-        self.whites_turn = fen_str.chars().nth(char_idx).unwrap() == 'w';
-        char_idx += 2;
+        let whites_turn: bool = match side {
+            "w" => true,
+            "b" => false,
+            _ => return Err(FenError::BadSide(side.to_string())),
+        };
 
-        while fen_str.chars().nth(char_idx).unwrap() != ' ' {
-            match fen_str.chars().nth(char_idx).unwrap() {
-                'K' => self.castle_rights[CastleRights::CWK] = true,
-                'Q' => self.castle_rights[CastleRights::CWQ] = true,
-                'k' => self.castle_rights[CastleRights::CBK] = true,
-                'q' => self.castle_rights[CastleRights::CBQ] = true,
-                _ => (),
+        let mut castle_rights: [bool; 4] = [false; 4];
+        if castling != "-" {
+            for castle_char in castling.chars() {
+                match castle_char {
+                    'K' => castle_rights[CastleRights::CWK] = true,
+                    'Q' => castle_rights[CastleRights::CWQ] = true,
+                    'k' => castle_rights[CastleRights::CBK] = true,
+                    'q' => castle_rights[CastleRights::CBQ] = true,
+                    _ => return Err(FenError::BadCastling(castling.to_string())),
+                }
             }
-            char_idx += 1;
         }
 
-        char_idx += 1;
-        if fen_str.chars().nth(char_idx).unwrap() != '-' {
-            self.bitboards[Piece::EP] = sb.file_masks[fen_str.chars().nth(char_idx).unwrap() as usize - 'a' as usize];
+        if ep != "-" {
+            // target square must be behind a pawn that could have just been pushed two squares
+            let ep_chars: Vec<char> = ep.chars().collect();
+            let (ep_rank, pawn_row, pawn) = if whites_turn {('6', 3, Piece::BP)} else {('3', 4, Piece::WP)};
+            if ep_chars.len() != 2 || !('a'..='h').contains(&ep_chars[0]) || ep_chars[1] != ep_rank {
+                return Err(FenError::BadEpSquare(ep.to_string()));
+            }
+            let col: usize = ep_chars[0] as usize - 'a' as usize;
+            if get_bit!(bitboards[pawn], pawn_row * 8 + col) == 0 {
+                return Err(FenError::BadEpSquare(ep.to_string()));
+            }
+            bitboards[Piece::EP] = sb.file_masks[col];
         }
+
+        let halfmove_clock: u32 = match fields.next() {
+            Some(counter) => counter.parse().map_err(|_| FenError::BadMoveCounter(counter.to_string()))?,
+            None => 0,
+        };
+        let fullmove_number: u32 = match fields.next() {
+            Some(counter) => counter.parse().map_err(|_| FenError::BadMoveCounter(counter.to_string()))?,
+            None => 1,
+        };
+
+        GameState::validatePosition(sb, bitboards, castle_rights, whites_turn)?;
+
+        self.position = Position::new(bitboards, castle_rights, z.generateHashKey(bitboards, castle_rights, whites_turn), whites_turn);
+        self.position.halfmove_clock = halfmove_clock;
+        self.position.fullmove_number = fullmove_number;
+        self.move_log.clear();
         self.updateBoardArray();
-        self.hash_key = z.generateHashKey(self.bitboards, self.castle_rights, self.whites_turn);
         self.in_book_opening = false;
-        // Rest of FEN not used
+        Ok(())
     }
 
 
-    /// Makes a move and updates bitboards and hashkey
-    fn makeMove(
+    /// Export the game state as a FEN string with all six fields
+    pub fn toFEN(&self) -> String {
+        let mut fen_str: String = String::new();
+        for row in 0..8 {
+            let mut empty_count: u32 = 0;
+            for col in 0..8 {
+                match self.board[row][col] {
+                    ' ' => empty_count += 1,
+                    piece_char => {
+                        if empty_count != 0 {
+                            fen_str.push_str(&empty_count.to_string());
+                            empty_count = 0;
+                        }
+                        fen_str.push(piece_char);
+                    },
+                }
+            }
+            if empty_count != 0 {
+                fen_str.push_str(&empty_count.to_string());
+            }
+            if row != 7 {
+                fen_str.push('/');
+            }
+        }
+
+        fen_str.push_str(if self.position.whites_turn {" w "} else {" b "});
+
+        let castle_chars: [(CastleRights, char); 4] = [
+            (CastleRights::CWK, 'K'),
+            (CastleRights::CWQ, 'Q'),
+            (CastleRights::CBK, 'k'),
+            (CastleRights::CBQ, 'q'),
+        ];
+        let castle_len: usize = fen_str.len();
+        for (right, castle_char) in castle_chars {
+            if self.position.castle_rights[right] {
+                fen_str.push(castle_char);
+            }
+        }
+        if fen_str.len() == castle_len {
+            fen_str.push('-');
+        }
+
+        // the enpassant target square is behind the pawn that just moved
+        let ep: u64 = self.position.bitboards[Piece::EP];
+        if ep != 0 {
+            let col: u8 = ep.leading_zeros() as u8;
+            fen_str.push_str(&format!(" {}{} ", (b'a' + col) as char, if self.position.whites_turn {6} else {3}));
+        } else {
+            fen_str.push_str(" - ");
+        }
+
+        fen_str.push_str(&format!("{} {}", self.position.halfmove_clock, self.position.fullmove_number));
+        fen_str
+    }
+
+
+    /// Makes a move given as a 4 character move string
+    #[pyo3(name = "makeMove")]
+    fn makeMoveStr(
         &mut self,
-        mm: &Moves,
-        z: &mut Zobrist,
+        _mm: &Moves,
+        z: &Zobrist,
         move_str: String,
     ) {
-        if move_str.chars().nth(3).unwrap() == 'E' {
-            self.recent_piece_captured = if self.whites_turn {'p'} else {'P'};
-            self.recent_piece_moved = if self.whites_turn {'P'} else {'p'};
-        } else if move_str.chars().nth(3).unwrap() == 'P' {
-            let (_, _, _, c2) = move_to_u32s!(move_str);
-            self.recent_piece_captured = self.board[if self.whites_turn {0} else {7}][c2 as usize];
-            self.recent_piece_moved = if self.whites_turn {'P'} else {'p'};
+        let mv: Move = Move::fromMoveStr(&move_str, &self.position.bitboards);
+        self.makeMove(z, mv);
+    }
+
+
+    /// Takes back the last move, returns it as a 4 character move string
+    #[pyo3(name = "undoMove")]
+    fn undoMoveStr(&mut self) -> Option<String> {
+        self.undoMove().map(|mv| mv.toMoveStr())
+    }
+
+
+    #[getter(bitboards)]
+    fn getBitboards(&self) -> [u64; 13] {
+        self.position.bitboards
+    }
+
+
+    #[getter(castle_rights)]
+    fn getCastleRights(&self) -> [bool; 4] {
+        self.position.castle_rights
+    }
+
+
+    #[getter(whites_turn)]
+    fn getWhitesTurn(&self) -> bool {
+        self.position.whites_turn
+    }
+
+
+    #[getter(hash_key)]
+    fn getHashKey(&self) -> u64 {
+        self.position.hash_key
+    }
+
+
+    /*
+    Status of the game for the side to move, checked in order:
+        - Checkmate / Stalemate
+        - Fivefold repetition / Seventy-five move rule (automatic draws)
+        - Insufficient material
+        - Threefold repetition / Fifty move rule (draws that can be claimed)
+    */
+    pub fn gameStatus(&self, mm: &mut Moves, z: &mut Zobrist) -> GameResult {
+        let valid_moves: MoveList = mm.getValidMoves(z, self.position.bitboards, self.position.castle_rights, self.position.hash_key, self.position.whites_turn);
+        let repetitions: usize = self.position.repetitionCount();
+        if valid_moves.is_empty() {
+            if mm.checkmate {GameResult::Checkmate} else {GameResult::Stalemate}
+        } else if repetitions >= 5 {
+            GameResult::FivefoldRepetition
+        } else if self.position.halfmove_clock >= 150 {
+            GameResult::SeventyFiveMoveRule
+        } else if self.position.isInsufficientMaterial() {
+            GameResult::InsufficientMaterial
+        } else if repetitions >= 3 {
+            GameResult::ThreefoldRepetition
+        } else if self.position.halfmove_clock >= 100 {
+            GameResult::FiftyMoveRule
         } else {
-            let (r1, c1, r2, c2) = move_to_u32s!(move_str);
-            self.recent_piece_captured = self.board[r2 as usize][c2 as usize];
-            self.recent_piece_moved = self.board[r1 as usize][c1 as usize];
+            GameResult::Ongoing
         }
+    }
 
-        self.move_log.push_str(&move_str);
-        let bitboards_cached: [u64; 13] = self.bitboards;
-        (self.bitboards, self.hash_key) = mm.getUpdatedBitboards(z, &move_str, self.bitboards, self.hash_key, self.whites_turn);
-        (self.castle_rights, self.hash_key) = mm.getUpdatedCastleRights(z, &move_str, self.castle_rights, bitboards_cached, self.hash_key);
 
-        self.whites_turn = !self.whites_turn;
+    /// Find the valid move matching a coordinate notation move as a 4 character move string
+    #[pyo3(name = "findAlgebraMove")]
+    fn findAlgebraMoveStr(
+        &self,
+        mm: &mut Moves,
+        z: &mut Zobrist,
+        alg_str: &str,
+    ) -> Option<String> {
+        self.findAlgebraMove(mm, z, alg_str).map(|mv| mv.toMoveStr())
+    }
+
+
+    /// Convert a valid 4 character move string to SAN
+    #[pyo3(name = "moveToSAN")]
+    fn moveToSANStr(
+        &self,
+        mm: &mut Moves,
+        z: &mut Zobrist,
+        move_str: &str,
+    ) -> String {
+        let mv: Move = Move::fromMoveStr(move_str, &self.position.bitboards);
+        self.moveToSAN(mm, z, mv)
+    }
+
+
+    /// Find the valid move matching a SAN string as a 4 character move string
+    #[pyo3(name = "sanToMove")]
+    fn sanToMoveStr(
+        &self,
+        mm: &mut Moves,
+        z: &mut Zobrist,
+        san_str: &str,
+    ) -> Result<String, SanError> {
+        self.sanToMove(mm, z, san_str).map(|mv| mv.toMoveStr())
+    }
+}
+
+
+/// Game progressing functions that work with moves directly
+impl GameState {
+    /// Find the valid move matching a coordinate notation move ("e2e4", "e7e8q")
+    pub fn findAlgebraMove(
+        &self,
+        mm: &mut Moves,
+        z: &mut Zobrist,
+        alg_str: &str,
+    ) -> Option<Move> {
+        let valid_moves: MoveList = mm.getValidMoves(z, self.position.bitboards, self.position.castle_rights, self.position.hash_key, self.position.whites_turn);
+        valid_moves.iter()
+            .find(|mv| mv.to_string().eq_ignore_ascii_case(alg_str))
+            .copied()
+    }
+
+
+    /// Makes a move and updates bitboards and hashkey
+    pub fn makeMove(&mut self, z: &Zobrist, mv: Move) {
+        let (start_sq, end_sq) = (mv.startSquare() as usize, mv.endSquare() as usize);
+        if mv.isEnPassant() {
+            self.recent_piece_captured = if self.position.whites_turn {'p'} else {'P'};
+        } else {
+            self.recent_piece_captured = self.board[end_sq / 8][end_sq % 8];
+        }
+        self.recent_piece_moved = self.board[start_sq / 8][start_sq % 8];
+
+        self.move_log.push_str(&mv.toMoveStr());
+        self.position.makeMove(z, mv);
+        self.updateBoardArray();
+    }
+
+
+    /*
+    Check a position can be reached in a game:
+        - Each side has exactly one king
+        - No pawns on the first or last rank
+        - Castle rights only where the king and rook have not moved
+        - Side not to move is not in check
+    */
+    fn validatePosition(
+        sb: &SpecialBitBoards,
+        bitboards: [u64; 13],
+        castle_rights: [bool; 4],
+        whites_turn: bool,
+    ) -> Result<(), FenError> {
+        if bitboards[Piece::WK].count_ones() != 1 || bitboards[Piece::BK].count_ones() != 1 {
+            return Err(FenError::IllegalPosition("each side needs exactly one king"));
+        }
+        let back_ranks: u64 = 0xff000000000000ff;
+        if (bitboards[Piece::WP] | bitboards[Piece::BP]) & back_ranks != 0 {
+            return Err(FenError::IllegalPosition("pawns on the first or last rank"));
+        }
+        let castle_pieces: [(CastleRights, Piece, u32, Piece, u32); 4] = [
+            (CastleRights::CWK, Piece::WK, 60, Piece::WR, 63),
+            (CastleRights::CWQ, Piece::WK, 60, Piece::WR, 56),
+            (CastleRights::CBK, Piece::BK, 4, Piece::BR, 7),
+            (CastleRights::CBQ, Piece::BK, 4, Piece::BR, 0),
+        ];
+        for (right, king, king_sq, rook, rook_sq) in castle_pieces {
+            if castle_rights[right] && (get_bit!(bitboards[king], king_sq) == 0 || get_bit!(bitboards[rook], rook_sq) == 0) {
+                return Err(FenError::IllegalPosition("castle rights without the king and rook on their start squares"));
+            }
+        }
+        if Moves::withMasks(sb.clone()).isKingAttacked(bitboards, !whites_turn) {
+            return Err(FenError::IllegalPosition("side not to move is in check"));
+        }
+        Ok(())
+    }
+
+
+    /// Takes back the last move, returns None if no moves have been made
+    pub fn undoMove(&mut self) -> Option<Move> {
+        let mv: Move = self.position.unmakeMove()?;
+        self.move_log.truncate(self.move_log.len() - mv.toMoveStr().len());
+        self.recent_piece_moved = ' ';
+        self.recent_piece_captured = ' ';
         self.updateBoardArray();
+        Some(mv)
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FEN strings passed to importFEN in a source or text file
+    fn fensIn(text: &str) -> Vec<String> {
+        text.lines()
+            .filter(|line| line.contains("importFEN("))
+            .filter_map(|line| line.split(['\'', '"']).nth(1))
+            .map(|fen| fen.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        let z: Zobrist = Zobrist::new();
+        let m: Moves = Moves::new();
+        let mut fens: Vec<String> = fensIn(include_str!("../interesting_positions.txt"));
+        fens.extend(fensIn(include_str!("perft.rs")));
+        assert!(fens.len() > 30);
+        for fen in fens {
+            let mut gs: GameState = GameState::new(&z);
+            gs.importFEN(&m.masks, &z, fen.clone()).unwrap();
+            let fen_out: String = gs.toFEN();
+            if fen.split_whitespace().count() == 6 {
+                assert!(fen_out == fen, "{} != {}", fen_out, fen);
+            }
+            let mut gs_t: GameState = GameState::new(&z);
+            gs_t.importFEN(&m.masks, &z, fen_out.clone()).unwrap();
+            assert!(gs_t.toFEN() == fen_out);
+            assert!(gs_t.position.bitboards == gs.position.bitboards);
+            assert!(gs_t.position.castle_rights == gs.position.castle_rights);
+            assert!(gs_t.position.whites_turn == gs.position.whites_turn);
+            assert!(gs_t.position.hash_key == gs.position.hash_key);
+            assert!(gs_t.position.halfmove_clock == gs.position.halfmove_clock);
+            assert!(gs_t.position.fullmove_number == gs.position.fullmove_number);
+        }
+    }
+
+    #[test]
+    fn move_counters_follow_moves() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        assert!(gs.toFEN() == "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        for alg_str in ["e2e4", "g8f6", "g1f3"] {
+            let mv: Move = gs.findAlgebraMove(&mut m, &mut z, alg_str).unwrap();
+            gs.makeMove(&z, mv);
+        }
+        assert!(gs.toFEN() == "rnbqkb1r/pppppppp/5n2/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 2 2");
+        gs.undoMove();
+        gs.undoMove();
+        assert!(gs.toFEN() == "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    }
+
+    #[test]
+    fn bad_fens_are_rejected() {
+        let z: Zobrist = Zobrist::new();
+        let m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        let start_fen: String = gs.toFEN();
+        for (fen, err) in [
+            ("", FenError::MissingField("piece placement")),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq", FenError::MissingField("en passant")),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1", FenError::WrongRankCount(7)),
+            ("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPieceChar('9')),
+            ("rnbqkbnr/pppppppp/08/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPieceChar('0')),
+            ("rnbqkbnr/pppppppp/8/3p04/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPieceChar('0')),
+            ("rnbqkbnr/pppppppp/44/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::WrongRankLength(6)),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR1 w KQkq - 0 1", FenError::WrongRankLength(1)),
+            ("rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::BadPieceChar('x')),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", FenError::BadSide(String::from("x"))),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1", FenError::BadCastling(String::from("KQkx"))),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e6 0 1", FenError::BadEpSquare(String::from("e6"))),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1", FenError::BadEpSquare(String::from("e6"))),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1", FenError::BadMoveCounter(String::from("x"))),
+            ("rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1", FenError::IllegalPosition("each side needs exactly one king")),
+            ("rnbqkbnP/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNR w KQq - 0 1", FenError::IllegalPosition("pawns on the first or last rank")),
+            ("rnbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", FenError::IllegalPosition("castle rights without the king and rook on their start squares")),
+            ("4k2R/8/8/8/8/8/8/4K3 w - - 0 1", FenError::IllegalPosition("side not to move is in check")),
+        ] {
+            assert!(gs.importFEN(&m.masks, &z, String::from(fen)) == Err(err));
+            assert!(gs.toFEN() == start_fen);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn game_status_detects_endings() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        for (fen, result) in [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", GameResult::Ongoing),
+            ("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", GameResult::Checkmate),
+            ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", GameResult::Stalemate),
+            ("8/8/4k3/8/8/4K3/8/8 w - - 0 1", GameResult::InsufficientMaterial),
+            ("8/8/4k3/8/8/4KN2/8/8 w - - 0 1", GameResult::InsufficientMaterial),
+            ("8/8/2b1k3/8/8/4KB2/8/8 w - - 0 1", GameResult::InsufficientMaterial),
+            ("8/8/3bk3/8/8/4KB2/8/8 w - - 0 1", GameResult::Ongoing),
+            ("8/8/4k3/8/8/3NKN2/8/8 w - - 0 1", GameResult::Ongoing),
+            ("8/8/4k3/8/8/4K3/8/R7 w - - 100 80", GameResult::FiftyMoveRule),
+            ("8/8/4k3/8/8/4K3/8/R7 w - - 150 80", GameResult::SeventyFiveMoveRule),
+        ] {
+            gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+            assert!(gs.gameStatus(&mut m, &mut z) == result);
+        }
+
+        // knights going back and forth repeat the start position every 4 plies
+        gs = GameState::new(&z);
+        for (i, alg_str) in ["g1f3", "g8f6", "f3g1", "f6g8"].iter().cycle().take(16).enumerate() {
+            let mv: Move = gs.findAlgebraMove(&mut m, &mut z, alg_str).unwrap();
+            gs.makeMove(&z, mv);
+            let result: GameResult = match i + 1 {
+                8..=15 => GameResult::ThreefoldRepetition,
+                16 => GameResult::FivefoldRepetition,
+                _ => GameResult::Ongoing,
+            };
+            assert!(gs.gameStatus(&mut m, &mut z) == result);
+        }
+    }
+}
@@ -133,9 +133,8 @@ If no piece captured then Piece::EP returned.
 #[macro_export]
 macro_rules! get_move_pieces {
     ($bitboards:expr, $move:expr) => {{
-        let (r1, c1, r2, c2) = move_to_u32s!($move);
-        let start_sq: u32 = r1 * 8 + c1;
-        let end_sq: u32 = r2 * 8 + c2;
+        let start_sq: u32 = $move.startSquare();
+        let end_sq: u32 = $move.endSquare();
         let pieces: [Piece; 12] = Piece::allPieces();
         let mut start_piece: Piece = Piece::EP; // default for no piece captured
         let mut end_piece: Piece = Piece::EP; // default for no piece captured
@@ -3,10 +3,28 @@ pub mod macros;
 pub mod special_bitboards;
 pub mod game_state;
 pub mod moves;
+pub mod see;
+pub mod chess_move;
+pub mod position;
+pub mod fen_error;
+pub mod game_result;
+pub mod san;
+pub mod pgn;
 pub mod perft;
 pub mod best_move_finder;
+pub mod eval_params;
+pub mod eval_trace;
+pub mod tuner;
+pub mod time_manager;
+pub mod search_limits;
+pub mod search_context;
+pub mod search_info;
+pub mod async_search;
 pub mod piece;
 pub mod castle_rights;
 pub mod zobrist;
 pub mod trans_table;
 pub mod opening_book;
+pub mod polyglot;
+pub mod uci;
+pub mod xboard;
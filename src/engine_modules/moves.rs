@@ -4,13 +4,19 @@
 use pyo3::prelude::*;
 use crate::{
     castle_rights::CastleRights,
+    chess_move::{
+        Move,
+        MoveList,
+    },
     special_bitboards::SpecialBitBoards,
     piece::Piece,
+    position::Position,
     zobrist::Zobrist,
 };
 
 
 #[pyclass(module = "ChessProject", get_all, set_all)]
+#[derive(Clone)]
 pub struct Moves {
     pub castle_rooks: [usize; 4], // squares from top-left to bottom-right
     pub masks: SpecialBitBoards,
@@ -23,17 +29,13 @@ pub struct Moves {
 impl Moves {
     #[new]
     pub fn new() -> Self {
-        Moves {
-            castle_rooks: [0, 7, 56, 63],
-            masks: SpecialBitBoards::new(),
-            checkmate: false,
-            stalemate: false,
-        }
+        Moves::withMasks(SpecialBitBoards::new())
     }
 
 
-    /// Gets the valid moves of a game state
-    pub fn getValidMoves(
+    /// Gets the valid moves of a game state as a string of 4 character moves
+    #[pyo3(name = "getValidMoves")]
+    fn getValidMovesStr(
         &mut self,
         z: &mut Zobrist,
         bitboards: [u64; 13],
@@ -41,169 +43,333 @@ impl Moves {
         hash_key: u64,
         whites_turn: bool,
     ) -> String {
-        let moves: String = self.getPossibleMoves(bitboards, castle_rights, whites_turn);
-        let mut valid_moves: String = String::new();
-        for i in (0..moves.len()).step_by(4) {
-            let (bitboards_t, _) = self.getUpdatedBitboards(z, &moves[i..i+4], bitboards, hash_key, whites_turn);
-            if self.isValidMove(bitboards_t, whites_turn) {
-                valid_moves += &moves[i..i+4];
+        self.getValidMoves(z, bitboards, castle_rights, hash_key, whites_turn).toMoveStr()
+    }
+
+
+    /// Gets the possible (pseudo-legal) moves of a game state as a string of 4 character moves
+    #[pyo3(name = "getPossibleMoves")]
+    fn getPossibleMovesStr(
+        &mut self,
+        bitboards: [u64; 13],
+        castle_rights: [bool; 4],
+        whites_turn: bool,
+    ) -> String {
+        self.getPossibleMoves(bitboards, castle_rights, whites_turn).toMoveStr()
+    }
+
+
+    /*
+    Returns all possible horizontal and vertical moves of piece at index piece_idx
+
+    Example for formula derivation:
+    occupied = o = 11000101 -> wP wP -- -- -- bR -- wP
+    slider = s = 00000100
+    o - s = 11000001 -> removes slider bit
+    o - 2s = 10111101 -> flips bits left of slider bit until first seen occupied bit (inclusive)
+    left = o^(o-2s) = 01111000 -> extracts all possible left sliding positions including first taken piece
+    let o' denote reverse of o
+    right = (o'^(o'-2s'))' = o^(o'-2s')' = 00000011
+    lineAttacks_h = right^left = o^(o'-2s')' ^ o^(o-2s) = (o'-2s')' ^ (o-2s)
+    m = mask
+    lineAttacks_v = (((o&m)'-2s')' ^ ((o&m)-2s))
+
+    return (possible_h & rank_m) | (possible_v & file_m) to only consider one file and rank
+    */
+    pub fn possibleHAndVMoves(&self, piece_idx: usize) -> u64 {
+        // piece_idx = 0 -> top left of board -> 1000...000
+        let binary_idx: u64 = 1 << (64 - 1 - piece_idx);
+        let rank_mask = self.masks.rank_masks[piece_idx / 8];
+        let file_mask = self.masks.file_masks[piece_idx % 8];
+        let possible_h = (wrap_op!(self.masks.occupied, wrap_op!(binary_idx, 2, '*'), '-'))
+            ^ (wrap_op!(
+                self.masks.occupied.reverse_bits(),
+                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
+                '-'
+            )).reverse_bits();
+        let possible_v = (wrap_op!((self.masks.occupied & file_mask), wrap_op!(binary_idx, 2, '*'), '-'))
+            ^ (wrap_op!(
+                (self.masks.occupied & file_mask).reverse_bits(),
+                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
+                '-'
+            )).reverse_bits();
+        (possible_h & rank_mask) | (possible_v & file_mask)
+    }
+
+
+    /*
+    Returns all possible diagonal and anti-diagonal moves of piece at index piece_idx
+
+    See possibleHAndVMoves func description for formula derivation
+    */
+    pub fn possibleDiagAndAntiDiagMoves(&self, piece_idx: usize) -> u64 {
+        // piece_idx = 0 -> top left of board -> 1000...000
+        let binary_idx: u64 = 1 << (64 - 1 - piece_idx);
+        let diag_mask = self.masks.diagonal_masks[(piece_idx / 8) + (piece_idx % 8)];
+        let a_diag_mask = self.masks.anti_diagonal_masks[7 + (piece_idx / 8) - (piece_idx % 8)];
+        let possible_d = (wrap_op!((self.masks.occupied & diag_mask), wrap_op!(binary_idx, 2, '*'), '-'))
+            ^ (wrap_op!(
+                (self.masks.occupied & diag_mask).reverse_bits(),
+                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
+                '-'
+            )).reverse_bits();
+        let possible_ad = (wrap_op!((self.masks.occupied & a_diag_mask), wrap_op!(binary_idx, 2, '*'), '-'))
+            ^ (wrap_op!(
+                (self.masks.occupied & a_diag_mask).reverse_bits(),
+                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
+                '-'
+            )).reverse_bits();
+        (possible_d & diag_mask) | (possible_ad & a_diag_mask)
+    }
+
+
+    /// Generates a bitboard with 1's where white attacks
+    pub fn unsafeForBlack(&mut self, mut bitboards: [u64; 13]) -> u64 {
+        self.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
+        // pawn threats
+        let mut unsafe_b: u64 = (bitboards[Piece::WP] << 7) & !self.masks.file_masks[0]; // pawn right capture
+        set_bits!(unsafe_b, (bitboards[Piece::WP] << 9) & !self.masks.file_masks[7]); // pawn left capture
+
+        // knight threat
+        let mut knight: u64 = get_ls1b!(bitboards[Piece::WN]);
+        let knight_span_c6_idx: usize = 18;
+        while knight != 0 {
+            let knight_idx: usize = knight.leading_zeros() as usize;
+            // allign the knight_span_c6 mask
+            let mut moves: u64;
+            if knight_idx <= knight_span_c6_idx {
+                moves = self.masks.knight_span_c6 << (knight_span_c6_idx - knight_idx);
+            } else {
+                moves = self.masks.knight_span_c6 >> (knight_idx - knight_span_c6_idx);
             }
-        }
-        if valid_moves.len() == 0 {
-            if self.isKingAttacked(bitboards, whites_turn) {
-                self.checkmate = true;
+            // remove moves sliding off board or allied pieces
+            if knight_idx % 8 < 4 {
+                pop_bits!(moves, self.masks.file_gh);
             } else {
-                self.stalemate = true;
+                pop_bits!(moves, self.masks.file_ab);
             }
-        } else {
-            self.checkmate = false;
-            self.stalemate = false;
+            set_bits!(unsafe_b, moves);
+            pop_bits!(bitboards[Piece::WN], knight); // remove current knight
+            knight = get_ls1b!(bitboards[Piece::WN]);
+        }
+
+        // bishop / queen threats (diagonals)
+        let mut wQB: u64 = bitboards[Piece::WQ] | bitboards[Piece::WB];
+        let mut b_or_q: u64 = get_ls1b!(wQB);
+        while b_or_q != 0 {
+            let b_or_q_idx: usize = b_or_q.leading_zeros() as usize;
+            let moves: u64 = self.possibleDiagAndAntiDiagMoves(b_or_q_idx);
+            set_bits!(unsafe_b, moves);
+            pop_bits!(wQB, b_or_q); // remove current bishop or queen
+            b_or_q = get_ls1b!(wQB);
         }
-        valid_moves
-    }
 
+        // rook / queen threats (hor and vert)
+        let mut wQR: u64 = bitboards[Piece::WQ] | bitboards[Piece::WR];
+        let mut r_or_q: u64 = get_ls1b!(wQR);
+        while r_or_q != 0 {
+            let r_or_q_idx: usize = r_or_q.leading_zeros() as usize;
+            let moves: u64 = self.possibleHAndVMoves(r_or_q_idx);
+            set_bits!(unsafe_b, moves);
+            pop_bits!(wQR, r_or_q); // remove current rook or queen
+            r_or_q = get_ls1b!(wQR);
+        }
 
-    /// Makes a move for a specified piece type given a move string
-    pub fn makeMove(
-        &self,
-        z: &mut Zobrist,
-        mut bitboard: u64,
-        mut hash_key: u64,
-        move_str: &str,
-        p_type: Piece,
-    ) -> (u64, u64) {
-        let (r1, c1, r2, c2) = move_to_u32s!(move_str);
-        let start_sq: u32 = r1 * 8 + c1;
-        let end_sq: u32 = r2 * 8 + c2;
-        if move_str.chars().nth(3).unwrap().is_numeric() { // regular move
-            if get_bit!(bitboard, end_sq) == 1 {
-                hash_key ^= z.piece_keys[p_type][end_sq as usize]; // remove taken piece from hash
-            }
-            if get_bit!(bitboard, start_sq) == 1 {
-                hash_key ^= z.piece_keys[p_type][start_sq as usize]; // remove source piece from hash
-                hash_key ^= z.piece_keys[p_type][end_sq as usize]; // add target piece to hash
-                pop_bit!(bitboard, start_sq);
-                set_bit!(bitboard, end_sq);
+        // king threats
+        let mut king: u64 = get_ls1b!(bitboards[Piece::WK]);
+        let king_span_c7_idx: usize = 10;
+        while king != 0 {
+            let king_idx: usize = king.leading_zeros() as usize;
+            // allign the king_span_c7 mask
+            let mut moves: u64;
+            if king_idx <= king_span_c7_idx {
+                moves = self.masks.king_span_c7 << (king_span_c7_idx - king_idx);
             } else {
-                pop_bit!(bitboard, end_sq);
+                moves = self.masks.king_span_c7 >> (king_idx - king_span_c7_idx);
             }
-        } else if move_str.chars().nth(3).unwrap() == 'P' { // pawn promo
-            if get_bit!(bitboard, end_sq) == 1 {
-                hash_key ^= z.piece_keys[p_type][end_sq as usize]; // remove taken piece from hash
+            // remove moves sliding off board or allied pieces
+            if king_idx % 8 < 4 {
+                pop_bits!(moves, self.masks.file_gh);
+            } else {
+                pop_bits!(moves, self.masks.file_ab);
             }
-            if get_bit!(bitboard, start_sq) == 1 {
-                hash_key ^= z.piece_keys[p_type][start_sq as usize]; // remove source piece from hash
+            set_bits!(unsafe_b, moves);
+            pop_bits!(bitboards[Piece::WK], king); // remove current king
+            king = get_ls1b!(bitboards[Piece::WK]);
+        }
+        unsafe_b
+    }
+
+
+    /// Generates a bitboard with 1's where black attacks
+    pub fn unsafeForWhite(&mut self, mut bitboards: [u64; 13]) -> u64 {
+        self.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
+        // pawn threats
+        let mut unsafe_w: u64 = (bitboards[Piece::BP] >> 7) & !self.masks.file_masks[7]; // pawn right capture
+        set_bits!(unsafe_w, (bitboards[Piece::BP] >> 9) & !self.masks.file_masks[0]); // pawn left capture
+
+        // knight threat
+        let mut knight: u64 = get_ls1b!(bitboards[Piece::BN]);
+        let knight_span_c6_idx: usize = 18;
+        while knight != 0 {
+            let knight_idx: usize = knight.leading_zeros() as usize;
+            // allign the knight_span_c6 mask
+            let mut moves: u64;
+            if knight_idx <= knight_span_c6_idx {
+                moves = self.masks.knight_span_c6 << (knight_span_c6_idx - knight_idx);
+            } else {
+                moves = self.masks.knight_span_c6 >> (knight_idx - knight_span_c6_idx);
             }
-            if p_type == move_str.chars().nth(2).unwrap() {
-                hash_key ^= z.piece_keys[p_type][end_sq as usize]; // add promoted piece to hash
-                set_bit!(bitboard, end_sq);
+            // remove moves sliding off board or allied pieces
+            if knight_idx % 8 < 4 {
+                pop_bits!(moves, self.masks.file_gh);
             } else {
-                pop_bit!(bitboard, start_sq);
-                pop_bit!(bitboard, end_sq);
+                pop_bits!(moves, self.masks.file_ab);
             }
-        } else if move_str.chars().nth(3).unwrap() == 'E' { // enpassant
-            if move_str.chars().nth(2).unwrap() == 'w' { // white
-                if get_bit!(bitboard, end_sq + 8) == 1 {
-                    hash_key ^= z.piece_keys[p_type][(r1 * 8 + c2) as usize] // remove taken piece from hash
-                }
-                pop_bits!(bitboard, self.masks.file_masks[c2 as usize] & self.masks.rank_masks[3]);
-            } else { // black
-                if get_bit!(bitboard, end_sq - 8) == 1 {
-                    hash_key ^= z.piece_keys[p_type][(r1 * 8 + c2) as usize] // remove taken piece from hash
-                }
-                pop_bits!(bitboard, self.masks.file_masks[c2 as usize] & self.masks.rank_masks[4]);
+            set_bits!(unsafe_w, moves);
+            pop_bits!(bitboards[Piece::BN], knight); // remove current knight
+            knight = get_ls1b!(bitboards[Piece::BN]);
+        }
+
+        // bishop / queen threats (diagonals)
+        let mut bQB: u64 = bitboards[Piece::BQ] | bitboards[Piece::BB];
+        let mut b_or_q: u64 = get_ls1b!(bQB);
+        while b_or_q != 0 {
+            let b_or_q_idx: usize = b_or_q.leading_zeros() as usize;
+            let moves: u64 = self.possibleDiagAndAntiDiagMoves(b_or_q_idx);
+            set_bits!(unsafe_w, moves);
+            pop_bits!(bQB, b_or_q); // remove current bishop or queen
+            b_or_q = get_ls1b!(bQB);
+        }
+
+        // rook / queen threats (hor and vert)
+        let mut bQR: u64 = bitboards[Piece::BQ] | bitboards[Piece::BR];
+        let mut r_or_q: u64 = get_ls1b!(bQR);
+        while r_or_q != 0 {
+            let r_or_q_idx: usize = r_or_q.leading_zeros() as usize;
+            let moves: u64 = self.possibleHAndVMoves(r_or_q_idx);
+            set_bits!(unsafe_w, moves);
+            pop_bits!(bQR, r_or_q); // remove current rook or queen
+            r_or_q = get_ls1b!(bQR);
+        }
+
+        // king threats
+        let mut king = get_ls1b!(bitboards[Piece::BK]);
+        let king_span_c7_idx: usize = 10;
+        while king != 0 {
+            let king_idx: usize = king.leading_zeros() as usize;
+            // allign the king_span_c7 mask
+            let mut moves: u64;
+            if king_idx <= king_span_c7_idx {
+                moves = self.masks.king_span_c7 << (king_span_c7_idx - king_idx);
+            } else {
+                moves = self.masks.king_span_c7 >> (king_idx - king_span_c7_idx);
             }
-            if get_bit!(bitboard, start_sq) == 1 {
-                hash_key ^= z.piece_keys[p_type][start_sq as usize]; // remove source piece from hash
-                hash_key ^= z.piece_keys[p_type][end_sq as usize]; // add target piece to hash
-                pop_bit!(bitboard, start_sq);
-                set_bit!(bitboard, end_sq);
+            // remove moves sliding off board or allied pieces
+            if king_idx % 8 < 4 {
+                pop_bits!(moves, self.masks.file_gh);
+            } else {
+                pop_bits!(moves, self.masks.file_ab);
             }
-        } else {
-            panic!("INVALID MOVE TYPE");
+            set_bits!(unsafe_w, moves);
+            pop_bits!(bitboards[Piece::BK], king); // remove current king
+            king = get_ls1b!(bitboards[Piece::BK]);
         }
-        (bitboard, hash_key)
+        unsafe_w
+    }
+
+
+    /// Checks if a move is valid (looks for illegal moves while in check)
+    pub fn isValidMove(&mut self, bitboards: [u64; 13], whites_turn: bool) -> bool {
+        (whites_turn && (bitboards[Piece::WK] & self.unsafeForWhite(bitboards)) == 0)
+            || (!whites_turn && (bitboards[Piece::BK] & self.unsafeForBlack(bitboards)) == 0)
+    }
+
+
+    /// Checks if the king is being attacked
+    pub fn isKingAttacked(&mut self, bitboards: [u64; 13], whites_turn: bool) -> bool {
+        (whites_turn && (bitboards[Piece::WK] & self.unsafeForWhite(bitboards)) != 0)
+            || (!whites_turn && (bitboards[Piece::BK] & self.unsafeForBlack(bitboards)) != 0)
+    }
+
+
+    /// Checks if the specified move is attacking
+    pub fn isAttackingMove(
+        &mut self,
+        bitboards: [u64; 13],
+        bitboards_t: [u64; 13],
+        whites_turn: bool,
+    ) -> bool {
+        self.isValidMove(bitboards_t, whites_turn)
+        && (
+            (!whites_turn
+                && or_array_elems!(Piece::whitePiecesNoKing(), bitboards).count_ones()
+                != or_array_elems!(Piece::whitePiecesNoKing(), bitboards_t).count_ones()
+            )
+            || (whites_turn
+                && or_array_elems!(Piece::blackPiecesNoKing(), bitboards).count_ones()
+                != or_array_elems!(Piece::blackPiecesNoKing(), bitboards_t).count_ones()
+            )
+        )
+    }
+
+
+    /// Alias so conversion can be done in python as well
+    fn moveToAlgebra(&self, move_str: &str) -> String {
+        move_to_algebra!(&move_str)
+    }
+
+
+    /// Alias so conversion can be done in python as well
+    fn algebraToMove(&self, alg_str: &str) -> String {
+        algebra_to_move!(&alg_str)
     }
 
 
-    /// Makes a casteling move given a move string
-    pub fn makeMoveCastle(
-        &self,
-        z: &mut Zobrist,
-        mut rook: u64,
-        king: u64,
-        mut hash_key: u64,
-        move_str: &str,
-        p_type: Piece,
-    ) -> (u64, u64) {
-        let (r1, c1, _, _) = move_to_u32s!(move_str);
-        let start_sq: u32 = r1 * 8 + c1;
-        if get_bit!(king, start_sq) == 1 && ((move_str == "0402") || (move_str == "0406") || (move_str == "7472") || (move_str == "7476")) {
-            if p_type == Piece::WR { // white
-                match move_str {
-                    "7476" => { // king side
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[3]];
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[3] - 2];
-                        pop_bit!(rook, self.castle_rooks[3]);
-                        set_bit!(rook, self.castle_rooks[3] - 2);
-                    },
-                    "7472" => { // queen side
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[2]];
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[2] + 3];
-                        pop_bit!(rook, self.castle_rooks[2]);
-                        set_bit!(rook, self.castle_rooks[2] + 3);
-                    },
-                    _ => (),
-                }
-            } else { // black
-                match move_str {
-                    "0406" => { // king side
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[1]];
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[1] - 2];
-                        pop_bit!(rook, self.castle_rooks[1]);
-                        set_bit!(rook, self.castle_rooks[1] - 2);
-                    },
-                    "0402" => { // queen side
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[0]];
-                        hash_key ^= z.piece_keys[p_type][self.castle_rooks[0] + 3];
-                        pop_bit!(rook, self.castle_rooks[0]);
-                        set_bit!(rook, self.castle_rooks[0] + 3);
-                    },
-                    _ => (),
-                }
-            }
+    /// Material outcome of the exchange started by a 4 character move string, see see()
+    #[pyo3(name = "see")]
+    fn seePy(&mut self, bitboards: [u64; 13], move_str: &str) -> i32 {
+        self.see(bitboards, Move::fromMoveStr(move_str, &bitboards))
+    }
+}
+
+
+/// Move generation, moves are converted to strings before reaching python
+impl Moves {
+    /// Move generator reusing masks that were already built
+    pub fn withMasks(masks: SpecialBitBoards) -> Self {
+        Moves {
+            castle_rooks: [0, 7, 56, 63],
+            masks,
+            checkmate: false,
+            stalemate: false,
         }
-        (rook, hash_key)
     }
 
 
-    /// Sets the EP bitboard given a move string
-    pub fn makeMoveEP(
-        &self,
+    /// Gets the valid moves of a game state
+    pub fn getValidMoves(
+        &mut self,
         z: &mut Zobrist,
-        ep: u64,
-        bitboard: u64,
-        mut hash_key: u64,
-        move_str: &str,
+        bitboards: [u64; 13],
+        castle_rights: [bool; 4],
+        hash_key: u64,
         whites_turn: bool,
-    ) -> (u64, u64) {
-        // remove current enpassant status from hash
-        if ep != 0 {
-            let col: usize = ep.leading_zeros() as usize;
-            let row: usize = if whites_turn {2} else {5};
-            hash_key ^= z.enpassant_keys[row * 8 + col];
-        }
-        let mut ep_t: u64 = 0;
-        if move_str.chars().nth(3).unwrap().is_numeric() {
-            let (r1, c1, r2, _) = move_to_u32s!(move_str);
-            if (r1 as i32 - r2 as i32).abs() == 2 && get_bit!(bitboard, r1 * 8 + c1) == 1 {
-                ep_t = self.masks.file_masks[c1 as usize];
-                let col: usize = ep_t.leading_zeros() as usize;
-                let row: usize = if !whites_turn {2} else {5};
-                hash_key ^= z.enpassant_keys[row * 8 + col]; // add next move enpassant status to hash
-            }
-        }
-        (ep_t, hash_key)
+    ) -> MoveList {
+        let moves: MoveList = self.getPossibleMoves(bitboards, castle_rights, whites_turn);
+        let mut valid_moves: MoveList = MoveList::new();
+        let mut pos: Position = Position::new(bitboards, castle_rights, hash_key, whites_turn);
+        for &mv in moves.iter() {
+            pos.makeMove(z, mv);
+            if self.isValidMove(pos.bitboards, whites_turn) {
+                valid_moves.push(mv);
+            }
+            pos.unmakeMove();
+        }
+        // both flags are set every call so results from a previous position do not linger
+        self.checkmate = valid_moves.is_empty() && self.isKingAttacked(bitboards, whites_turn);
+        self.stalemate = valid_moves.is_empty() && !self.checkmate;
+        valid_moves
     }
 
 
@@ -213,61 +379,64 @@ impl Moves {
         bitboards: [u64; 13],
         castle_rights: [bool; 4],
         whites_turn: bool,
-    ) -> String {
+    ) -> MoveList {
         if whites_turn {self.possibleMovesW(bitboards, castle_rights)}
         else {self.possibleMovesB(bitboards, castle_rights)}
     }
 
 
-    /// Generates a move string with all possible white moves
+    /// Generates a move list with all possible white moves
     pub fn possibleMovesW(
         &mut self,
         bitboards: [u64; 13],
         castle_rights: [bool; 4],
-    ) -> String {
+    ) -> MoveList {
         self.masks.not_allied_pieces = !or_array_elems!(Piece::whitePiecesWithEnemyKing(), bitboards); // avoid illegal bK capture
         self.masks.enemy_pieces = or_array_elems!(Piece::blackPiecesNoKing(), bitboards); // avoid illegal bK capture
         self.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
         self.masks.empty = !self.masks.occupied;
-        self.possibleWP(bitboards[Piece::WP], bitboards[Piece::BP], bitboards[Piece::EP])
-            + &self.possibleB(bitboards[Piece::WB])
-            + &self.possibleQ(bitboards[Piece::WQ])
-            + &self.possibleR(bitboards[Piece::WR])
-            + &self.possibleN(bitboards[Piece::WN])
-            + &self.possibleK(bitboards[Piece::WK])
-            + &self.possibleCastleW(bitboards, castle_rights)
+        let mut move_list: MoveList = MoveList::new();
+        self.possibleWP(&mut move_list, bitboards[Piece::WP], bitboards[Piece::BP], bitboards[Piece::EP]);
+        self.possibleB(&mut move_list, bitboards[Piece::WB]);
+        self.possibleQ(&mut move_list, bitboards[Piece::WQ]);
+        self.possibleR(&mut move_list, bitboards[Piece::WR]);
+        self.possibleN(&mut move_list, bitboards[Piece::WN]);
+        self.possibleK(&mut move_list, bitboards[Piece::WK]);
+        self.possibleCastleW(&mut move_list, bitboards, castle_rights);
+        move_list
     }
 
 
-    /// Generates a move string with all possible black moves
+    /// Generates a move list with all possible black moves
     pub fn possibleMovesB(
         &mut self,
         bitboards: [u64; 13],
         castle_rights: [bool; 4],
-    ) -> String {
+    ) -> MoveList {
         self.masks.not_allied_pieces = !or_array_elems!(Piece::blackPiecesWithEnemyKing(), bitboards); // avoid illegal wK capture
         self.masks.enemy_pieces = or_array_elems!(Piece::whitePiecesNoKing(), bitboards); // avoid illegal bK capture
         self.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
         self.masks.empty = !self.masks.occupied;
-        self.possibleBP(bitboards[Piece::WP], bitboards[Piece::BP], bitboards[Piece::EP])
-            + &self.possibleB(bitboards[Piece::BB])
-            + &self.possibleQ(bitboards[Piece::BQ])
-            + &self.possibleR(bitboards[Piece::BR])
-            + &self.possibleN(bitboards[Piece::BN])
-            + &self.possibleK(bitboards[Piece::BK])
-            + &self.possibleCastleB(bitboards, castle_rights)
+        let mut move_list: MoveList = MoveList::new();
+        self.possibleBP(&mut move_list, bitboards[Piece::WP], bitboards[Piece::BP], bitboards[Piece::EP]);
+        self.possibleB(&mut move_list, bitboards[Piece::BB]);
+        self.possibleQ(&mut move_list, bitboards[Piece::BQ]);
+        self.possibleR(&mut move_list, bitboards[Piece::BR]);
+        self.possibleN(&mut move_list, bitboards[Piece::BN]);
+        self.possibleK(&mut move_list, bitboards[Piece::BK]);
+        self.possibleCastleB(&mut move_list, bitboards, castle_rights);
+        move_list
     }
 
 
-    /// Generates a move string with all possible white pawn moves
-    fn possibleWP(&self, wP: u64, bP: u64, EP: u64) -> String {
+    /// Adds all possible white pawn moves
+    fn possibleWP(&self, move_list: &mut MoveList, wP: u64, bP: u64, EP: u64) {
         // standard moves and captures
-        let mut move_list: String = String::new(); // r1,c1,r2,c2
         let mut moves: u64 = (wP << 7) & self.masks.enemy_pieces & !self.masks.rank_masks[0] & !self.masks.file_masks[0]; // right capture
         let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) + 1, (idx % 8) - 1, idx / 8, idx % 8);
+            move_list.push(Move::new(idx + 7, idx, Move::CAPTURE));
             pop_bits!(moves, possible_move); // remove current move from moves
             possible_move = get_ls1b!(moves); // get next possible move
         }
@@ -276,7 +445,7 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) + 1, (idx % 8) + 1, idx / 8, idx % 8);
+            move_list.push(Move::new(idx + 9, idx, Move::CAPTURE));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -285,7 +454,7 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) + 1, idx % 8, idx / 8, idx % 8);
+            move_list.push(Move::new(idx + 8, idx, Move::QUIET));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -294,18 +463,19 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) + 2, idx % 8, idx / 8, idx % 8);
+            move_list.push(Move::new(idx + 16, idx, Move::DOUBLE_PUSH));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
 
-        // pawn promotion, move_list -> c1,c2,promo type,'P'
+        // pawn promotion
         moves = (wP << 7) & self.masks.enemy_pieces & self.masks.rank_masks[0] & !self.masks.file_masks[0]; // promo by right capture
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) - 1; let c2 = idx % 8;
-            move_list += &format!("{}{}QP{}{}RP{}{}BP{}{}NP", c1, c2, c1, c2, c1, c2, c1, c2);
+            for flag in [Move::PROMO_Q, Move::PROMO_R, Move::PROMO_B, Move::PROMO_N] {
+                move_list.push(Move::new(idx + 7, idx, flag | Move::CAPTURE));
+            }
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -314,8 +484,9 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) + 1; let c2 = idx % 8;
-            move_list += &format!("{}{}QP{}{}RP{}{}BP{}{}NP", c1, c2, c1, c2, c1, c2, c1, c2);
+            for flag in [Move::PROMO_Q, Move::PROMO_R, Move::PROMO_B, Move::PROMO_N] {
+                move_list.push(Move::new(idx + 9, idx, flag | Move::CAPTURE));
+            }
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -324,19 +495,19 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = idx % 8; let c2 = idx % 8;
-            move_list += &format!("{}{}QP{}{}RP{}{}BP{}{}NP", c1, c2, c1, c2, c1, c2, c1, c2);
+            for flag in [Move::PROMO_Q, Move::PROMO_R, Move::PROMO_B, Move::PROMO_N] {
+                move_list.push(Move::new(idx + 8, idx, flag));
+            }
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
 
-        // enpassant, move_list -> c1,c2,'wE'
+        // enpassant, idx is the square of the captured pawn
         moves = (wP >> 1) & bP & self.masks.rank_masks[3] & !self.masks.file_masks[0] & EP; // enpassant right
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) - 1; let c2 = idx % 8;
-            move_list += &format!("{}{}wE", c1, c2);
+            move_list.push(Move::new(idx - 1, idx - 8, Move::EN_PASSANT));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -345,24 +516,21 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) + 1; let c2 = idx % 8;
-            move_list += &format!("{}{}wE", c1, c2);
+            move_list.push(Move::new(idx + 1, idx - 8, Move::EN_PASSANT));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
-        move_list
     }
 
 
-    /// Generates a move string with all possible black pawn moves
-    fn possibleBP(&self, wP: u64, bP: u64, EP: u64) -> String {
+    /// Adds all possible black pawn moves
+    fn possibleBP(&self, move_list: &mut MoveList, wP: u64, bP: u64, EP: u64) {
         // standard moves and captures
-        let mut move_list: String = String::new(); // r1,c1,r2,c2
         let mut moves: u64 = (bP >> 7) & self.masks.enemy_pieces & !self.masks.rank_masks[7] & !self.masks.file_masks[7]; // right capture
         let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) - 1, (idx % 8) + 1, idx / 8, idx % 8);
+            move_list.push(Move::new(idx - 7, idx, Move::CAPTURE));
             pop_bits!(moves, possible_move); // remove current move from moves
             possible_move = get_ls1b!(moves); // get next possible move
         }
@@ -371,7 +539,7 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) - 1, (idx % 8) - 1, idx / 8, idx % 8);
+            move_list.push(Move::new(idx - 9, idx, Move::CAPTURE));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -380,7 +548,7 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) - 1, idx % 8, idx / 8, idx % 8);
+            move_list.push(Move::new(idx - 8, idx, Move::QUIET));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -389,18 +557,19 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            move_list += &format!("{}{}{}{}", (idx / 8) - 2, idx % 8, idx / 8, idx % 8);
+            move_list.push(Move::new(idx - 16, idx, Move::DOUBLE_PUSH));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
 
-        // pawn promotion, move_list -> c1,c2,promo type,'P'
+        // pawn promotion
         moves = (bP >> 7) & self.masks.enemy_pieces & self.masks.rank_masks[7] & !self.masks.file_masks[7]; // promo by right capture
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) + 1; let c2 = idx % 8;
-            move_list += &format!("{}{}qP{}{}rP{}{}bP{}{}nP", c1, c2, c1, c2, c1, c2, c1, c2);
+            for flag in [Move::PROMO_Q, Move::PROMO_R, Move::PROMO_B, Move::PROMO_N] {
+                move_list.push(Move::new(idx - 7, idx, flag | Move::CAPTURE));
+            }
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -409,8 +578,9 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) - 1; let c2 = idx % 8;
-            move_list += &format!("{}{}qP{}{}rP{}{}bP{}{}nP", c1, c2, c1, c2, c1, c2, c1, c2);
+            for flag in [Move::PROMO_Q, Move::PROMO_R, Move::PROMO_B, Move::PROMO_N] {
+                move_list.push(Move::new(idx - 9, idx, flag | Move::CAPTURE));
+            }
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -419,19 +589,19 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = idx % 8; let c2 = idx % 8;
-            move_list += &format!("{}{}qP{}{}rP{}{}bP{}{}nP", c1, c2, c1, c2, c1, c2, c1, c2);
+            for flag in [Move::PROMO_Q, Move::PROMO_R, Move::PROMO_B, Move::PROMO_N] {
+                move_list.push(Move::new(idx - 8, idx, flag));
+            }
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
 
-        // enpassant, move_list -> c1,c2,'wE'
+        // enpassant, idx is the square of the captured pawn
         moves = (bP << 1) & wP & self.masks.rank_masks[4] & !self.masks.file_masks[7] & EP; // enpassant right
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) + 1; let c2 = idx % 8;
-            move_list += &format!("{}{}bE", c1, c2);
+            move_list.push(Move::new(idx + 1, idx + 8, Move::EN_PASSANT));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
@@ -440,18 +610,15 @@ impl Moves {
         possible_move = get_ls1b!(moves);
         while possible_move != 0 {
             let idx: u32 = possible_move.leading_zeros();
-            let c1 = (idx % 8) - 1; let c2 = idx % 8;
-            move_list += &format!("{}{}bE", c1, c2);
+            move_list.push(Move::new(idx - 1, idx + 8, Move::EN_PASSANT));
             pop_bits!(moves, possible_move);
             possible_move = get_ls1b!(moves);
         }
-        move_list
     }
 
 
-    /// Generates a move string with all possible bishop moves
-    fn possibleB(&self, mut B: u64) -> String {
-        let mut move_list: String = String::new();
+    /// Adds all possible bishop moves
+    fn possibleB(&self, move_list: &mut MoveList, mut B: u64) {
         let mut bishop: u64 = get_ls1b!(B);
         while bishop != 0 {
             let bishop_idx: usize = bishop.leading_zeros() as usize;
@@ -459,343 +626,70 @@ impl Moves {
             let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
 
             while possible_move != 0 {
-                let move_idx: usize = possible_move.leading_zeros() as usize;
-                move_list += &format!("{}{}{}{}", bishop_idx / 8, bishop_idx % 8, move_idx / 8, move_idx % 8);
-                pop_bits!(moves, possible_move); // remove current possible move
-                possible_move = get_ls1b!(moves);
-            }
-
-            pop_bits!(B, bishop); // remove current bishop
-            bishop = get_ls1b!(B);
-        }
-        move_list
-    }
-
-
-     /// Generates a move string with all possible queen moves
-    fn possibleQ(&self, mut Q: u64) -> String {
-        let mut move_list: String = String::new();
-        let mut queen: u64 = get_ls1b!(Q);
-        while queen != 0 {
-            let queen_idx: usize = queen.leading_zeros() as usize;
-            let mut moves: u64 = (self.possibleDiagAndAntiDiagMoves(queen_idx) | self.possibleHAndVMoves(queen_idx)) & self.masks.not_allied_pieces;
-            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
-
-            while possible_move != 0 {
-                let move_idx: usize = possible_move.leading_zeros() as usize;
-                move_list += &format!("{}{}{}{}", queen_idx / 8, queen_idx % 8, move_idx / 8, move_idx % 8);
-                pop_bits!(moves, possible_move); // remove current possible move
-                possible_move = get_ls1b!(moves);
-            }
-
-            pop_bits!(Q, queen); // remove current queen
-            queen = get_ls1b!(Q);
-        }
-        move_list
-    }
-
-
-     /// Generates a move string with all possible rook moves
-    fn possibleR(&self, mut R: u64) -> String {
-        let mut move_list: String = String::new();
-        let mut rook: u64 = get_ls1b!(R);
-        while rook != 0 {
-            let rook_idx: usize = rook.leading_zeros() as usize;
-            let mut moves: u64 = self.possibleHAndVMoves(rook_idx) & self.masks.not_allied_pieces;
-            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
-
-            while possible_move != 0 {
-                let move_idx: usize = possible_move.leading_zeros() as usize;
-                move_list += &format!("{}{}{}{}", rook_idx / 8, rook_idx % 8, move_idx / 8, move_idx % 8);
-                pop_bits!(moves, possible_move); // remove current possible move
-                possible_move = get_ls1b!(moves);
-            }
-
-            pop_bits!(R, rook); // remove current rook
-            rook = get_ls1b!(R);
-        }
-        move_list
-    }
-
-
-     /// Generates a move string with all possible knight moves
-    fn possibleN(&self, mut N: u64) -> String {
-        let mut move_list: String = String::new();
-        let mut knight: u64 = get_ls1b!(N);
-        let knight_span_c6_idx: usize = 18;
-        while knight != 0 {
-            let knight_idx: usize = knight.leading_zeros() as usize;
-
-            // allign the knight_span_c6 mask
-            let mut moves: u64;
-            if knight_idx <= knight_span_c6_idx {
-                moves = self.masks.knight_span_c6 << (knight_span_c6_idx - knight_idx);
-            } else {
-                moves = self.masks.knight_span_c6 >> (knight_idx - knight_span_c6_idx);
-            }
-
-            // remove moves sliding off board or allied pieces
-            if knight_idx % 8 < 4 {
-                pop_bits!(moves, !(!self.masks.file_gh & self.masks.not_allied_pieces));
-            } else {
-                pop_bits!(moves, !(!self.masks.file_ab & self.masks.not_allied_pieces));
-            }
-            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
-
-            while possible_move != 0 {
-                let move_idx: usize = possible_move.leading_zeros() as usize;
-                move_list += &format!("{}{}{}{}", knight_idx / 8, knight_idx % 8, move_idx / 8, move_idx % 8);
-                pop_bits!(moves, possible_move); // remove current possible move
-                possible_move = get_ls1b!(moves);
-            }
-
-            pop_bits!(N, knight); // remove current knight
-            knight = get_ls1b!(N);
-        }
-        move_list
-    }
-
-
-     /// Generates a move string with all possible king moves
-    fn possibleK(&self, mut K: u64) -> String {
-        let mut move_list: String = String::new();
-        let mut king: u64 = get_ls1b!(K);
-        let king_span_c7_idx: usize = 10;
-        while king != 0 {
-            let king_idx: usize = king.leading_zeros() as usize;
-
-            // allign the king_span_c7 mask
-            let mut moves: u64;
-            if king_idx <= king_span_c7_idx {
-                moves = self.masks.king_span_c7 << (king_span_c7_idx - king_idx);
-            } else {
-                moves = self.masks.king_span_c7 >> (king_idx - king_span_c7_idx);
-            }
-
-            // remove moves sliding off board or allied pieces
-            if king_idx % 8 < 4 {
-                pop_bits!(moves, !(!self.masks.file_gh & self.masks.not_allied_pieces));
-            } else {
-                pop_bits!(moves, !(!self.masks.file_ab & self.masks.not_allied_pieces));
-            }
-            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
-
-            while possible_move != 0 {
-                let move_idx: usize = possible_move.leading_zeros() as usize;
-                move_list += &format!("{}{}{}{}", king_idx / 8, king_idx % 8, move_idx / 8, move_idx % 8);
-                pop_bits!(moves, possible_move); // remove current possible move
-                possible_move = get_ls1b!(moves);
-            }
-
-            pop_bits!(K, king); // remove current king
-            king = get_ls1b!(K);
-        }
-        move_list
-    }
-
-
-    /// Generates casteling moves for white
-    fn possibleCastleW(
-        &mut self,
-        bitboards: [u64; 13],
-        castle_rights: [bool; 4],
-    ) -> String {
-        let unsafe_w: u64 = self.unsafeForWhite(bitboards);
-        let mut move_list: String = String::new(); // king move r1c1r2c1
-        if unsafe_w & bitboards[Piece::WK] == 0 {
-            if castle_rights[CastleRights::CWK] && get_bit!(bitboards[Piece::WR], self.castle_rooks[3]) == 1 {
-                if ((self.masks.occupied | unsafe_w) & ((1 << 1) | (1 << 2))) == 0 {
-                    move_list += "7476";
-                }
-            }
-            if castle_rights[CastleRights::CWQ] && get_bit!(bitboards[Piece::WR], self.castle_rooks[2]) == 1 {
-                if ((self.masks.occupied | (unsafe_w & !(1 << 6))) & ((1 << 4) | (1 << 5) | (1 << 6))) == 0 {
-                    move_list += "7472";
-                }
-            }
-        }
-        move_list
-    }
-
-
-    /// Generates casteling moves for black
-    fn possibleCastleB(
-        &mut self,
-        bitboards: [u64; 13],
-        castle_rights: [bool; 4],
-    ) -> String {
-        let unsafe_b = self.unsafeForBlack(bitboards);
-        let mut move_list: String = String::new(); // king move r1c1r2c1
-        if unsafe_b & bitboards[Piece::BK] == 0 {
-            if castle_rights[CastleRights::CBK] && get_bit!(bitboards[Piece::BR], self.castle_rooks[1]) == 1 {
-                if ((self.masks.occupied | unsafe_b) & ((1 << 58) | (1 << 57))) == 0 {
-                    move_list += "0406";
-                }
-            }
-            if castle_rights[CastleRights::CBQ] && get_bit!(bitboards[Piece::BR], self.castle_rooks[0]) == 1 {
-                if ((self.masks.occupied | (unsafe_b & !(1 << 62))) & ((1 << 62) | (1 << 61) | (1 << 60))) == 0 {
-                    move_list += "0402";
-                }
-            }
-        }
-        move_list
-    }
-
-
-    /*
-    Returns all possible horizontal and vertical moves of piece at index piece_idx
-
-    Example for formula derivation:
-    occupied = o = 11000101 -> wP wP -- -- -- bR -- wP
-    slider = s = 00000100
-    o - s = 11000001 -> removes slider bit
-    o - 2s = 10111101 -> flips bits left of slider bit until first seen occupied bit (inclusive)
-    left = o^(o-2s) = 01111000 -> extracts all possible left sliding positions including first taken piece
-    let o' denote reverse of o
-    right = (o'^(o'-2s'))' = o^(o'-2s')' = 00000011
-    lineAttacks_h = right^left = o^(o'-2s')' ^ o^(o-2s) = (o'-2s')' ^ (o-2s)
-    m = mask
-    lineAttacks_v = (((o&m)'-2s')' ^ ((o&m)-2s))
-
-    return (possible_h & rank_m) | (possible_v & file_m) to only consider one file and rank
-    */
-    pub fn possibleHAndVMoves(&self, piece_idx: usize) -> u64 {
-        // piece_idx = 0 -> top left of board -> 1000...000
-        let binary_idx: u64 = 1 << (64 - 1 - piece_idx);
-        let rank_mask = self.masks.rank_masks[piece_idx / 8];
-        let file_mask = self.masks.file_masks[piece_idx % 8];
-        let possible_h = (wrap_op!(self.masks.occupied, wrap_op!(binary_idx, 2, '*'), '-'))
-            ^ (wrap_op!(
-                self.masks.occupied.reverse_bits(),
-                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
-                '-'
-            )).reverse_bits();
-        let possible_v = (wrap_op!((self.masks.occupied & file_mask), wrap_op!(binary_idx, 2, '*'), '-'))
-            ^ (wrap_op!(
-                (self.masks.occupied & file_mask).reverse_bits(),
-                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
-                '-'
-            )).reverse_bits();
-        (possible_h & rank_mask) | (possible_v & file_mask)
-    }
-
-
-    /*
-    Returns all possible diagonal and anti-diagonal moves of piece at index piece_idx
-
-    See possibleHAndVMoves func description for formula derivation
-    */
-    pub fn possibleDiagAndAntiDiagMoves(&self, piece_idx: usize) -> u64 {
-        // piece_idx = 0 -> top left of board -> 1000...000
-        let binary_idx: u64 = 1 << (64 - 1 - piece_idx);
-        let diag_mask = self.masks.diagonal_masks[(piece_idx / 8) + (piece_idx % 8)];
-        let a_diag_mask = self.masks.anti_diagonal_masks[7 + (piece_idx / 8) - (piece_idx % 8)];
-        let possible_d = (wrap_op!((self.masks.occupied & diag_mask), wrap_op!(binary_idx, 2, '*'), '-'))
-            ^ (wrap_op!(
-                (self.masks.occupied & diag_mask).reverse_bits(),
-                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
-                '-'
-            )).reverse_bits();
-        let possible_ad = (wrap_op!((self.masks.occupied & a_diag_mask), wrap_op!(binary_idx, 2, '*'), '-'))
-            ^ (wrap_op!(
-                (self.masks.occupied & a_diag_mask).reverse_bits(),
-                wrap_op!(binary_idx.reverse_bits(), 2, '*'),
-                '-'
-            )).reverse_bits();
-        (possible_d & diag_mask) | (possible_ad & a_diag_mask)
+                let move_idx: usize = possible_move.leading_zeros() as usize;
+                let flag: u16 = if possible_move & self.masks.occupied != 0 {Move::CAPTURE} else {Move::QUIET};
+                move_list.push(Move::new(bishop_idx as u32, move_idx as u32, flag));
+                pop_bits!(moves, possible_move); // remove current possible move
+                possible_move = get_ls1b!(moves);
+            }
+
+            pop_bits!(B, bishop); // remove current bishop
+            bishop = get_ls1b!(B);
+        }
     }
 
 
-    /// Generates a bitboard with 1's where white attacks
-    pub fn unsafeForBlack(&mut self, mut bitboards: [u64; 13]) -> u64 {
-        self.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
-        // pawn threats
-        let mut unsafe_b: u64 = (bitboards[Piece::WP] << 7) & !self.masks.file_masks[0]; // pawn right capture
-        set_bits!(unsafe_b, (bitboards[Piece::WP] << 9) & !self.masks.file_masks[7]); // pawn left capture
+     /// Adds all possible queen moves
+    fn possibleQ(&self, move_list: &mut MoveList, mut Q: u64) {
+        let mut queen: u64 = get_ls1b!(Q);
+        while queen != 0 {
+            let queen_idx: usize = queen.leading_zeros() as usize;
+            let mut moves: u64 = (self.possibleDiagAndAntiDiagMoves(queen_idx) | self.possibleHAndVMoves(queen_idx)) & self.masks.not_allied_pieces;
+            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
 
-        // knight threat
-        let mut knight: u64 = get_ls1b!(bitboards[Piece::WN]);
-        let knight_span_c6_idx: usize = 18;
-        while knight != 0 {
-            let knight_idx: usize = knight.leading_zeros() as usize;
-            // allign the knight_span_c6 mask
-            let mut moves: u64;
-            if knight_idx <= knight_span_c6_idx {
-                moves = self.masks.knight_span_c6 << (knight_span_c6_idx - knight_idx);
-            } else {
-                moves = self.masks.knight_span_c6 >> (knight_idx - knight_span_c6_idx);
-            }
-            // remove moves sliding off board or allied pieces
-            if knight_idx % 8 < 4 {
-                pop_bits!(moves, self.masks.file_gh);
-            } else {
-                pop_bits!(moves, self.masks.file_ab);
+            while possible_move != 0 {
+                let move_idx: usize = possible_move.leading_zeros() as usize;
+                let flag: u16 = if possible_move & self.masks.occupied != 0 {Move::CAPTURE} else {Move::QUIET};
+                move_list.push(Move::new(queen_idx as u32, move_idx as u32, flag));
+                pop_bits!(moves, possible_move); // remove current possible move
+                possible_move = get_ls1b!(moves);
             }
-            set_bits!(unsafe_b, moves);
-            pop_bits!(bitboards[Piece::WN], knight); // remove current knight
-            knight = get_ls1b!(bitboards[Piece::WN]);
-        }
 
-        // bishop / queen threats (diagonals)
-        let mut wQB: u64 = bitboards[Piece::WQ] | bitboards[Piece::WB];
-        let mut b_or_q: u64 = get_ls1b!(wQB);
-        while b_or_q != 0 {
-            let b_or_q_idx: usize = b_or_q.leading_zeros() as usize;
-            let moves: u64 = self.possibleDiagAndAntiDiagMoves(b_or_q_idx);
-            set_bits!(unsafe_b, moves);
-            pop_bits!(wQB, b_or_q); // remove current bishop or queen
-            b_or_q = get_ls1b!(wQB);
+            pop_bits!(Q, queen); // remove current queen
+            queen = get_ls1b!(Q);
         }
+    }
 
-        // rook / queen threats (hor and vert)
-        let mut wQR: u64 = bitboards[Piece::WQ] | bitboards[Piece::WR];
-        let mut r_or_q: u64 = get_ls1b!(wQR);
-        while r_or_q != 0 {
-            let r_or_q_idx: usize = r_or_q.leading_zeros() as usize;
-            let moves: u64 = self.possibleHAndVMoves(r_or_q_idx);
-            set_bits!(unsafe_b, moves);
-            pop_bits!(wQR, r_or_q); // remove current rook or queen
-            r_or_q = get_ls1b!(wQR);
-        }
 
-        // king threats
-        let mut king: u64 = get_ls1b!(bitboards[Piece::WK]);
-        let king_span_c7_idx: usize = 10;
-        while king != 0 {
-            let king_idx: usize = king.leading_zeros() as usize;
-            // allign the king_span_c7 mask
-            let mut moves: u64;
-            if king_idx <= king_span_c7_idx {
-                moves = self.masks.king_span_c7 << (king_span_c7_idx - king_idx);
-            } else {
-                moves = self.masks.king_span_c7 >> (king_idx - king_span_c7_idx);
-            }
-            // remove moves sliding off board or allied pieces
-            if king_idx % 8 < 4 {
-                pop_bits!(moves, self.masks.file_gh);
-            } else {
-                pop_bits!(moves, self.masks.file_ab);
+     /// Adds all possible rook moves
+    fn possibleR(&self, move_list: &mut MoveList, mut R: u64) {
+        let mut rook: u64 = get_ls1b!(R);
+        while rook != 0 {
+            let rook_idx: usize = rook.leading_zeros() as usize;
+            let mut moves: u64 = self.possibleHAndVMoves(rook_idx) & self.masks.not_allied_pieces;
+            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
+
+            while possible_move != 0 {
+                let move_idx: usize = possible_move.leading_zeros() as usize;
+                let flag: u16 = if possible_move & self.masks.occupied != 0 {Move::CAPTURE} else {Move::QUIET};
+                move_list.push(Move::new(rook_idx as u32, move_idx as u32, flag));
+                pop_bits!(moves, possible_move); // remove current possible move
+                possible_move = get_ls1b!(moves);
             }
-            set_bits!(unsafe_b, moves);
-            pop_bits!(bitboards[Piece::WK], king); // remove current king
-            king = get_ls1b!(bitboards[Piece::WK]);
+
+            pop_bits!(R, rook); // remove current rook
+            rook = get_ls1b!(R);
         }
-        unsafe_b
     }
 
 
-    /// Generates a bitboard with 1's where black attacks
-    pub fn unsafeForWhite(&mut self, mut bitboards: [u64; 13]) -> u64 {
-        self.masks.occupied = or_array_elems!(Piece::allPieces(), bitboards);
-        // pawn threats
-        let mut unsafe_w: u64 = (bitboards[Piece::BP] >> 7) & !self.masks.file_masks[7]; // pawn right capture
-        set_bits!(unsafe_w, (bitboards[Piece::BP] >> 9) & !self.masks.file_masks[0]); // pawn left capture
-
-        // knight threat
-        let mut knight: u64 = get_ls1b!(bitboards[Piece::BN]);
+     /// Adds all possible knight moves
+    fn possibleN(&self, move_list: &mut MoveList, mut N: u64) {
+        let mut knight: u64 = get_ls1b!(N);
         let knight_span_c6_idx: usize = 18;
         while knight != 0 {
             let knight_idx: usize = knight.leading_zeros() as usize;
+
             // allign the knight_span_c6 mask
             let mut moves: u64;
             if knight_idx <= knight_span_c6_idx {
@@ -803,44 +697,36 @@ impl Moves {
             } else {
                 moves = self.masks.knight_span_c6 >> (knight_idx - knight_span_c6_idx);
             }
+
             // remove moves sliding off board or allied pieces
             if knight_idx % 8 < 4 {
-                pop_bits!(moves, self.masks.file_gh);
+                pop_bits!(moves, !(!self.masks.file_gh & self.masks.not_allied_pieces));
             } else {
-                pop_bits!(moves, self.masks.file_ab);
+                pop_bits!(moves, !(!self.masks.file_ab & self.masks.not_allied_pieces));
             }
-            set_bits!(unsafe_w, moves);
-            pop_bits!(bitboards[Piece::BN], knight); // remove current knight
-            knight = get_ls1b!(bitboards[Piece::BN]);
-        }
+            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
 
-        // bishop / queen threats (diagonals)
-        let mut bQB: u64 = bitboards[Piece::BQ] | bitboards[Piece::BB];
-        let mut b_or_q: u64 = get_ls1b!(bQB);
-        while b_or_q != 0 {
-            let b_or_q_idx: usize = b_or_q.leading_zeros() as usize;
-            let moves: u64 = self.possibleDiagAndAntiDiagMoves(b_or_q_idx);
-            set_bits!(unsafe_w, moves);
-            pop_bits!(bQB, b_or_q); // remove current bishop or queen
-            b_or_q = get_ls1b!(bQB);
-        }
+            while possible_move != 0 {
+                let move_idx: usize = possible_move.leading_zeros() as usize;
+                let flag: u16 = if possible_move & self.masks.occupied != 0 {Move::CAPTURE} else {Move::QUIET};
+                move_list.push(Move::new(knight_idx as u32, move_idx as u32, flag));
+                pop_bits!(moves, possible_move); // remove current possible move
+                possible_move = get_ls1b!(moves);
+            }
 
-        // rook / queen threats (hor and vert)
-        let mut bQR: u64 = bitboards[Piece::BQ] | bitboards[Piece::BR];
-        let mut r_or_q: u64 = get_ls1b!(bQR);
-        while r_or_q != 0 {
-            let r_or_q_idx: usize = r_or_q.leading_zeros() as usize;
-            let moves: u64 = self.possibleHAndVMoves(r_or_q_idx);
-            set_bits!(unsafe_w, moves);
-            pop_bits!(bQR, r_or_q); // remove current rook or queen
-            r_or_q = get_ls1b!(bQR);
+            pop_bits!(N, knight); // remove current knight
+            knight = get_ls1b!(N);
         }
+    }
 
-        // king threats
-        let mut king = get_ls1b!(bitboards[Piece::BK]);
+
+     /// Adds all possible king moves
+    fn possibleK(&self, move_list: &mut MoveList, mut K: u64) {
+        let mut king: u64 = get_ls1b!(K);
         let king_span_c7_idx: usize = 10;
         while king != 0 {
             let king_idx: usize = king.leading_zeros() as usize;
+
             // allign the king_span_c7 mask
             let mut moves: u64;
             if king_idx <= king_span_c7_idx {
@@ -848,147 +734,71 @@ impl Moves {
             } else {
                 moves = self.masks.king_span_c7 >> (king_idx - king_span_c7_idx);
             }
+
             // remove moves sliding off board or allied pieces
             if king_idx % 8 < 4 {
-                pop_bits!(moves, self.masks.file_gh);
+                pop_bits!(moves, !(!self.masks.file_gh & self.masks.not_allied_pieces));
             } else {
-                pop_bits!(moves, self.masks.file_ab);
+                pop_bits!(moves, !(!self.masks.file_ab & self.masks.not_allied_pieces));
             }
-            set_bits!(unsafe_w, moves);
-            pop_bits!(bitboards[Piece::BK], king); // remove current king
-            king = get_ls1b!(bitboards[Piece::BK]);
+            let mut possible_move: u64 = get_ls1b!(moves); // selects single possible move
+
+            while possible_move != 0 {
+                let move_idx: usize = possible_move.leading_zeros() as usize;
+                let flag: u16 = if possible_move & self.masks.occupied != 0 {Move::CAPTURE} else {Move::QUIET};
+                move_list.push(Move::new(king_idx as u32, move_idx as u32, flag));
+                pop_bits!(moves, possible_move); // remove current possible move
+                possible_move = get_ls1b!(moves);
+            }
+
+            pop_bits!(K, king); // remove current king
+            king = get_ls1b!(K);
         }
-        unsafe_w
     }
 
 
-    /// Return the new casteling rights and hashkey after performing the specified move
-    pub fn getUpdatedCastleRights(
-        &self,
-        z: &mut Zobrist,
-        move_str: &str,
-        castle_rights: [bool; 4],
+    /// Generates casteling moves for white
+    fn possibleCastleW(
+        &mut self,
+        move_list: &mut MoveList,
         bitboards: [u64; 13],
-        mut hash_key: u64,
-    ) -> ([bool; 4], u64) {
-        // remove current castle rights from hash
-        hash_key ^= z.castle_keys[
-            ((castle_rights[CastleRights::CBQ] as usize) << 3)
-            | ((castle_rights[CastleRights::CBK] as usize) << 2)
-            | ((castle_rights[CastleRights::CWQ] as usize) << 1)
-            | (castle_rights[CastleRights::CWK] as usize)
-        ];
-        let mut castle_rights_t: [bool; 4] = castle_rights;
-        if move_str.chars().nth(3).unwrap().is_numeric() {
-            let (r1, c1, r2, c2) = move_to_u32s!(move_str);
-            let start_sq: u32 = r1 * 8 + c1;
-            let end_sq: u32 = r2 * 8 + c2;
-            if get_bit!(bitboards[Piece::WK], start_sq) != 0 { // white king move
-                (castle_rights_t[CastleRights::CWK], castle_rights_t[CastleRights::CWQ]) = (false, false);
-            }
-            if get_bit!(bitboards[Piece::BK], start_sq) != 0 { // black king move
-                (castle_rights_t[CastleRights::CBK], castle_rights_t[CastleRights::CBQ]) = (false, false);
-            }
-            if start_sq == self.castle_rooks[3] as u32 && get_bit!(bitboards[Piece::WR], start_sq) == 1 { // white king side rook move
-                castle_rights_t[CastleRights::CWK] = false;
-            }
-            if start_sq == self.castle_rooks[2] as u32 && get_bit!(bitboards[Piece::WR], start_sq) == 1 { // white queen side rook move
-                castle_rights_t[CastleRights::CWQ] = false;
-            }
-            if start_sq == self.castle_rooks[1] as u32 && get_bit!(bitboards[Piece::BR], start_sq) == 1 { // black king side rook move
-                castle_rights_t[CastleRights::CBK] = false;
-            }
-            if start_sq == self.castle_rooks[0] as u32 && get_bit!(bitboards[Piece::BR], start_sq) == 1 { // black queen side rook move
-                castle_rights_t[CastleRights::CBQ] = false;
-            }
-            if end_sq == self.castle_rooks[3] as u32 && get_bit!(bitboards[Piece::WR], end_sq) == 1 { // white king side rook taken
-                castle_rights_t[CastleRights::CWK] = false;
-            }
-            if end_sq == self.castle_rooks[2] as u32 && get_bit!(bitboards[Piece::WR], end_sq) == 1 { // white queen side rook taken
-                castle_rights_t[CastleRights::CWQ] = false;
-            }
-            if end_sq == self.castle_rooks[1] as u32 && get_bit!(bitboards[Piece::BR], end_sq) == 1 { // black king side rook taken
-                castle_rights_t[CastleRights::CBK] = false;
+        castle_rights: [bool; 4],
+    ) {
+        let unsafe_w: u64 = self.unsafeForWhite(bitboards);
+        if unsafe_w & bitboards[Piece::WK] == 0 {
+            if castle_rights[CastleRights::CWK] && get_bit!(bitboards[Piece::WR], self.castle_rooks[3]) == 1 {
+                if ((self.masks.occupied | unsafe_w) & ((1 << 1) | (1 << 2))) == 0 {
+                    move_list.push(Move::new(60, 62, Move::KING_CASTLE));
+                }
             }
-            if end_sq == self.castle_rooks[0] as u32 && get_bit!(bitboards[Piece::BR], end_sq) == 1 { // black queen side rook taken
-                castle_rights_t[CastleRights::CBQ] = false;
+            if castle_rights[CastleRights::CWQ] && get_bit!(bitboards[Piece::WR], self.castle_rooks[2]) == 1 {
+                if ((self.masks.occupied | (unsafe_w & !(1 << 6))) & ((1 << 4) | (1 << 5) | (1 << 6))) == 0 {
+                    move_list.push(Move::new(60, 58, Move::QUEEN_CASTLE));
+                }
             }
         }
-        // add next moves castle rights to hash
-        hash_key ^= z.castle_keys[
-            ((castle_rights_t[CastleRights::CBQ] as usize) << 3)
-            | ((castle_rights_t[CastleRights::CBK] as usize) << 2)
-            | ((castle_rights_t[CastleRights::CWQ] as usize) << 1)
-            | (castle_rights_t[CastleRights::CWK] as usize)
-        ];
-        (castle_rights_t, hash_key)
-    }
-
-
-    /// Return the new bitboards and hashkey after performing the specified move
-    pub fn getUpdatedBitboards(
-        &self,
-        z: &mut Zobrist,
-        move_str: &str,
-        bitboards: [u64; 13],
-        mut hash_key: u64,
-        whites_turn: bool,
-    ) -> ([u64; 13], u64) {
-        hash_key ^= z.side_key; // hash side
-        let mut bitboards_t: [u64; 13] = [0; 13];
-        for piece in Piece::allPieces() {
-            (bitboards_t[piece], hash_key) = self.makeMove(z, bitboards[piece], hash_key, move_str, piece);
-        }
-        (bitboards_t[Piece::WR], hash_key) = self.makeMoveCastle(z, bitboards_t[Piece::WR], bitboards[Piece::WK], hash_key, move_str, Piece::WR);
-        (bitboards_t[Piece::BR], hash_key) = self.makeMoveCastle(z, bitboards_t[Piece::BR], bitboards[Piece::BK], hash_key, move_str, Piece::BR);
-        (bitboards_t[Piece::EP], hash_key) = self.makeMoveEP(z, bitboards[Piece::EP], or_array_elems!([Piece::WP, Piece::BP], bitboards), hash_key, move_str, whites_turn);
-        (bitboards_t, hash_key)
-    }
-
-
-    /// Checks if a move is valid (looks for illegal moves while in check)
-    pub fn isValidMove(&mut self, bitboards: [u64; 13], whites_turn: bool) -> bool {
-        (whites_turn && (bitboards[Piece::WK] & self.unsafeForWhite(bitboards)) == 0)
-            || (!whites_turn && (bitboards[Piece::BK] & self.unsafeForBlack(bitboards)) == 0)
-    }
-
-
-    /// Checks if the king is being attacked
-    pub fn isKingAttacked(&mut self, bitboards: [u64; 13], whites_turn: bool) -> bool {
-        (whites_turn && (bitboards[Piece::WK] & self.unsafeForWhite(bitboards)) != 0)
-            || (!whites_turn && (bitboards[Piece::BK] & self.unsafeForBlack(bitboards)) != 0)
     }
 
 
-    /// Checks if the specified move is attacking
-    pub fn isAttackingMove(
+    /// Generates casteling moves for black
+    fn possibleCastleB(
         &mut self,
+        move_list: &mut MoveList,
         bitboards: [u64; 13],
-        bitboards_t: [u64; 13],
-        whites_turn: bool,
-    ) -> bool {
-        self.isValidMove(bitboards_t, whites_turn)
-        && (
-            (!whites_turn
-                && or_array_elems!(Piece::whitePiecesNoKing(), bitboards).count_ones()
-                != or_array_elems!(Piece::whitePiecesNoKing(), bitboards_t).count_ones()
-            )
-            || (whites_turn
-                && or_array_elems!(Piece::blackPiecesNoKing(), bitboards).count_ones()
-                != or_array_elems!(Piece::blackPiecesNoKing(), bitboards_t).count_ones()
-            )
-        )
-    }
-
-
-    /// Alias so conversion can be done in python as well
-    fn moveToAlgebra(&self, move_str: &str) -> String {
-        move_to_algebra!(&move_str)
-    }
-
-
-    /// Alias so conversion can be done in python as well
-    fn algebraToMove(&self, alg_str: &str) -> String {
-        algebra_to_move!(&alg_str)
+        castle_rights: [bool; 4],
+    ) {
+        let unsafe_b = self.unsafeForBlack(bitboards);
+        if unsafe_b & bitboards[Piece::BK] == 0 {
+            if castle_rights[CastleRights::CBK] && get_bit!(bitboards[Piece::BR], self.castle_rooks[1]) == 1 {
+                if ((self.masks.occupied | unsafe_b) & ((1 << 58) | (1 << 57))) == 0 {
+                    move_list.push(Move::new(4, 6, Move::KING_CASTLE));
+                }
+            }
+            if castle_rights[CastleRights::CBQ] && get_bit!(bitboards[Piece::BR], self.castle_rooks[0]) == 1 {
+                if ((self.masks.occupied | (unsafe_b & !(1 << 62))) & ((1 << 62) | (1 << 61) | (1 << 60))) == 0 {
+                    move_list.push(Move::new(4, 2, Move::QUEEN_CASTLE));
+                }
+            }
+        }
     }
 }
@@ -3,7 +3,9 @@
 
 use pyo3::prelude::*;
 use crate::{
+    chess_move::MoveList,
     moves::Moves,
+    position::Position,
     zobrist::Zobrist,
 };
 
@@ -28,8 +30,8 @@ impl Perft {
     }
 
 
-    /// Counts the nodes in a specific depth game tree
-    fn perft(
+    /// Root function to count the nodes in a specific depth game tree
+    pub fn perftRoot(
         &mut self,
         mm: &mut Moves,
         z: &mut Zobrist,
@@ -39,45 +41,47 @@ impl Perft {
         whites_turn: bool,
         depth: u32,
     ) {
-        if depth < self.max_depth {
-            let moves: String = mm.getPossibleMoves(bitboards, castle_rights, whites_turn);
-            for i in (0..moves.len()).step_by(4) {
-                let (bitboards_t, hash_key_t) = mm.getUpdatedBitboards(z, &moves[i..i+4], bitboards, hash_key, whites_turn);
-                let (castle_rights_t, hash_key_t) = mm.getUpdatedCastleRights(z, &moves[i..i+4], castle_rights, bitboards, hash_key_t);
-                if mm.isValidMove(bitboards_t, whites_turn) {
-                    if depth + 1 == self.max_depth { // only count leaf nodes
-                        self.move_counter += 1
-                    }
-                    self.perft(mm, z, bitboards_t, castle_rights_t, hash_key_t, !whites_turn, depth + 1)
-                }
+        let mut pos: Position = Position::new(bitboards, castle_rights, hash_key, whites_turn);
+        let moves: MoveList = mm.getPossibleMoves(bitboards, castle_rights, whites_turn);
+        for &mv in moves.iter() {
+            pos.makeMove(z, mv);
+            if mm.isValidMove(pos.bitboards, whites_turn) {
+                self.perft(mm, z, &mut pos, depth + 1);
+                println!("{} {}", mv, self.move_counter);
+                self.total_move_counter += self.move_counter;
+                self.move_counter = 0;
             }
-        } else if self.move_counter == 0 {
-            self.move_counter += 1;
+            pos.unmakeMove();
         }
     }
+}
 
 
-    /// Root function to count the nodes in a specific depth game tree
-    pub fn perftRoot(
+/// Tree walking on a position, positions are not passed through python
+impl Perft {
+    /// Counts the nodes in a specific depth game tree
+    fn perft(
         &mut self,
         mm: &mut Moves,
-        z: &mut Zobrist,
-        bitboards: [u64; 13],
-        castle_rights: [bool; 4],
-        hash_key: u64,
-        whites_turn: bool,
+        z: &Zobrist,
+        pos: &mut Position,
         depth: u32,
     ) {
-        let moves: String = mm.getPossibleMoves(bitboards, castle_rights, whites_turn);
-        for i in (0..moves.len()).step_by(4) {
-            let (bitboards_t, hash_key_t) = mm.getUpdatedBitboards(z, &moves[i..i+4], bitboards, hash_key, whites_turn);
-            let (castle_rights_t, hash_key_t) = mm.getUpdatedCastleRights(z, &moves[i..i+4], castle_rights, bitboards, hash_key_t);
-            if mm.isValidMove(bitboards_t, whites_turn) {
-                self.perft(mm, z, bitboards_t, castle_rights_t, hash_key_t, !whites_turn, depth + 1);
-                println!("{} {}", move_to_algebra!(moves[i..i+4]), self.move_counter);
-                self.total_move_counter += self.move_counter;
-                self.move_counter = 0;
+        if depth < self.max_depth {
+            let whites_turn: bool = pos.whites_turn;
+            let moves: MoveList = mm.getPossibleMoves(pos.bitboards, pos.castle_rights, whites_turn);
+            for &mv in moves.iter() {
+                pos.makeMove(z, mv);
+                if mm.isValidMove(pos.bitboards, whites_turn) {
+                    if depth + 1 == self.max_depth { // only count leaf nodes
+                        self.move_counter += 1
+                    }
+                    self.perft(mm, z, pos, depth + 1)
+                }
+                pos.unmakeMove();
             }
+        } else if self.move_counter == 0 {
+            self.move_counter += 1;
         }
     }
 }
@@ -97,7 +101,7 @@ mod tests {
         let gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(5);
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 4865609);
     }
 
@@ -107,8 +111,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(4);
-        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 4085603);
     }
 
@@ -118,8 +122,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 11030083);
     }
 
@@ -129,8 +133,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(5);
-        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 15833292);
     }
 
@@ -140,8 +144,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(5);
-        gs.importFEN(&m.masks, &mut z, String::from("1k6/1b6/8/8/7R/8/8/4K2R b K - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, false, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("1k6/1b6/8/8/7R/8/8/4K2R b K - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, false, 0);
         assert!(p.total_move_counter == 1063513);
     }
 
@@ -151,8 +155,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, false, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, false, 0);
         assert!(p.total_move_counter == 1134888);
     }
 
@@ -162,8 +166,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 1015133);
     }
 
@@ -173,8 +177,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, false, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, false, 0);
         assert!(p.total_move_counter == 1440467);
     }
 
@@ -184,8 +188,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("5k2/8/8/8/8/8/8/4K2R w K - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("5k2/8/8/8/8/8/8/4K2R w K - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 661072);
     }
 
@@ -195,8 +199,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 803711);
     }
 
@@ -206,8 +210,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(4);
-        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 1274206);
     }
 
@@ -217,8 +221,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(4);
-        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, false, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, false, 0);
         assert!(p.total_move_counter == 1720476);
     }
 
@@ -228,8 +232,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 3821001);
     }
 
@@ -239,8 +243,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(5);
-        gs.importFEN(&m.masks, &mut z, String::from("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, false, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, false, 0);
         assert!(p.total_move_counter == 1004658);
     }
 
@@ -250,8 +254,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("4k3/1P6/8/8/8/8/K7/8 w - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("4k3/1P6/8/8/8/8/K7/8 w - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 217342);
     }
 
@@ -261,8 +265,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("8/P1k5/K7/8/8/8/8/8 w - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("8/P1k5/K7/8/8/8/8/8 w - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 92683);
     }
 
@@ -272,8 +276,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(6);
-        gs.importFEN(&m.masks, &mut z, String::from("K1k5/8/P7/8/8/8/8/8 w - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("K1k5/8/P7/8/8/8/8/8 w - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 2217);
     }
 
@@ -283,8 +287,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(7);
-        gs.importFEN(&m.masks, &mut z, String::from("8/k1P5/8/1K6/8/8/8/8 w - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, true, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("8/k1P5/8/1K6/8/8/8/8 w - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, true, 0);
         assert!(p.total_move_counter == 567584);
     }
 
@@ -294,8 +298,8 @@ mod tests {
         let mut gs = GameState::new(&mut z);
         let mut m: Moves = Moves::new();
         let mut p: Perft = Perft::new(4);
-        gs.importFEN(&m.masks, &mut z, String::from("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1"));
-        p.perftRoot(&mut m, &mut z, gs.bitboards, gs.castle_rights, gs.hash_key, false, 0);
+        gs.importFEN(&m.masks, &mut z, String::from("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1")).unwrap();
+        p.perftRoot(&mut m, &mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, false, 0);
         assert!(p.total_move_counter == 23527);
     }
 }
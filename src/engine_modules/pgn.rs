@@ -0,0 +1,485 @@
+//! Module for reading and writing games in Portable Game Notation (PGN)
+
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fmt;
+use crate::{
+    chess_move::Move,
+    fen_error::FenError,
+    game_result::GameResult,
+    game_state::GameState,
+    moves::Moves,
+    san::SanError,
+    zobrist::Zobrist,
+};
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PgnError {
+    BadTag(String),
+    UnterminatedComment,
+    UnterminatedVariation,
+    UnmatchedClose(char), // '}' or ')' without an opening brace or parenthesis
+    BadFen(FenError),
+    IllegalMove(usize, SanError), // ply the move was played on, starting at 1
+}
+
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::BadTag(tag) => write!(f, "bad tag pair '{}'", tag),
+            PgnError::UnterminatedComment => write!(f, "comment is missing its closing brace"),
+            PgnError::UnterminatedVariation => write!(f, "variation is missing its closing parenthesis"),
+            PgnError::UnmatchedClose(c) => write!(f, "'{}' without a matching opening", c),
+            PgnError::BadFen(err) => write!(f, "bad FEN tag, {}", err),
+            PgnError::IllegalMove(ply, err) => write!(f, "ply {}, {}", ply, err),
+        }
+    }
+}
+
+
+impl std::error::Error for PgnError {}
+
+
+/// Bad PGN raises a ValueError in python
+impl From<PgnError> for PyErr {
+    fn from(err: PgnError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+
+/*
+A single game record. Only the main line is kept, variations and NAGs are
+skipped when reading. Comments are stored per move, an empty string means the
+move has no comment. A comment before the first move is kept for the whole game.
+*/
+#[pyclass(module = "ChessProject", get_all)]
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>, // in the order they were read or set
+    pub moves: Vec<String>, // SAN
+    pub comment: String, // before the first move
+    pub comments: Vec<String>, // one per move
+    pub result: String, // "1-0", "0-1", "1/2-1/2" or "*"
+}
+
+
+#[pymethods]
+impl PgnGame {
+    pub const SEVEN_TAG_ROSTER: [&'static str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+    #[new]
+    pub fn new() -> Self {
+        PgnGame {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            comment: String::new(),
+            comments: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+
+
+    /// Value of a tag, None if the game does not have it
+    pub fn tag(&self, name: &str) -> Option<String> {
+        self.tags.iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.clone())
+    }
+
+
+    /// Set a tag, replacing its value if the game already has it
+    pub fn setTag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag_name, _)| tag_name == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+        if name == "Result" {
+            self.result = value.to_string();
+        }
+    }
+
+
+    /// Set the comment written after a move, ply starts at 0 for the first move
+    pub fn setComment(&mut self, ply: usize, comment: &str) {
+        if ply < self.comments.len() {
+            self.comments[ply] = comment.to_string();
+        }
+    }
+
+
+    /// Parse every game in a PGN string
+    #[staticmethod]
+    pub fn parse(pgn_str: &str) -> Result<Vec<PgnGame>, PgnError> {
+        let mut games: Vec<PgnGame> = Vec::new();
+        let mut game: PgnGame = PgnGame::new();
+        let mut game_started: bool = false;
+        let mut in_movetext: bool = false;
+        let chars: Vec<char> = pgn_str.chars().collect();
+        let mut i: usize = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '[' => {
+                    // a tag after movetext starts the next game
+                    if in_movetext {
+                        games.push(std::mem::take(&mut game).finished());
+                        in_movetext = false;
+                    }
+                    game_started = true;
+                    let end: usize = PgnGame::tagEnd(&chars, i).ok_or_else(|| PgnError::BadTag(chars[i..].iter().take(40).collect()))?;
+                    let tag_str: String = chars[i + 1..end].iter().collect();
+                    let (name, value) = PgnGame::parseTag(&tag_str).ok_or(PgnError::BadTag(tag_str.clone()))?;
+                    game.setTag(&name, &value);
+                    i = end + 1;
+                },
+                '{' => {
+                    let end: usize = chars[i..].iter().position(|&c| c == '}').ok_or(PgnError::UnterminatedComment)? + i;
+                    let comment: String = chars[i + 1..end].iter().collect::<String>().split_whitespace().collect::<Vec<&str>>().join(" ");
+                    let target: &mut String = game.comments.last_mut().unwrap_or(&mut game.comment);
+                    if !target.is_empty() {
+                        target.push(' ');
+                    }
+                    target.push_str(&comment);
+                    game_started = true;
+                    i = end + 1;
+                },
+                ';' => {
+                    // rest of line comment
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                },
+                '%' if i == 0 || chars[i - 1] == '\n' => {
+                    // escaped line
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                },
+                '(' => i = PgnGame::variationEnd(&chars, i).ok_or(PgnError::UnterminatedVariation)? + 1,
+                ')' | '}' => return Err(PgnError::UnmatchedClose(chars[i])),
+                c if c.is_whitespace() => i += 1,
+                _ => {
+                    let start: usize = i;
+                    while i < chars.len() && !chars[i].is_whitespace() && !"{}();[".contains(chars[i]) {
+                        i += 1;
+                    }
+                    let token: String = chars[start..i].iter().collect();
+                    game_started = true;
+                    in_movetext = true;
+                    // move numbers can be attached to the move ("1.e4", "2...Qh4#")
+                    let san: &str = if token.starts_with(|c: char| c.is_ascii_digit()) && token.contains('.') {
+                        token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+                    } else {
+                        &token
+                    };
+                    match san {
+                        "1-0" | "0-1" | "1/2-1/2" | "*" => game.result = san.to_string(),
+                        "" | "e.p." => (),
+                        _ if san.starts_with('$') => (), // NAG
+                        _ => {
+                            game.moves.push(san.to_string());
+                            game.comments.push(String::new());
+                        },
+                    }
+                },
+            }
+        }
+        if game_started {
+            games.push(game.finished());
+        }
+        Ok(games)
+    }
+
+
+    /// Replay the moves from the start position (or FEN tag) and return the final game state
+    pub fn replay(&self, mm: &mut Moves, z: &mut Zobrist) -> Result<GameState, PgnError> {
+        let mut gs: GameState = GameState::new(z);
+        if let Some(fen_str) = self.tag("FEN") {
+            gs.importFEN(&mm.masks, z, fen_str).map_err(PgnError::BadFen)?;
+        }
+        for (ply, san) in self.moves.iter().enumerate() {
+            let mv: Move = gs.sanToMove(mm, z, san).map_err(|err| PgnError::IllegalMove(ply + 1, err))?;
+            gs.makeMove(z, mv);
+        }
+        Ok(gs)
+    }
+
+
+    /// FEN of every position in the game, starting with the position before the first move
+    pub fn fens(&self, mm: &mut Moves, z: &mut Zobrist) -> Result<Vec<String>, PgnError> {
+        let mut gs: GameState = self.replay(mm, z)?;
+        let mut fens: Vec<String> = vec![gs.toFEN()];
+        while gs.undoMove().is_some() {
+            fens.push(gs.toFEN());
+        }
+        fens.reverse();
+        Ok(fens)
+    }
+
+
+    /*
+    Record the moves played in a game state. The result is taken from the game
+    status and a FEN tag is added when the game did not start from the initial position.
+    */
+    #[staticmethod]
+    pub fn fromGameState(gs: &GameState, mm: &mut Moves, z: &mut Zobrist) -> PgnGame {
+        let mut game: PgnGame = PgnGame::new();
+        let mut gs_t: GameState = gs.clone();
+        let mut played: Vec<Move> = Vec::new();
+        while let Some(mv) = gs_t.undoMove() {
+            played.push(mv);
+        }
+        let start_fen: String = gs_t.toFEN();
+        for &mv in played.iter().rev() {
+            game.moves.push(gs_t.moveToSAN(mm, z, mv));
+            game.comments.push(String::new());
+            gs_t.makeMove(z, mv);
+        }
+
+        let result: &str = match gs.gameStatus(mm, z) {
+            GameResult::Ongoing => "*",
+            GameResult::Checkmate if gs.position.whites_turn => "0-1",
+            GameResult::Checkmate => "1-0",
+            _ => "1/2-1/2",
+        };
+        game.setTag("Result", result);
+        if start_fen != GameState::new(z).toFEN() {
+            game.setTag("SetUp", "1");
+            game.setTag("FEN", &start_fen);
+        }
+        game
+    }
+
+
+    /// Export format PGN, the seven tag roster comes first and movetext lines are at most 80 characters
+    pub fn toPGN(&self) -> String {
+        let mut pgn_str: String = String::new();
+        for name in PgnGame::SEVEN_TAG_ROSTER {
+            let value: String = match name {
+                "Result" => self.result.clone(),
+                "Date" => self.tag(name).unwrap_or(String::from("????.??.??")),
+                _ => self.tag(name).unwrap_or(String::from("?")),
+            };
+            pgn_str.push_str(&format!("[{} \"{}\"]\n", name, PgnGame::escapeTag(&value)));
+        }
+        for (name, value) in self.tags.iter().filter(|(name, _)| !PgnGame::SEVEN_TAG_ROSTER.contains(&name.as_str())) {
+            pgn_str.push_str(&format!("[{} \"{}\"]\n", name, PgnGame::escapeTag(value)));
+        }
+        pgn_str.push('\n');
+
+        // black moves first when the game starts from a FEN with black to move
+        let (first_number, black_first) = match self.tag("FEN") {
+            Some(fen_str) => {
+                let fields: Vec<&str> = fen_str.split_whitespace().collect();
+                (fields.get(5).and_then(|n| n.parse().ok()).unwrap_or(1), fields.get(1) == Some(&"b"))
+            },
+            None => (1, false),
+        };
+        let mut tokens: Vec<String> = Vec::new();
+        if !self.comment.is_empty() {
+            tokens.push(format!("{{{}}}", self.comment));
+        }
+        for (ply, san) in self.moves.iter().enumerate() {
+            let half: usize = ply + black_first as usize;
+            let number: usize = first_number + half / 2;
+            if half.is_multiple_of(2) {
+                tokens.push(format!("{}.", number));
+            } else if ply == 0 || !self.comments[ply - 1].is_empty() {
+                tokens.push(format!("{}...", number));
+            }
+            // e.p. suffixes are not part of export format SAN
+            tokens.push(san.replace(" e.p.", ""));
+            if !self.comments[ply].is_empty() {
+                tokens.push(format!("{{{}}}", self.comments[ply]));
+            }
+        }
+        tokens.push(self.result.clone());
+
+        let mut line_len: usize = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 80 {
+                pgn_str.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn_str.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn_str.push_str(&token);
+        }
+        pgn_str.push_str("\n\n");
+        pgn_str
+    }
+}
+
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        PgnGame::new()
+    }
+}
+
+
+/// PGN text helpers that are not exposed to python
+impl PgnGame {
+    /// The result tag wins over a missing or unknown movetext result
+    fn finished(mut self) -> Self {
+        if self.result == "*" {
+            if let Some(result) = self.tag("Result") {
+                self.result = result;
+            }
+        }
+        self
+    }
+
+
+    /// Index of the ']' closing the tag starting at start, quoted values may contain ']'
+    fn tagEnd(chars: &[char], start: usize) -> Option<usize> {
+        let mut in_quotes: bool = false;
+        let mut i: usize = start + 1;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' if in_quotes => i += 1,
+                '"' => in_quotes = !in_quotes,
+                ']' if !in_quotes => return Some(i),
+                _ => (),
+            }
+            i += 1;
+        }
+        None
+    }
+
+
+    /// Index of the ')' closing the variation starting at start, variations can be nested
+    fn variationEnd(chars: &[char], start: usize) -> Option<usize> {
+        let mut depth: usize = 0;
+        let mut i: usize = start;
+        while i < chars.len() {
+            match chars[i] {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i);
+                    }
+                },
+                '{' => i += chars[i..].iter().position(|&c| c == '}')?,
+                _ => (),
+            }
+            i += 1;
+        }
+        None
+    }
+
+
+    /// Name and unescaped value of a tag pair without its brackets
+    fn parseTag(tag_str: &str) -> Option<(String, String)> {
+        let (name, rest) = tag_str.trim().split_once(char::is_whitespace)?;
+        let quoted: &str = rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+        let mut value: String = String::new();
+        let mut escaped: bool = false;
+        for c in quoted.chars() {
+            if c == '\\' && !escaped {
+                escaped = true;
+            } else {
+                value.push(c);
+                escaped = false;
+            }
+        }
+        Some((name.to_string(), value))
+    }
+
+
+    fn escapeTag(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = r#"[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+
+{Game 1 of the rematch.} 1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 {This opening is called the Ruy Lopez.}
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+[Event "Casual"]
+[Result "0-1"]
+[FEN "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2"]
+[SetUp "1"]
+
+2... Qh4# $1 (2... Nc6 3. Nc3 {sideline} (3. h4)) ; rest of line
+0-1
+"#;
+
+    #[test]
+    fn parse_and_replay_games() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let games: Vec<PgnGame> = PgnGame::parse(GAMES).unwrap();
+        assert!(games.len() == 2);
+        assert!(games[0].tag("White") == Some(String::from("Fischer, Robert J.")));
+        assert!(games[0].moves.len() == 85 && games[0].result == "1/2-1/2");
+        assert!(games[0].comments[5] == "This opening is called the Ruy Lopez.");
+        assert!(games[0].comment == "Game 1 of the rematch." && games[0].comments[0].is_empty());
+        let fens: Vec<String> = games[0].fens(&mut m, &mut z).unwrap();
+        assert!(fens.len() == 86);
+        assert!(fens[85] == "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43");
+
+        assert!(games[1].moves == vec!["Qh4#"] && games[1].result == "0-1");
+        let gs: GameState = games[1].replay(&mut m, &mut z).unwrap();
+        assert!(gs.gameStatus(&mut m, &mut z) == GameResult::Checkmate);
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let games: Vec<PgnGame> = PgnGame::parse(GAMES).unwrap();
+        for game in games {
+            let gs: GameState = game.replay(&mut m, &mut z).unwrap();
+            let mut written: PgnGame = PgnGame::fromGameState(&gs, &mut m, &mut z);
+            written.setTag("White", "Engine \"A\"");
+            written.setComment(0, "+0.25/10");
+            written.comment = game.comment.clone();
+            let read: PgnGame = PgnGame::parse(&written.toPGN()).unwrap().remove(0);
+            assert!(read.moves == game.moves && read.result == written.result);
+            assert!(read.tag("White") == Some(String::from("Engine \"A\"")));
+            assert!(read.comments[0] == "+0.25/10" && read.comment == game.comment);
+            assert!(read.tag("FEN") == game.tag("FEN"));
+        }
+    }
+
+    #[test]
+    fn bad_pgn_is_rejected() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        assert!(PgnGame::parse("[Event \"x\"\n1. e4").is_err());
+        assert!(PgnGame::parse("1. e4 {no end").unwrap_err() == PgnError::UnterminatedComment);
+        assert!(PgnGame::parse("1. e4 (1. d4 e5").unwrap_err() == PgnError::UnterminatedVariation);
+        assert!(PgnGame::parse("1. e4 ) e5 *").unwrap_err() == PgnError::UnmatchedClose(')'));
+        assert!(PgnGame::parse("1. e4 e5 } *").unwrap_err() == PgnError::UnmatchedClose('}'));
+        let game: PgnGame = PgnGame::parse("1. e4 e5 2. Ke3 *").unwrap().remove(0);
+        assert!(matches!(game.replay(&mut m, &mut z), Err(PgnError::IllegalMove(3, SanError::NoMatchingMove(_)))));
+    }
+}
@@ -0,0 +1,381 @@
+//! Module holding the position that moves are made and unmade on
+
+
+use pyo3::prelude::*;
+use crate::{
+    castle_rights::CastleRights,
+    chess_move::Move,
+    piece::Piece,
+    zobrist::Zobrist,
+};
+
+
+/// State that can not be recovered from a move when taking it back
+#[derive(Clone, Copy, Debug)]
+struct UndoInfo {
+    mv: Move,
+    captured: Piece, // Piece::EP if nothing was captured
+    castle_rights: [bool; 4],
+    ep: u64,
+    halfmove_clock: u32,
+    hash_key: u64,
+}
+
+
+#[pyclass(module = "ChessProject")]
+#[derive(Clone, Debug)]
+pub struct Position {
+    #[pyo3(get, set)]
+    pub bitboards: [u64; 13],
+    #[pyo3(get, set)]
+    pub castle_rights: [bool; 4],
+    #[pyo3(get, set)]
+    pub whites_turn: bool,
+    #[pyo3(get, set)]
+    pub hash_key: u64,
+    #[pyo3(get, set)]
+    pub halfmove_clock: u32, // plies since the last capture or pawn move
+    #[pyo3(get, set)]
+    pub fullmove_number: u32, // starts at 1 and goes up after each black move
+    undo_stack: Vec<UndoInfo>,
+}
+
+
+#[pymethods]
+impl Position {
+    pub const FILE_A: u64 = 0x8080808080808080;
+    pub const LIGHT_SQUARES: u64 = 0xaa55aa55aa55aa55;
+
+    #[new]
+    pub fn new(
+        bitboards: [u64; 13],
+        castle_rights: [bool; 4],
+        hash_key: u64,
+        whites_turn: bool,
+    ) -> Self {
+        Position {
+            bitboards,
+            castle_rights,
+            whites_turn,
+            hash_key,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            undo_stack: Vec::with_capacity(128),
+        }
+    }
+
+
+    /// Piece standing on a square, Piece::EP if the square is empty
+    pub fn pieceAt(&self, square: u32) -> Piece {
+        for piece in Piece::allPieces() {
+            if get_bit!(self.bitboards[piece], square) == 1 {
+                return piece;
+            }
+        }
+        Piece::EP
+    }
+
+
+    /// Number of moves that can be taken back
+    pub fn undoDepth(&self) -> usize {
+        self.undo_stack.len()
+    }
+
+
+    /// Number of times the current position has occurred, only moves since the last capture or pawn move are checked
+    pub fn repetitionCount(&self) -> usize {
+        let reversible: usize = (self.halfmove_clock as usize).min(self.undo_stack.len());
+        1 + self.undo_stack[self.undo_stack.len() - reversible..].iter()
+            .filter(|undo| undo.hash_key == self.hash_key)
+            .count()
+    }
+
+
+    /*
+    Neither side can mate with any sequence of legal moves:
+        - King vs king
+        - King and a minor piece vs king
+        - Kings and bishops that are all on the same colour squares
+    */
+    pub fn isInsufficientMaterial(&self) -> bool {
+        let pawns_and_heavy_pieces: u64 = or_array_elems!([Piece::WP, Piece::BP, Piece::WR, Piece::BR, Piece::WQ, Piece::BQ], self.bitboards);
+        if pawns_and_heavy_pieces != 0 {
+            return false;
+        }
+        let knights: u64 = self.bitboards[Piece::WN] | self.bitboards[Piece::BN];
+        let bishops: u64 = self.bitboards[Piece::WB] | self.bitboards[Piece::BB];
+        (knights | bishops).count_ones() <= 1
+            || (knights == 0 && (bishops & Position::LIGHT_SQUARES == 0 || bishops & !Position::LIGHT_SQUARES == 0))
+    }
+}
+
+
+/// Making and unmaking moves, moves are not exposed to python
+impl Position {
+    /*
+    Make a pseudo-legal move by updating only the bitboards it touches.
+
+    The hash key is updated incrementally and everything needed to take
+    the move back is pushed onto the undo stack.
+    */
+    pub fn makeMove(&mut self, z: &Zobrist, mv: Move) {
+        let start_sq: u32 = mv.startSquare();
+        let end_sq: u32 = mv.endSquare();
+        let moved: Piece = self.pieceAt(start_sq);
+        let captured_sq: u32 = if mv.isEnPassant() {(start_sq / 8) * 8 + end_sq % 8} else {end_sq};
+        let captured: Piece = self.pieceAt(captured_sq);
+        self.undo_stack.push(UndoInfo {
+            mv,
+            captured,
+            castle_rights: self.castle_rights,
+            ep: self.bitboards[Piece::EP],
+            halfmove_clock: self.halfmove_clock,
+            hash_key: self.hash_key,
+        });
+
+        // remove taken piece
+        if captured != Piece::EP {
+            pop_bit!(self.bitboards[captured], captured_sq);
+            self.hash_key ^= z.piece_keys[captured][captured_sq as usize];
+        }
+
+        // move piece, pawns are replaced by the promoted piece
+        let placed: Piece = if mv.isPromotion() {mv.promotionPiece()} else {moved};
+        pop_bit!(self.bitboards[moved], start_sq);
+        set_bit!(self.bitboards[placed], end_sq);
+        self.hash_key ^= z.piece_keys[moved][start_sq as usize];
+        self.hash_key ^= z.piece_keys[placed][end_sq as usize];
+        if mv.isCastle() {
+            let (rook, rook_start, rook_end) = Position::castleRookMove(end_sq);
+            pop_bit!(self.bitboards[rook], rook_start);
+            set_bit!(self.bitboards[rook], rook_end);
+            self.hash_key ^= z.piece_keys[rook][rook_start as usize];
+            self.hash_key ^= z.piece_keys[rook][rook_end as usize];
+        }
+
+        // enpassant file is only set after a double pawn push
+        if self.bitboards[Piece::EP] != 0 {
+            let col: usize = self.bitboards[Piece::EP].leading_zeros() as usize;
+            let row: usize = if self.whites_turn {2} else {5};
+            self.hash_key ^= z.enpassant_keys[row * 8 + col];
+        }
+        self.bitboards[Piece::EP] = 0;
+        if mv.isDoublePush() {
+            let col: usize = (start_sq % 8) as usize;
+            let row: usize = if self.whites_turn {5} else {2};
+            self.bitboards[Piece::EP] = Position::FILE_A >> col;
+            self.hash_key ^= z.enpassant_keys[row * 8 + col];
+        }
+
+        // moving the king or a rook, or taking a rook, loses castle rights
+        self.hash_key ^= z.castle_keys[Position::castleKeyIdx(self.castle_rights)];
+        for square in [start_sq, end_sq] {
+            match square {
+                60 => (self.castle_rights[CastleRights::CWK], self.castle_rights[CastleRights::CWQ]) = (false, false),
+                4 => (self.castle_rights[CastleRights::CBK], self.castle_rights[CastleRights::CBQ]) = (false, false),
+                63 => self.castle_rights[CastleRights::CWK] = false,
+                56 => self.castle_rights[CastleRights::CWQ] = false,
+                7 => self.castle_rights[CastleRights::CBK] = false,
+                0 => self.castle_rights[CastleRights::CBQ] = false,
+                _ => (),
+            }
+        }
+        self.hash_key ^= z.castle_keys[Position::castleKeyIdx(self.castle_rights)];
+
+        if moved == Piece::WP || moved == Piece::BP || captured != Piece::EP {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if !self.whites_turn {
+            self.fullmove_number += 1;
+        }
+        self.whites_turn = !self.whites_turn;
+        self.hash_key ^= z.side_key;
+    }
+
+
+    /// Pass the turn without moving, used by null move pruning and taken back with unmakeMove
+    pub fn makeNullMove(&mut self, z: &Zobrist) {
+        self.undo_stack.push(UndoInfo {
+            mv: Move::NULL,
+            captured: Piece::EP,
+            castle_rights: self.castle_rights,
+            ep: self.bitboards[Piece::EP],
+            halfmove_clock: self.halfmove_clock,
+            hash_key: self.hash_key,
+        });
+        if self.bitboards[Piece::EP] != 0 {
+            let col: usize = self.bitboards[Piece::EP].leading_zeros() as usize;
+            let row: usize = if self.whites_turn {2} else {5};
+            self.hash_key ^= z.enpassant_keys[row * 8 + col];
+        }
+        self.bitboards[Piece::EP] = 0;
+        self.halfmove_clock += 1;
+        if !self.whites_turn {
+            self.fullmove_number += 1;
+        }
+        self.whites_turn = !self.whites_turn;
+        self.hash_key ^= z.side_key;
+    }
+
+
+    /// Take back the last move made, returns None if there is no move to take back
+    pub fn unmakeMove(&mut self) -> Option<Move> {
+        let undo: UndoInfo = self.undo_stack.pop()?;
+        let mv: Move = undo.mv;
+        let start_sq: u32 = mv.startSquare();
+        let end_sq: u32 = mv.endSquare();
+        self.whites_turn = !self.whites_turn;
+        if !self.whites_turn {
+            self.fullmove_number -= 1;
+        }
+        if mv == Move::NULL {
+            // a null move only passed the turn
+            self.bitboards[Piece::EP] = undo.ep;
+            self.halfmove_clock = undo.halfmove_clock;
+            self.hash_key = undo.hash_key;
+            return Some(mv);
+        }
+
+        // move piece back, promoted pieces turn back into pawns
+        let placed: Piece = self.pieceAt(end_sq);
+        let moved: Piece = if !mv.isPromotion() {placed} else if self.whites_turn {Piece::WP} else {Piece::BP};
+        pop_bit!(self.bitboards[placed], end_sq);
+        set_bit!(self.bitboards[moved], start_sq);
+        if mv.isCastle() {
+            let (rook, rook_start, rook_end) = Position::castleRookMove(end_sq);
+            pop_bit!(self.bitboards[rook], rook_end);
+            set_bit!(self.bitboards[rook], rook_start);
+        }
+
+        // put taken piece back
+        if undo.captured != Piece::EP {
+            let captured_sq: u32 = if mv.isEnPassant() {(start_sq / 8) * 8 + end_sq % 8} else {end_sq};
+            set_bit!(self.bitboards[undo.captured], captured_sq);
+        }
+
+        self.castle_rights = undo.castle_rights;
+        self.bitboards[Piece::EP] = undo.ep;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash_key = undo.hash_key;
+        Some(mv)
+    }
+
+
+    /// Last move made, None if there is no move to take back
+    pub fn lastMove(&self) -> Option<Move> {
+        self.undo_stack.last().map(|undo| undo.mv)
+    }
+
+
+    /// Hash keys of the positions before the current one, oldest first
+    pub fn hashHistory(&self) -> Vec<u64> {
+        self.undo_stack.iter().map(|undo| undo.hash_key).collect()
+    }
+
+
+    /// Rook type and its start and end square for the castle ending on the kings end square
+    fn castleRookMove(king_end_sq: u32) -> (Piece, u32, u32) {
+        match king_end_sq {
+            62 => (Piece::WR, 63, 61), // white king side
+            58 => (Piece::WR, 56, 59), // white queen side
+            6 => (Piece::BR, 7, 5), // black king side
+            _ => (Piece::BR, 0, 3), // black queen side
+        }
+    }
+
+
+    /// Castle rights encoded as a 4 bit uint to index the zobrist castle keys
+    fn castleKeyIdx(castle_rights: [bool; 4]) -> usize {
+        ((castle_rights[CastleRights::CBQ] as usize) << 3)
+            | ((castle_rights[CastleRights::CBK] as usize) << 2)
+            | ((castle_rights[CastleRights::CWQ] as usize) << 1)
+            | (castle_rights[CastleRights::CWK] as usize)
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_modules::{
+        chess_move::MoveList,
+        game_state::GameState,
+        moves::Moves,
+    };
+
+    /// Make and unmake every move of a small game tree checking the state is restored
+    fn makeUnmakeTree(m: &mut Moves, z: &Zobrist, pos: &mut Position, depth: u32) {
+        if depth == 0 {
+            return;
+        }
+        let before: Position = pos.clone();
+        let moves: MoveList = m.getPossibleMoves(pos.bitboards, pos.castle_rights, pos.whites_turn);
+        for &mv in moves.iter() {
+            pos.makeMove(z, mv);
+            assert!(pos.hash_key == z.generateHashKey(pos.bitboards, pos.castle_rights, pos.whites_turn));
+            if m.isValidMove(pos.bitboards, !pos.whites_turn) {
+                makeUnmakeTree(m, z, pos, depth - 1);
+            }
+            assert!(pos.unmakeMove() == Some(mv));
+            assert!(pos.bitboards == before.bitboards);
+            assert!(pos.castle_rights == before.castle_rights);
+            assert!(pos.whites_turn == before.whites_turn);
+            assert!(pos.hash_key == before.hash_key);
+            assert!(pos.halfmove_clock == before.halfmove_clock);
+            assert!(pos.fullmove_number == before.fullmove_number);
+        }
+    }
+
+    #[test]
+    fn make_unmake_restores_position() {
+        let z: Zobrist = Zobrist::new();
+        let mut gs: GameState = GameState::new(&z);
+        let mut m: Moves = Moves::new();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+        ] {
+            gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+            makeUnmakeTree(&mut m, &z, &mut gs.position, 3);
+        }
+    }
+
+    #[test]
+    fn null_move_passes_the_turn() {
+        let z: Zobrist = Zobrist::new();
+        let m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        gs.importFEN(&m.masks, &z, String::from("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1")).unwrap();
+        let before: Position = gs.position.clone();
+        gs.position.makeNullMove(&z);
+        assert!(!gs.position.whites_turn && gs.position.bitboards[Piece::EP] == 0);
+        assert!(gs.position.hash_key == z.generateHashKey(gs.position.bitboards, gs.position.castle_rights, gs.position.whites_turn));
+        assert!(gs.position.lastMove() == Some(Move::NULL));
+        assert!(gs.position.unmakeMove() == Some(Move::NULL));
+        assert!(gs.position.hash_key == before.hash_key && gs.position.bitboards == before.bitboards && gs.position.whites_turn);
+    }
+
+    #[test]
+    fn halfmove_clock_resets_on_pawn_moves_and_captures() {
+        let z: Zobrist = Zobrist::new();
+        let mut gs: GameState = GameState::new(&z);
+        let m: Moves = Moves::new();
+        gs.importFEN(&m.masks, &z, String::from("4k3/8/8/3p4/8/8/4P3/R3K3 w Q - 0 1")).unwrap();
+        let mut pos: Position = gs.position.clone();
+        pos.makeMove(&z, Move::new(56, 48, Move::QUIET)); // a1a2
+        assert!(pos.halfmove_clock == 1 && !pos.castle_rights[CastleRights::CWQ]);
+        pos.makeMove(&z, Move::new(4, 12, Move::QUIET)); // e8e7
+        assert!(pos.halfmove_clock == 2);
+        pos.makeMove(&z, Move::new(52, 36, Move::DOUBLE_PUSH)); // e2e4
+        assert!(pos.halfmove_clock == 0 && pos.bitboards[Piece::EP] == Position::FILE_A >> 4);
+        pos.makeMove(&z, Move::new(27, 36, Move::CAPTURE)); // d5e4
+        assert!(pos.halfmove_clock == 0 && pos.pieceAt(36) == Piece::BP);
+        assert!(pos.undoDepth() == 4);
+    }
+}
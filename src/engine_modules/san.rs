@@ -0,0 +1,264 @@
+//! Module for Standard Algebraic Notation (SAN) output and parsing
+
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fmt;
+use crate::{
+    chess_move::{
+        Move,
+        MoveList,
+    },
+    game_state::GameState,
+    moves::Moves,
+    piece::Piece,
+    position::Position,
+    zobrist::Zobrist,
+};
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SanError {
+    BadSyntax(String),
+    NoMatchingMove(String),
+    AmbiguousMove(String),
+}
+
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::BadSyntax(san) => write!(f, "'{}' is not a SAN move", san),
+            SanError::NoMatchingMove(san) => write!(f, "no valid move matches '{}'", san),
+            SanError::AmbiguousMove(san) => write!(f, "more than one valid move matches '{}'", san),
+        }
+    }
+}
+
+
+impl std::error::Error for SanError {}
+
+
+/// Bad SAN moves raise a ValueError in python
+impl From<SanError> for PyErr {
+    fn from(err: SanError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+
+/// SAN conversion against the current game state, moves must be valid in it
+impl GameState {
+    /*
+    Convert a valid move to SAN ("Nf3", "exd6 e.p.", "O-O-O", "e8=Q+", "Rad1#").
+
+    Pieces are disambiguated by file, then rank, then both when another piece
+    of the same type can reach the end square.
+    */
+    pub fn moveToSAN(&self, mm: &mut Moves, z: &mut Zobrist, mv: Move) -> String {
+        let (start_sq, end_sq) = (mv.startSquare(), mv.endSquare());
+        let mut san: String = String::new();
+        if mv.isCastle() {
+            san.push_str(if end_sq % 8 == 6 {"O-O"} else {"O-O-O"});
+        } else {
+            let moved: Piece = self.position.pieceAt(start_sq);
+            let letter: char = pieceLetter(moved);
+            if letter == 'P' {
+                if mv.isCapture() {
+                    san.push(fileChar(start_sq));
+                }
+            } else {
+                san.push(letter);
+                let valid_moves: MoveList = mm.getValidMoves(z, self.position.bitboards, self.position.castle_rights, self.position.hash_key, self.position.whites_turn);
+                let rivals: Vec<u32> = valid_moves.iter()
+                    .filter(|other| other.endSquare() == end_sq && other.startSquare() != start_sq)
+                    .filter(|other| self.position.pieceAt(other.startSquare()) == moved)
+                    .map(|other| other.startSquare())
+                    .collect();
+                if !rivals.is_empty() {
+                    if rivals.iter().all(|&sq| sq % 8 != start_sq % 8) {
+                        san.push(fileChar(start_sq));
+                    } else if rivals.iter().all(|&sq| sq / 8 != start_sq / 8) {
+                        san.push(rankChar(start_sq));
+                    } else {
+                        san.push(fileChar(start_sq));
+                        san.push(rankChar(start_sq));
+                    }
+                }
+            }
+            if mv.isCapture() {
+                san.push('x');
+            }
+            san.push(fileChar(end_sq));
+            san.push(rankChar(end_sq));
+            if mv.isPromotion() {
+                san.push('=');
+                san.push(pieceLetter(mv.promotionPiece()));
+            }
+            if mv.isEnPassant() {
+                san.push_str(" e.p.");
+            }
+        }
+
+        // check and mate marks
+        let mut pos: Position = self.position.clone();
+        pos.makeMove(z, mv);
+        if mm.isKingAttacked(pos.bitboards, pos.whites_turn) {
+            let replies: MoveList = mm.getValidMoves(z, pos.bitboards, pos.castle_rights, pos.hash_key, pos.whites_turn);
+            san.push(if replies.is_empty() {'#'} else {'+'});
+        }
+        san
+    }
+
+
+    /*
+    Find the valid move matching a SAN string.
+
+    Check, mate and annotation marks are ignored, "0-0" is accepted for castling
+    and the '=' before a promotion piece is optional.
+    */
+    pub fn sanToMove(&self, mm: &mut Moves, z: &mut Zobrist, san_str: &str) -> Result<Move, SanError> {
+        let san: &str = san_str.trim()
+            .trim_end_matches(['+', '#', '!', '?'])
+            .trim_end_matches("e.p.")
+            .trim_end();
+        let valid_moves: MoveList = mm.getValidMoves(z, self.position.bitboards, self.position.castle_rights, self.position.hash_key, self.position.whites_turn);
+
+        if san == "O-O" || san == "0-0" || san == "O-O-O" || san == "0-0-0" {
+            let flag: u16 = if san.len() == 3 {Move::KING_CASTLE} else {Move::QUEEN_CASTLE};
+            return valid_moves.iter()
+                .find(|mv| mv.flag() == flag)
+                .copied()
+                .ok_or(SanError::NoMatchingMove(san_str.to_string()));
+        }
+
+        let bad_syntax = || SanError::BadSyntax(san_str.to_string());
+        let mut chars: Vec<char> = san.chars().collect();
+        let letter: char = match chars.first() {
+            Some(&c) if "KQRBN".contains(c) => {
+                chars.remove(0);
+                c
+            },
+            Some(_) => 'P',
+            None => return Err(bad_syntax()),
+        };
+        let mut promotion: Option<char> = None;
+        if let Some(&c) = chars.last() {
+            if "QRBN".contains(c.to_ascii_uppercase()) && letter == 'P' {
+                promotion = Some(c.to_ascii_uppercase());
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+        if chars.len() < 2 {
+            return Err(bad_syntax());
+        }
+        let end_sq: u32 = squareIdx(chars[chars.len() - 2], chars[chars.len() - 1]).ok_or_else(bad_syntax)?;
+        let mut from_file: Option<u32> = None;
+        let mut from_rank: Option<u32> = None;
+        for &c in chars[..chars.len() - 2].iter().filter(|&&c| c != 'x' && c != ':') {
+            match c {
+                'a'..='h' if from_file.is_none() => from_file = Some(c as u32 - 'a' as u32),
+                '1'..='8' if from_rank.is_none() => from_rank = Some('8' as u32 - c as u32),
+                _ => return Err(bad_syntax()),
+            }
+        }
+
+        let mut matches = valid_moves.iter().filter(|mv| {
+            let start_sq: u32 = mv.startSquare();
+            mv.endSquare() == end_sq
+                && !mv.isCastle()
+                && pieceLetter(self.position.pieceAt(start_sq)) == letter
+                && from_file.is_none_or(|col| start_sq % 8 == col)
+                && from_rank.is_none_or(|row| start_sq / 8 == row)
+                && promotion == mv.isPromotion().then(|| pieceLetter(mv.promotionPiece()))
+        });
+        match (matches.next(), matches.next()) {
+            (Some(&mv), None) => Ok(mv),
+            (None, _) => Err(SanError::NoMatchingMove(san_str.to_string())),
+            (Some(_), Some(_)) => Err(SanError::AmbiguousMove(san_str.to_string())),
+        }
+    }
+}
+
+
+/// Upper case SAN letter of a piece, 'P' for pawns
+fn pieceLetter(piece: Piece) -> char {
+    match piece {
+        Piece::WN | Piece::BN => 'N',
+        Piece::WB | Piece::BB => 'B',
+        Piece::WR | Piece::BR => 'R',
+        Piece::WQ | Piece::BQ => 'Q',
+        Piece::WK | Piece::BK => 'K',
+        _ => 'P',
+    }
+}
+
+
+fn fileChar(square: u32) -> char {
+    (b'a' + (square % 8) as u8) as char
+}
+
+
+fn rankChar(square: u32) -> char {
+    (b'8' - (square / 8) as u8) as char
+}
+
+
+/// Square index (0 is a8) of a file and rank character pair
+fn squareIdx(file: char, rank: char) -> Option<u32> {
+    if ('a'..='h').contains(&file) && ('1'..='8').contains(&rank) {
+        Some(('8' as u32 - rank as u32) * 8 + file as u32 - 'a' as u32)
+    } else {
+        None
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Play SAN moves from a FEN checking each one is written back the same way
+    fn sanRoundTrip(fen: &str, sans: &[&str]) {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+        for &san in sans {
+            let mv: Move = gs.sanToMove(&mut m, &mut z, san).unwrap();
+            assert!(gs.moveToSAN(&mut m, &mut z, mv) == san, "{}", san);
+            gs.makeMove(&z, mv);
+        }
+    }
+
+    #[test]
+    fn san_round_trip() {
+        sanRoundTrip(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "Bg4", "h3", "h5", "hxg4", "hxg4", "Nxe5", "Qh4", "Nxg4", "Qh1#"],
+        );
+        sanRoundTrip("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", &["exd6 e.p.", "Kd7", "Kd2", "Kxd6"]);
+        sanRoundTrip("r3k3/1P6/8/8/8/8/8/4K3 w q - 0 1", &["bxa8=Q+", "Kd7", "Qb7+", "Ke6"]);
+        sanRoundTrip("r3k3/8/8/8/8/8/8/R3K2R b KQq - 0 1", &["O-O-O", "O-O", "Rh8", "Rad1"]);
+        sanRoundTrip("k7/8/8/N7/N1N5/8/8/N3K3 w - - 0 1", &["Nab6+", "Kb8", "N1b3", "Kc7"]);
+    }
+
+    #[test]
+    fn san_parse_errors() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let mut gs: GameState = GameState::new(&z);
+        assert!(gs.sanToMove(&mut m, &mut z, "Nc3+").unwrap().to_string() == "b1c3");
+        assert!(gs.sanToMove(&mut m, &mut z, "Zf3") == Err(SanError::BadSyntax(String::from("Zf3"))));
+        assert!(gs.sanToMove(&mut m, &mut z, "Nf6") == Err(SanError::NoMatchingMove(String::from("Nf6"))));
+        assert!(gs.sanToMove(&mut m, &mut z, "O-O") == Err(SanError::NoMatchingMove(String::from("O-O"))));
+        gs.importFEN(&m.masks, &z, String::from("k7/8/8/N7/N1N5/8/8/N3K3 w - - 0 1")).unwrap();
+        assert!(gs.sanToMove(&mut m, &mut z, "Nb6") == Err(SanError::AmbiguousMove(String::from("Nb6"))));
+    }
+}
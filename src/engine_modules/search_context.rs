@@ -0,0 +1,18 @@
+//! Module holding the engine components a search runs with
+
+
+use crate::{
+    best_move_finder::BestMoveFinder,
+    moves::Moves,
+    trans_table::TransTable,
+    zobrist::Zobrist,
+};
+
+
+/// Engine components that are moved into the search thread while searching
+pub struct SearchContext {
+    pub bmf: BestMoveFinder,
+    pub mm: Moves,
+    pub z: Zobrist,
+    pub tt: TransTable,
+}
@@ -0,0 +1,137 @@
+//! Module for reporting search progress to Rust listeners and python callbacks
+
+
+use pyo3::prelude::*;
+use crate::chess_move::Move;
+
+
+/// Progress of the search after a completed iteration
+#[pyclass(module = "ChessProject", get_all)]
+#[derive(Clone, Debug, Default)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub seldepth: u32, // deepest ply reached, quiescence included
+    pub multipv: u32, // rank of the PV line, 1 is the best line
+    pub score: i32, // centipawns for the side to move
+    pub mate: Option<i32>, // mate in this many moves, negative when the side to move is mated
+    pub nodes: u64,
+    pub nps: u64,
+    pub time_ms: u64,
+    pub hashfull: u32, // permille of the transposition table in use
+    pub pv: Vec<String>, // coordinate notation ("e2e4", "e7e8q")
+}
+
+
+#[pymethods]
+impl SearchInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "SearchInfo(depth={}, seldepth={}, multipv={}, score={}, mate={:?}, nodes={}, nps={}, time_ms={}, hashfull={}, pv={:?})",
+            self.depth, self.seldepth, self.multipv, self.score, self.mate, self.nodes, self.nps, self.time_ms, self.hashfull, self.pv,
+        )
+    }
+}
+
+
+impl SearchInfo {
+    /// Mate distance in moves of a search score, None when the score is not a mate
+    pub fn mateMoves(score: i32, mate_score: i32) -> Option<i32> {
+        if score > mate_score - 1000 {
+            Some((mate_score - score + 1) / 2)
+        } else if score < -mate_score + 1000 {
+            Some(-(score + mate_score) / 2)
+        } else {
+            None
+        }
+    }
+
+
+    pub fn pvMoves(pv: &[Move]) -> Vec<String> {
+        pv.iter().map(|mv| mv.to_string()).collect()
+    }
+}
+
+
+/// Receives search progress while BestMoveFinder::searchPosition runs
+pub trait SearchListener: Send {
+    fn onInfo(&mut self, info: &SearchInfo);
+}
+
+
+/// Calls a python callable with each SearchInfo
+pub struct PyInfoCallback {
+    pub callback: PyObject,
+}
+
+
+impl SearchListener for PyInfoCallback {
+    /// Exceptions raised by the callback are printed and the search carries on
+    fn onInfo(&mut self, info: &SearchInfo) {
+        Python::with_gil(|py| {
+            if let Err(err) = self.callback.call1(py, (info.clone(),)) {
+                err.print(py);
+            }
+        });
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        Mutex,
+    };
+    use crate::engine_modules::{
+        best_move_finder::{
+            BestMoveFinder,
+            SearchOutput,
+        },
+        game_state::GameState,
+        moves::Moves,
+        trans_table::TransTable,
+        zobrist::Zobrist,
+    };
+
+    struct Collector {
+        infos: Arc<Mutex<Vec<SearchInfo>>>,
+    }
+
+    impl SearchListener for Collector {
+        fn onInfo(&mut self, info: &SearchInfo) {
+            self.infos.lock().unwrap().push(info.clone());
+        }
+    }
+
+    #[test]
+    fn listener_gets_each_iteration() {
+        let mut z: Zobrist = Zobrist::new();
+        let mut m: Moves = Moves::new();
+        let tt: TransTable = TransTable::new(TransTable::DEFAULT_SIZE_MB);
+        let mut gs: GameState = GameState::new(&z);
+        gs.importFEN(&m.masks, &z, String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1")).unwrap();
+        let infos: Arc<Mutex<Vec<SearchInfo>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(3);
+        bmf.output_mode = SearchOutput::Silent;
+        bmf.listener = Some(Box::new(Collector {infos: Arc::clone(&infos)}));
+        bmf.searchPosition(&mut m, &mut z, &tt, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+
+        let infos: Vec<SearchInfo> = infos.lock().unwrap().clone();
+        assert!(infos.iter().map(|info| info.depth).collect::<Vec<u32>>() == [1, 2, 3]);
+        let last: &SearchInfo = infos.last().unwrap();
+        assert!(last.mate == Some(1) && last.pv == ["a1a8"]);
+        assert!(last.seldepth >= last.depth && last.nodes > 0 && last.hashfull <= 1000);
+    }
+
+    #[test]
+    fn mate_moves_from_score() {
+        assert!(SearchInfo::mateMoves(48999, 49000) == Some(1));
+        assert!(SearchInfo::mateMoves(48995, 49000) == Some(3));
+        assert!(SearchInfo::mateMoves(-48998, 49000) == Some(-1));
+        assert!(SearchInfo::mateMoves(350, 49000).is_none());
+    }
+}
@@ -0,0 +1,85 @@
+//! Module holding what ends a search: depth, node, time and mate limits or a stop request
+
+
+use pyo3::prelude::*;
+use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use crate::time_manager::TimeManager;
+
+
+#[pyclass(module = "ChessProject", get_all, set_all)]
+#[derive(Clone, Copy, Debug)]
+pub struct SearchLimits {
+    pub depth: u32, // deepest iteration, the PV table holds 64 plies
+    pub nodes: Option<u64>,
+    pub mate: Option<u32>, // stop once a mate in this many moves or less is found
+    pub infinite: bool, // no time limit, only a stop request or the depth ends the search
+    pub time_manager: TimeManager,
+}
+
+
+#[pymethods]
+impl SearchLimits {
+    pub const MAX_DEPTH: u32 = 63;
+
+    /// Limits left as None do not apply, no move time means no time limit
+    #[new]
+    #[pyo3(signature = (depth=SearchLimits::MAX_DEPTH, nodes=None, move_time_ms=None, mate=None, infinite=false))]
+    pub fn new(
+        depth: u32,
+        nodes: Option<u64>,
+        move_time_ms: Option<u64>,
+        mate: Option<u32>,
+        infinite: bool,
+    ) -> Self {
+        SearchLimits {
+            depth: depth.clamp(1, SearchLimits::MAX_DEPTH),
+            nodes,
+            mate,
+            infinite,
+            time_manager: match move_time_ms {
+                Some(move_time_ms) if !infinite => TimeManager::fixedTime(move_time_ms),
+                _ => TimeManager::infinite(),
+            },
+        }
+    }
+}
+
+
+/*
+Shared flag that ends a running search early, the best move of the last
+completed depth is kept. Clones share the same flag so it can be set from
+another thread. The flag stays set until cleared.
+*/
+#[pyclass(module = "ChessProject")]
+#[derive(Clone, Debug, Default)]
+pub struct StopFlag {
+    pub flag: Arc<AtomicBool>,
+}
+
+
+#[pymethods]
+impl StopFlag {
+    #[new]
+    pub fn new() -> Self {
+        StopFlag::default()
+    }
+
+
+    pub fn set(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+
+    pub fn isSet(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,198 @@
+//! Module for Static Exchange Evaluation (SEE) of captures
+
+
+use crate::{
+    chess_move::Move,
+    moves::Moves,
+    piece::Piece,
+};
+
+
+// material values used by the exchange, the king is worth more than everything else combined
+pub const SEE_VALUES: [i32; 6] = [100, 300, 350, 500, 1000, 10000]; // P N B R Q K
+
+
+/// Value of a piece for the exchange, empty squares are worth nothing
+fn seeValue(piece: Piece) -> i32 {
+    match piece {
+        Piece::EP => 0,
+        _ => SEE_VALUES[piece as usize % 6],
+    }
+}
+
+
+/// Attack generation and exchange evaluation on a single square
+impl Moves {
+    /*
+    Returns the pieces of both colours in occupied that attack square
+
+    Sliders are found by sliding from the square itself, so removing a piece from
+    occupied reveals the x-ray attackers behind it.
+    */
+    pub fn attackersTo(&mut self, bitboards: [u64; 13], square: u32, occupied: u64) -> u64 {
+        let square_idx: usize = square as usize;
+        let target: u64 = 1 << (64 - 1 - square_idx);
+        let saved_occupied: u64 = self.masks.occupied;
+        self.masks.occupied = occupied | target;
+
+        // sliders
+        let rooks: u64 = bitboards[Piece::WR] | bitboards[Piece::BR] | bitboards[Piece::WQ] | bitboards[Piece::BQ];
+        let bishops: u64 = bitboards[Piece::WB] | bitboards[Piece::BB] | bitboards[Piece::WQ] | bitboards[Piece::BQ];
+        let mut attackers: u64 = (self.possibleHAndVMoves(square_idx) & rooks)
+            | (self.possibleDiagAndAntiDiagMoves(square_idx) & bishops);
+        self.masks.occupied = saved_occupied;
+
+        // allign the knight_span_c6 mask, knights attacking the square are the ones it could reach
+        let knight_span_c6_idx: usize = 18;
+        let mut knights: u64 = if square_idx <= knight_span_c6_idx {
+            self.masks.knight_span_c6 << (knight_span_c6_idx - square_idx)
+        } else {
+            self.masks.knight_span_c6 >> (square_idx - knight_span_c6_idx)
+        };
+        pop_bits!(knights, if square_idx % 8 < 4 {self.masks.file_gh} else {self.masks.file_ab});
+        attackers |= knights & (bitboards[Piece::WN] | bitboards[Piece::BN]);
+
+        // allign the king_span_c7 mask
+        let king_span_c7_idx: usize = 10;
+        let mut kings: u64 = if square_idx <= king_span_c7_idx {
+            self.masks.king_span_c7 << (king_span_c7_idx - square_idx)
+        } else {
+            self.masks.king_span_c7 >> (square_idx - king_span_c7_idx)
+        };
+        pop_bits!(kings, if square_idx % 8 < 4 {self.masks.file_gh} else {self.masks.file_ab});
+        attackers |= kings & (bitboards[Piece::WK] | bitboards[Piece::BK]);
+
+        // pawns capture diagonally forward, so look one rank behind the square
+        let file_a: u64 = self.masks.file_masks[0];
+        let file_h: u64 = self.masks.file_masks[7];
+        attackers |= (((target >> 7) & !file_h) | ((target >> 9) & !file_a)) & bitboards[Piece::WP];
+        attackers |= (((target << 7) & !file_a) | ((target << 9) & !file_h)) & bitboards[Piece::BP];
+
+        attackers & occupied
+    }
+
+
+    /*
+    Returns the material won (positive) or lost (negative) by the side making the
+    move when both sides keep recapturing on the end square with their least
+    valuable attacker, each side stops as soon as continuing would lose material.
+
+    Uses the swap list algorithm:
+        gain[d] = value of the piece captured at step d minus gain[d-1]
+    which is then folded back from the deepest capture with negamax.
+
+    Quiet moves are evaluated as well, a negative score means the piece is lost
+    on its new square. Pins and checks are ignored.
+    */
+    pub fn see(&mut self, bitboards: [u64; 13], mv: Move) -> i32 {
+        let (start_sq, end_sq) = (mv.startSquare(), mv.endSquare());
+        let (mut attacker, victim) = get_move_pieces!(bitboards, mv);
+        if attacker == Piece::EP {
+            return 0; // no piece on the start square
+        }
+        let mut occupied: u64 = or_array_elems!(Piece::allPieces(), bitboards);
+        let mut gain: [i32; 32] = [0; 32];
+        gain[0] = seeValue(victim);
+        if mv.isEnPassant() {
+            gain[0] = SEE_VALUES[0];
+            let captured_sq: u32 = if start_sq / 8 == 3 {end_sq + 8} else {end_sq - 8};
+            pop_bits!(occupied, 1 << (64 - 1 - captured_sq));
+        }
+        let mut attacker_value: i32 = seeValue(attacker);
+        if mv.isPromotion() {
+            attacker = mv.promotionPiece();
+            attacker_value = seeValue(attacker);
+            gain[0] += attacker_value - SEE_VALUES[0];
+        }
+        pop_bits!(occupied, 1 << (64 - 1 - start_sq));
+
+        let mut white_to_capture: bool = (attacker as usize) >= 6; // the reply comes from the other side
+        let mut d: usize = 0;
+        loop {
+            d += 1;
+            gain[d] = attacker_value - gain[d-1]; // speculative, holds if the piece is recaptured
+            if d == gain.len() - 1 {
+                break;
+            }
+            let attackers: u64 = self.attackersTo(bitboards, end_sq, occupied);
+            let side: [Piece; 6] = if white_to_capture {
+                [Piece::WP, Piece::WN, Piece::WB, Piece::WR, Piece::WQ, Piece::WK]
+            } else {
+                [Piece::BP, Piece::BN, Piece::BB, Piece::BR, Piece::BQ, Piece::BK]
+            };
+            let Some(&next) = side.iter().find(|&&piece| attackers & bitboards[piece] != 0) else {
+                break;
+            };
+            pop_bits!(occupied, get_ls1b!(attackers & bitboards[next]));
+            attacker_value = seeValue(next);
+            white_to_capture = !white_to_capture;
+        }
+        while d > 1 {
+            d -= 1;
+            gain[d-1] = -(-gain[d-1]).max(gain[d]);
+        }
+        gain[0]
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_modules::{
+        chess_move::MoveList,
+        game_state::GameState,
+        zobrist::Zobrist,
+    };
+
+    fn seeOf(fen: &str, move_str: &str) -> i32 {
+        let mut z: Zobrist = Zobrist::new();
+        let mut gs: GameState = GameState::new(&z);
+        let mut m: Moves = Moves::new();
+        gs.importFEN(&m.masks, &z, String::from(fen)).unwrap();
+        let moves: MoveList = m.getValidMoves(&mut z, gs.position.bitboards, gs.position.castle_rights, gs.position.hash_key, gs.position.whites_turn);
+        let mv: Move = moves.iter().copied().find(|mv| mv.to_string() == move_str).unwrap();
+        m.see(gs.position.bitboards, mv)
+    }
+
+    #[test]
+    fn attackers_to_square() {
+        let z: Zobrist = Zobrist::new();
+        let mut gs: GameState = GameState::new(&z);
+        let mut m: Moves = Moves::new();
+        gs.importFEN(&m.masks, &z, String::from("4k3/8/2n2b2/3p4/4P3/2N5/8/R3K3 w - - 0 1")).unwrap();
+        let occupied: u64 = or_array_elems!(Piece::allPieces(), gs.position.bitboards);
+        // d5 is attacked by the e4 pawn and the c3 knight
+        assert!(m.attackersTo(gs.position.bitboards, 27, occupied) == (1 << (63 - 36)) | (1 << (63 - 42)));
+        // e4 is attacked by the d5 pawn and the c3 knight
+        assert!(m.attackersTo(gs.position.bitboards, 36, occupied) == (1 << (63 - 27)) | (1 << (63 - 42)));
+        // a8 is attacked by the a1 rook only once the a-file is open
+        assert!(m.attackersTo(gs.position.bitboards, 0, occupied) == 1 << (63 - 56));
+    }
+
+    #[test]
+    fn see_values() {
+        // undefended pawn
+        assert!(seeOf("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5") == 100);
+        // defended pawn, the knight is lost
+        assert!(seeOf("4k3/8/5p2/4p3/8/3N4/8/4K3 w - - 0 1", "d3e5") == -200);
+        // pawn trade
+        assert!(seeOf("4k3/8/5p2/4p3/3P4/8/8/4K3 w - - 0 1", "d4e5") == 0);
+        // the queens join in behind the rook and the bishop (x-rays)
+        assert!(seeOf("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5") == -200);
+        assert!(seeOf("4k3/4r3/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5") == 100);
+        assert!(seeOf("4k3/4r3/4r3/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5") == -400);
+        // winning the exchange
+        assert!(seeOf("4k3/8/8/3r4/8/4N3/8/4K3 w - - 0 1", "e3d5") == 500);
+        assert!(seeOf("4k3/8/2p5/3r4/8/4N3/8/4K3 w - - 0 1", "e3d5") == 200);
+        // the king may only recapture an undefended piece
+        assert!(seeOf("4k3/8/8/8/8/8/2Kp4/8 b - - 0 1", "d2d1q") == -100);
+        assert!(seeOf("3rk3/8/8/8/8/8/2Kp4/8 b - - 0 1", "d2d1q") == 900);
+        // enpassant and capturing promotion
+        assert!(seeOf("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6") == 100);
+        assert!(seeOf("3rk3/2P5/8/8/8/8/8/4K3 w - - 0 1", "c7d8q") == 400);
+    }
+}
@@ -0,0 +1,150 @@
+//! Module deciding how long a search may run from the clock situation
+
+
+use pyo3::prelude::*;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+
+/*
+Soft and hard time limits of a search.
+
+Iterative deepening starts no new depth past the soft limit, the running
+depth is aborted at the hard limit and the last completed depth is used.
+*/
+#[pyclass(module = "ChessProject")]
+#[derive(Clone, Copy, Debug)]
+pub struct TimeManager {
+    pub soft_limit: Duration,
+    pub hard_limit: Duration,
+    start_time: Instant,
+}
+
+
+#[pymethods]
+impl TimeManager {
+    pub const MOVE_OVERHEAD_MS: u64 = 30; // kept back for communication lag
+    pub const DEFAULT_MOVES_TO_GO: u32 = 30; // assumed moves left in sudden death games
+    pub const HARD_LIMIT_FACTOR: u32 = 4; // how far a single depth may overrun the soft limit
+
+    /*
+    Limits from the clock, times are in milliseconds like the UCI "go" command.
+
+    A fixed move time is used for both limits. Otherwise the remaining time is
+    spread over the moves to go plus most of the increment, with the hard limit
+    capped to 3/4 of the remaining time. No clock information means no limit.
+    */
+    #[new]
+    #[pyo3(signature = (time_left_ms=None, increment_ms=0, moves_to_go=None, move_time_ms=None))]
+    pub fn new(
+        time_left_ms: Option<u64>,
+        increment_ms: u64,
+        moves_to_go: Option<u32>,
+        move_time_ms: Option<u64>,
+    ) -> Self {
+        if let Some(move_time_ms) = move_time_ms {
+            return TimeManager::fixedTime(move_time_ms);
+        }
+        let time_left_ms: u64 = match time_left_ms {
+            Some(time_left_ms) => time_left_ms,
+            None => return TimeManager::infinite(),
+        };
+        let usable: Duration = Duration::from_millis(time_left_ms.saturating_sub(TimeManager::MOVE_OVERHEAD_MS).max(1));
+        let moves_left: u32 = moves_to_go.unwrap_or(TimeManager::DEFAULT_MOVES_TO_GO).max(1);
+        let hard_limit: Duration = (usable / moves_left * TimeManager::HARD_LIMIT_FACTOR + Duration::from_millis(increment_ms))
+            .min(usable * 3 / 4);
+        let soft_limit: Duration = (usable / moves_left + Duration::from_millis(increment_ms * 3 / 4))
+            .min(hard_limit);
+        TimeManager {
+            soft_limit,
+            hard_limit,
+            start_time: Instant::now(),
+        }
+    }
+
+
+    /// Search for exactly the given time
+    #[staticmethod]
+    pub fn fixedTime(move_time_ms: u64) -> Self {
+        let move_time: Duration = Duration::from_millis(move_time_ms.saturating_sub(TimeManager::MOVE_OVERHEAD_MS).max(1));
+        TimeManager {
+            soft_limit: move_time,
+            hard_limit: move_time,
+            start_time: Instant::now(),
+        }
+    }
+
+
+    /// Search until the depth limit or a stop request
+    #[staticmethod]
+    pub fn infinite() -> Self {
+        TimeManager {
+            soft_limit: Duration::MAX,
+            hard_limit: Duration::MAX,
+            start_time: Instant::now(),
+        }
+    }
+
+
+    #[getter(soft_limit_ms)]
+    fn getSoftLimitMs(&self) -> u128 {
+        self.soft_limit.as_millis()
+    }
+
+
+    #[getter(hard_limit_ms)]
+    fn getHardLimitMs(&self) -> u128 {
+        self.hard_limit.as_millis()
+    }
+}
+
+
+impl TimeManager {
+    /// Start the clock, called when the search begins
+    pub fn start(&mut self) {
+        self.start_time = Instant::now();
+    }
+
+
+    pub fn softLimitReached(&self) -> bool {
+        self.start_time.elapsed() >= self.soft_limit
+    }
+
+
+    pub fn hardLimitReached(&self) -> bool {
+        self.start_time.elapsed() >= self.hard_limit
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_follow_the_clock() {
+        let fixed: TimeManager = TimeManager::new(Some(60_000), 1000, None, Some(500));
+        assert!(fixed.soft_limit == Duration::from_millis(470) && fixed.hard_limit == fixed.soft_limit);
+
+        let sudden_death: TimeManager = TimeManager::new(Some(60_030), 0, None, None);
+        assert!(sudden_death.soft_limit == Duration::from_secs(2));
+        assert!(sudden_death.hard_limit == Duration::from_secs(8));
+
+        let with_increment: TimeManager = TimeManager::new(Some(60_030), 1000, Some(20), None);
+        assert!(with_increment.soft_limit == Duration::from_millis(3750));
+        assert!(with_increment.hard_limit == Duration::from_millis(13000));
+
+        // the last move before the time control may not use up the clock
+        let last_move: TimeManager = TimeManager::new(Some(4030), 0, Some(1), None);
+        assert!(last_move.hard_limit == Duration::from_millis(3000));
+        assert!(last_move.soft_limit <= last_move.hard_limit);
+
+        let unlimited: TimeManager = TimeManager::new(None, 0, None, None);
+        assert!(!unlimited.softLimitReached() && !unlimited.hardLimitReached());
+    }
+}
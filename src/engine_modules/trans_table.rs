@@ -2,6 +2,12 @@
 
 
 use pyo3::prelude::*;
+use crate::chess_move::Move;
+use std::sync::atomic::{
+    AtomicU32,
+    AtomicU64,
+    Ordering,
+};
 
 
 pub enum HashFlag {
@@ -11,13 +17,20 @@ pub enum HashFlag {
 }
 
 
-#[pyclass(module = "ChessProject", get_all, set_all)]
+#[pyclass(module = "ChessProject")]
 #[derive(Clone, Debug)]
 pub struct TransTableEntry {
+    #[pyo3(get, set)]
     pub hash_key: u64,
+    #[pyo3(get, set)]
     pub depth: u32, // current search depth
+    #[pyo3(get, set)]
     pub flag: i32, // flag the type of move: fail-low, fail-high, PV
+    #[pyo3(get, set)]
     pub score: i32, // alpha, beta, or PV
+    #[pyo3(get, set)]
+    pub generation: u32, // search that wrote the entry, see TransTable::newSearch
+    pub best_move: Move, // PV move or refutation, null move when every move failed low
 }
 
 
@@ -30,14 +43,108 @@ impl TransTableEntry {
             depth: 0,
             flag: 0,
             score: 0,
+            generation: 0,
+            best_move: Move::NULL,
         }
     }
+
+
+    /// Best move in coordinate notation ("e2e4"), None without a best move
+    #[getter(best_move)]
+    fn getBestMove(&self) -> Option<String> {
+        (self.best_move != Move::NULL).then(|| self.best_move.to_string())
+    }
+}
+
+
+/*
+Table slot that can be read and written by several search threads without a lock.
+
+The entry is packed into one word and the hash key is stored XORed with it, a
+slot torn by two threads writing at once no longer matches its key and is
+treated as empty.
+*/
+#[derive(Debug, Default)]
+pub struct TransTableSlot {
+    key: AtomicU64, // hash key ^ data
+    data: AtomicU64, // score in the low 32 bits, then depth (8 bits), flag (2 bits), generation (6 bits) and best move (16 bits)
 }
 
 
-#[pyclass(module = "ChessProject", get_all, set_all)]
+impl TransTableSlot {
+    fn pack(depth: u32, flag: i32, score: i32, generation: u32, best_move: Move) -> u64 {
+        (score as u32 as u64)
+            | ((depth.min(255) as u64) << 32)
+            | (((flag as u64) & 0x3) << 40)
+            | (((generation & TransTable::GENERATION_MASK) as u64) << 42)
+            | ((best_move.bits() as u64) << 48)
+    }
+
+
+    fn unpack(hash_key: u64, data: u64) -> TransTableEntry {
+        TransTableEntry {
+            hash_key,
+            depth: ((data >> 32) & 0xff) as u32,
+            flag: ((data >> 40) & 0x3) as i32,
+            score: data as u32 as i32,
+            generation: ((data >> 42) & 0x3f) as u32,
+            best_move: Move::fromBits((data >> 48) as u16),
+        }
+    }
+
+
+    /// Entry stored for a hash key, None when the slot holds another position
+    fn load(&self, hash_key: u64) -> Option<TransTableEntry> {
+        let data: u64 = self.data.load(Ordering::Relaxed);
+        if self.key.load(Ordering::Relaxed) ^ data != hash_key || self.isEmpty() {
+            return None;
+        }
+        Some(TransTableSlot::unpack(hash_key, data))
+    }
+
+
+    /// Entry of whatever position the slot holds, None when empty
+    fn loadAny(&self) -> Option<TransTableEntry> {
+        let data: u64 = self.data.load(Ordering::Relaxed);
+        let key: u64 = self.key.load(Ordering::Relaxed);
+        (key != 0 || data != 0).then(|| TransTableSlot::unpack(key ^ data, data))
+    }
+
+
+    fn store(&self, hash_key: u64, data: u64) {
+        self.key.store(hash_key ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+
+    fn isEmpty(&self) -> bool {
+        self.key.load(Ordering::Relaxed) == 0 && self.data.load(Ordering::Relaxed) == 0
+    }
+
+
+    fn clear(&self) {
+        self.key.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
+}
+
+
+/*
+Slots of the positions mapped to the same index:
+    slot 0 keeps the deepest entry of the current search
+    slot 1 is always replaced
+*/
+#[derive(Debug, Default)]
+pub struct TransTableBucket {
+    slots: [TransTableSlot; 2],
+}
+
+
+/// Transposition table shared by all search threads, every method takes &self except resize
+#[pyclass(module = "ChessProject")]
 pub struct TransTable {
-    pub table: Vec<TransTableEntry>, // large size so heap allocated
+    pub table: Vec<TransTableBucket>, // large size so heap allocated, length is a power of two
+    generation: AtomicU32, // increased at the start of every search to age old entries
 }
 
 
@@ -46,33 +153,75 @@ impl TransTable {
     /*
     Table Memory Analysis:
 
-    TransTableEntry = u64 + u32 + i32 + i32 = 160 bits
-    Vec = heap-allocated buffer pointer + length + capacity = 3 * u64 = 192 bits
-    Table = TRANS_TABLE_SIZE * TransTableEntry + Vec = 5_000_000 * 160 + 192 ~= 100 MB
+    TransTableSlot = 2 * AtomicU64 = 128 bits
+    TransTableBucket = 2 * TransTableSlot = 256 bits = 32 bytes
+    Table = largest power of two bucket count fitting in size_mb, 64 MB = 2_097_152 buckets
     */
-    pub const TRANS_TABLE_SIZE: usize = 5_000_000;
+    pub const DEFAULT_SIZE_MB: usize = 64;
+    pub const MAX_SIZE_MB: usize = 65536;
     pub const NO_HASH_ENTRY: i32 = 100000;
+    pub const GENERATION_MASK: u32 = 0x3f; // generations wrap around after 64 searches
+
     #[new]
-    pub fn new() -> Self {
+    #[pyo3(signature = (size_mb=TransTable::DEFAULT_SIZE_MB))]
+    pub fn new(size_mb: usize) -> Self {
         TransTable {
-            table: vec![TransTableEntry::new(); TransTable::TRANS_TABLE_SIZE],
+            table: TransTable::allocate(size_mb),
+            generation: AtomicU32::new(0),
         }
     }
 
 
+    /// Reallocate the table with a new size in MB, the stored entries are lost
+    pub fn resize(&mut self, size_mb: usize) {
+        self.table = Vec::new(); // free the old table before allocating the new one
+        self.table = TransTable::allocate(size_mb);
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+
+    #[getter(size_mb)]
+    pub fn sizeMb(&self) -> usize {
+        self.table.len() * std::mem::size_of::<TransTableBucket>() / (1024 * 1024)
+    }
+
+
     /// Clears the transposition table
-    pub fn clearTable(&mut self) {
-        for i in 0..TransTable::TRANS_TABLE_SIZE {
-            self.table[i].hash_key = 0;
-            self.table[i].depth = 0;
-            self.table[i].flag = 0;
-            self.table[i].score = 0;
+    pub fn clearTable(&self) {
+        for slot in self.table.iter().flat_map(|bucket| bucket.slots.iter()) {
+            slot.clear();
         }
+        self.generation.store(0, Ordering::Relaxed);
     }
 
 
-    /// Read an entry in the transposition table
-    pub fn readEntry(
+    /// Start a new search, entries of earlier searches are replaced first
+    pub fn newSearch(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+
+    /// Permille of the table used by the current search, estimated from the first 1000 slots
+    pub fn hashfull(&self) -> u32 {
+        let generation: u32 = self.currentGeneration();
+        self.table.iter()
+            .flat_map(|bucket| bucket.slots.iter())
+            .take(1000)
+            .filter(|slot| slot.loadAny().is_some_and(|entry| entry.generation == generation))
+            .count() as u32
+    }
+
+
+    /// Entry stored for a hash key, None when the position is not in the table
+    pub fn probe(&self, hash_key: u64) -> Option<TransTableEntry> {
+        let bucket: &TransTableBucket = self.bucket(hash_key);
+        bucket.slots[0].load(hash_key).or_else(|| bucket.slots[1].load(hash_key))
+    }
+
+
+    /// Score of the table entry for a hash key, NO_HASH_ENTRY when it gives no cutoff
+    #[pyo3(name = "readEntry")]
+    fn readEntryPy(
         &self,
         alpha: i32,
         beta: i32,
@@ -80,40 +229,183 @@ impl TransTable {
         depth: i32,
         ply: u32,
     ) -> i32 {
-        let table_entry: &TransTableEntry = &self.table[hash_key as usize % TransTable::TRANS_TABLE_SIZE];
-        if table_entry.hash_key == hash_key && table_entry.depth as i32 >= depth {
+        self.readEntry(alpha, beta, hash_key, depth, ply).0
+    }
+
+
+    /// Write an entry without a best move
+    #[pyo3(name = "writeEntry")]
+    fn writeEntryPy(
+        &self,
+        score: i32,
+        hash_key: u64,
+        depth: u32,
+        ply: u32,
+        hash_flag: i32,
+    ) {
+        self.writeEntry(score, hash_key, depth, ply, hash_flag, Move::NULL);
+    }
+}
+
+
+/// Table access used by the search
+impl TransTable {
+    /// Buckets of the largest power of two count that fits in size_mb, at least one
+    fn allocate(size_mb: usize) -> Vec<TransTableBucket> {
+        let size_bytes: usize = size_mb.clamp(1, TransTable::MAX_SIZE_MB) * 1024 * 1024;
+        let bucket_count: usize = 1 << (size_bytes / std::mem::size_of::<TransTableBucket>()).ilog2();
+        (0..bucket_count).map(|_| TransTableBucket::default()).collect()
+    }
+
+
+    fn bucket(&self, hash_key: u64) -> &TransTableBucket {
+        &self.table[hash_key as usize & (self.table.len() - 1)]
+    }
+
+
+    fn currentGeneration(&self) -> u32 {
+        self.generation.load(Ordering::Relaxed) & TransTable::GENERATION_MASK
+    }
+
+
+    /*
+    Read an entry in the transposition table, returns the score (NO_HASH_ENTRY
+    when there is no cutoff) and the stored best move. The move is returned
+    even when the entry is too shallow for a cutoff so it can be searched first.
+    */
+    pub fn readEntry(
+        &self,
+        alpha: i32,
+        beta: i32,
+        hash_key: u64,
+        depth: i32,
+        ply: u32,
+    ) -> (i32, Move) {
+        let table_entry: TransTableEntry = match self.probe(hash_key) {
+            Some(table_entry) => table_entry,
+            None => return (TransTable::NO_HASH_ENTRY, Move::NULL),
+        };
+        if table_entry.depth as i32 >= depth {
             let mut score: i32 = table_entry.score;
             // add distance from root to current node if mate score
             score += if score > 48000 {-(ply as i32)} else if score < -48000 {ply as i32} else {0};
             if table_entry.flag == HashFlag::Exact as i32 {
-                return score;
+                return (score, table_entry.best_move);
             }
             if table_entry.flag == HashFlag::Alpha as i32 && score <= alpha {
-                return alpha;
+                return (alpha, table_entry.best_move);
             }
             if table_entry.flag == HashFlag::Beta as i32 && score >= beta {
-                return beta;
+                return (beta, table_entry.best_move);
             }
         }
-        TransTable::NO_HASH_ENTRY
+        (TransTable::NO_HASH_ENTRY, table_entry.best_move)
     }
 
 
-    /// Write an entry in the transposition table
+    /*
+    Write an entry in the transposition table.
+
+    The depth-preferred slot is replaced by the same position, by an entry at
+    least as deep or when it holds an entry of an earlier search, otherwise the
+    always-replace slot is used. A write without a best move keeps the move
+    already stored for the same position.
+    */
     pub fn writeEntry(
-        &mut self,
+        &self,
         mut score: i32,
         hash_key: u64,
         depth: u32,
         ply: u32,
         hash_flag: i32,
+        mut best_move: Move,
     ) {
-        let table_entry: &mut TransTableEntry = &mut self.table[hash_key as usize % TransTable::TRANS_TABLE_SIZE];
         // mate scores should be path independant in table, remove distance from root to current node from score
         score += if score > 48000 {ply as i32} else if score < -48000 {-(ply as i32)} else {0};
-        table_entry.hash_key = hash_key;
-        table_entry.depth = depth;
-        table_entry.flag = hash_flag;
-        table_entry.score = score;
+        if best_move == Move::NULL {
+            best_move = self.probe(hash_key).map_or(Move::NULL, |entry| entry.best_move);
+        }
+        let generation: u32 = self.currentGeneration();
+        let bucket: &TransTableBucket = self.bucket(hash_key);
+        let replace_deepest: bool = bucket.slots[0].loadAny().is_none_or(|entry| {
+            entry.hash_key == hash_key || entry.generation != generation || depth >= entry.depth
+        });
+        let slot: &TransTableSlot = &bucket.slots[if replace_deepest {0} else {1}];
+        slot.store(hash_key, TransTableSlot::pack(depth, hash_flag, score, generation, best_move));
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn entries_survive_concurrent_writes() {
+        let tt: Arc<TransTable> = Arc::new(TransTable::new(1));
+        let buckets: u64 = tt.table.len() as u64;
+        let mv: Move = Move::new(52, 36, Move::DOUBLE_PUSH);
+        tt.writeEntry(-48990, 42, 3, 2, HashFlag::Exact as i32, mv);
+        let entry: TransTableEntry = tt.probe(42).unwrap();
+        assert!(entry.depth == 3 && entry.flag == HashFlag::Exact as i32 && entry.score == -48992 && entry.best_move == mv);
+        assert!(tt.readEntry(-100, 100, 42, 3, 2) == (-48990, mv));
+        // the move is still returned from an entry too shallow for a cutoff
+        assert!(tt.readEntry(-100, 100, 42, 5, 2) == (TransTable::NO_HASH_ENTRY, mv));
+        // a fail-low write keeps the stored move
+        tt.writeEntry(-50, 42, 4, 2, HashFlag::Alpha as i32, Move::NULL);
+        assert!(tt.probe(42).unwrap().best_move == mv);
+        assert!(tt.probe(42 + buckets).is_none());
+
+        // threads writing different positions to the same bucket never leave a mixed entry
+        let writers: Vec<thread::JoinHandle<()>> = (1..=4u64).map(|id| {
+            let tt: Arc<TransTable> = Arc::clone(&tt);
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    tt.writeEntry(id as i32 * 100, id * buckets + 7, id as u32, 0, HashFlag::Beta as i32, Move::NULL);
+                }
+            })
+        }).collect();
+        for _ in 0..10_000 {
+            for id in 1..=4u64 {
+                if let Some(entry) = tt.probe(id * buckets + 7) {
+                    assert!(entry.depth == id as u32 && entry.score == id as i32 * 100);
+                }
+            }
+        }
+        writers.into_iter().for_each(|writer| writer.join().unwrap());
+        tt.clearTable();
+        assert!(tt.probe(42).is_none() && tt.hashfull() == 0);
+    }
+
+    #[test]
+    fn size_replacement_and_aging() {
+        let mut tt: TransTable = TransTable::new(1);
+        assert!(tt.table.len() == 32768 && tt.sizeMb() == 1);
+        tt.resize(3); // rounded down to a power of two
+        assert!(tt.table.len() == 65536 && tt.sizeMb() == 2);
+        let buckets: u64 = tt.table.len() as u64;
+
+        // a shallower entry goes to the always-replace slot and the deep entry is kept
+        tt.writeEntry(10, 5, 8, 0, HashFlag::Exact as i32, Move::NULL);
+        tt.writeEntry(20, 5 + buckets, 2, 0, HashFlag::Exact as i32, Move::NULL);
+        tt.writeEntry(30, 5 + 2 * buckets, 1, 0, HashFlag::Exact as i32, Move::NULL);
+        assert!(tt.probe(5).unwrap().score == 10);
+        assert!(tt.probe(5 + buckets).is_none() && tt.probe(5 + 2 * buckets).unwrap().score == 30);
+
+        // entries of an earlier search are replaced even when deeper
+        tt.newSearch();
+        tt.writeEntry(40, 5 + buckets, 1, 0, HashFlag::Exact as i32, Move::NULL);
+        assert!(tt.probe(5).is_none() && tt.probe(5 + buckets).unwrap().generation == 1);
+
+        // only entries of the current search count as used
+        for hash_key in 0..250 {
+            tt.writeEntry(0, hash_key, 1, 0, HashFlag::Exact as i32, Move::NULL);
+        }
+        assert!(tt.hashfull() == 250);
     }
 }
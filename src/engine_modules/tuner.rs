@@ -0,0 +1,363 @@
+//! Module for tuning the evaluation weights on positions with known game results (Texel's method)
+
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::{
+    fmt,
+    fs,
+    thread,
+};
+use crate::{
+    best_move_finder::{
+        BestMoveFinder,
+        SearchOutput,
+    },
+    eval_params::{
+        EvalParams,
+        EvalParamsError,
+    },
+    fen_error::FenError,
+    game_state::GameState,
+    moves::Moves,
+    position::Position,
+    trans_table::TransTable,
+    zobrist::Zobrist,
+};
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TunerError {
+    Io(String),
+    MissingResult(usize), // line number starting at 1
+    BadFen(Option<usize>, FenError), // line number, None for positions added one at a time
+    BadResult(String),
+    Params(EvalParamsError),
+}
+
+
+impl fmt::Display for TunerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TunerError::Io(err) => write!(f, "could not read positions, {}", err),
+            TunerError::MissingResult(line) => write!(f, "line {} has no game result", line),
+            TunerError::BadFen(Some(line), err) => write!(f, "line {}, {}", line, err),
+            TunerError::BadFen(None, err) => write!(f, "{}", err),
+            TunerError::BadResult(result) => write!(f, "result {} is not between 0 and 1", result),
+            TunerError::Params(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+
+impl std::error::Error for TunerError {}
+
+
+impl From<EvalParamsError> for TunerError {
+    fn from(err: EvalParamsError) -> TunerError {
+        TunerError::Params(err)
+    }
+}
+
+
+/// Bad datasets raise a ValueError in python
+impl From<TunerError> for PyErr {
+    fn from(err: TunerError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+
+/*
+Texel tuning of EvalParams.
+
+The evaluation q of each position (white's side, centipawns) is mapped to an
+expected score with the sigmoid
+    s(q) = 1 / (1 + 10^(-k * q / 400))
+and the error is the mean squared difference to the game results (1 white win,
+0.5 draw, 0 black win). k is first fitted to the current weights, then every
+weight is moved by step in both directions, keeping changes that lower the
+error, until a whole pass brings no improvement.
+
+The positions should be quiet, with use_quiescence the score of a quiescence
+search is used instead of the static evaluation.
+*/
+#[pyclass(module = "ChessProject")]
+pub struct Tuner {
+    positions: Vec<(Position, f64)>, // position and result from white's side
+    #[pyo3(get, set)]
+    pub params: EvalParams,
+    #[pyo3(get, set)]
+    pub k: f64, // sigmoid scaling, see findK()
+    #[pyo3(get, set)]
+    pub step: i32, // change tried for each weight
+    #[pyo3(get, set)]
+    pub use_quiescence: bool,
+    #[pyo3(get, set)]
+    pub threads: u32, // positions are scored in parallel
+    #[pyo3(get, set)]
+    pub verbose: bool, // print the error after each pass
+    mm: Moves,
+    z: Zobrist,
+}
+
+
+#[pymethods]
+impl Tuner {
+    pub const TT_SIZE_MB: usize = 1; // move ordering table of each quiescence thread
+
+    #[new]
+    pub fn new(params: EvalParams) -> Self {
+        Tuner {
+            positions: Vec::new(),
+            params,
+            k: 1.0,
+            step: 1,
+            use_quiescence: false,
+            threads: thread::available_parallelism().map_or(1, |n| n.get() as u32),
+            verbose: false,
+            mm: Moves::new(),
+            z: Zobrist::new(),
+        }
+    }
+
+
+    /*
+    Add the positions of a dataset file, returns how many were read.
+
+    Each line holds a FEN (the move counters may be left out) and a game result
+    in any of the common forms:
+        <fen> 1-0                           (also 0-1 and 1/2-1/2)
+        <fen> [0.5]                         (also 1.0 and 0.0)
+        <4 fen fields> c9 "1/2-1/2";        (EPD)
+    Blank lines and lines starting with # are skipped.
+    */
+    pub fn loadPositions(&mut self, path: &str) -> Result<usize, TunerError> {
+        let text: String = fs::read_to_string(path).map_err(|err| TunerError::Io(err.to_string()))?;
+        let mut gs: GameState = GameState::new(&self.z);
+        let start_count: usize = self.positions.len();
+        for (line_idx, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (fen, result) = Tuner::parseLine(line).ok_or(TunerError::MissingResult(line_idx + 1))?;
+            gs.importFEN(&self.mm.masks, &self.z, fen).map_err(|err| TunerError::BadFen(Some(line_idx + 1), err))?;
+            self.positions.push((gs.position.clone(), result));
+        }
+        Ok(self.positions.len() - start_count)
+    }
+
+
+    /// Add a single position, result is from white's side (1 win, 0.5 draw, 0 loss)
+    pub fn addPosition(&mut self, fen: &str, result: f64) -> Result<(), TunerError> {
+        if !(0.0..=1.0).contains(&result) {
+            return Err(TunerError::BadResult(result.to_string()));
+        }
+        let mut gs: GameState = GameState::new(&self.z);
+        gs.importFEN(&self.mm.masks, &self.z, String::from(fen)).map_err(|err| TunerError::BadFen(None, err))?;
+        self.positions.push((gs.position, result));
+        Ok(())
+    }
+
+
+    pub fn positionCount(&self) -> usize {
+        self.positions.len()
+    }
+
+
+    /// Mean squared error of the current weights and k
+    pub fn error(&self) -> f64 {
+        self.errorOf(&self.scores(&self.params), self.k)
+    }
+
+
+    /// Fit k to the current weights by narrowing the search around the best value, returns k
+    pub fn findK(&mut self) -> f64 {
+        let scores: Vec<i32> = self.scores(&self.params);
+        let mut best_k: f64 = self.k;
+        let mut best_error: f64 = self.errorOf(&scores, best_k);
+        for precision in [1.0, 0.1, 0.01, 0.001] {
+            let centre: f64 = best_k;
+            for i in -10..=10 {
+                let k: f64 = centre + i as f64 * precision;
+                if k <= 0.0 {
+                    continue;
+                }
+                let error: f64 = self.errorOf(&scores, k);
+                if error < best_error {
+                    best_error = error;
+                    best_k = k;
+                }
+            }
+        }
+        self.k = best_k;
+        best_k
+    }
+
+
+    /*
+    Local search over the named weights (all but the king values and the phase
+    weights when None) for at most max_passes passes, returns the final error.
+
+    With output_path the weights are saved after every pass so long runs can be
+    stopped at any time.
+    */
+    #[pyo3(signature = (max_passes, names=None, output_path=None))]
+    pub fn tune(&mut self, max_passes: u32, names: Option<Vec<String>>, output_path: Option<&str>) -> Result<f64, TunerError> {
+        let names: Vec<String> = match names {
+            Some(names) => names,
+            None => self.params.paramNames().into_iter()
+                .filter(|name| !name.ends_with(".K") && !name.starts_with("phase_weights."))
+                .collect(),
+        };
+        for name in &names {
+            if self.params.getParam(name).is_none() {
+                return Err(TunerError::Params(EvalParamsError::UnknownParam(name.clone())));
+            }
+        }
+        let mut best_error: f64 = self.error();
+        for pass in 0..max_passes {
+            let mut improved: bool = false;
+            for name in &names {
+                let value: i32 = self.params.getParam(name).unwrap();
+                for new_value in [value + self.step, value - self.step] {
+                    self.params.setParam(name, new_value)?;
+                    let error: f64 = self.error();
+                    if error < best_error {
+                        best_error = error;
+                        improved = true;
+                        break;
+                    }
+                    self.params.setParam(name, value)?;
+                }
+            }
+            if self.verbose {
+                println!("pass {} error {:.8}", pass + 1, best_error);
+            }
+            if let Some(path) = output_path {
+                self.params.save(path)?;
+            }
+            if !improved {
+                break;
+            }
+        }
+        Ok(best_error)
+    }
+}
+
+
+impl Tuner {
+    /// Split a dataset line into a FEN and a result from white's side
+    fn parseLine(line: &str) -> Option<(String, f64)> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() < 5 {
+            return None;
+        }
+        let result: f64 = tokens[4..].iter().find_map(|token| {
+            let token: &str = token.trim_matches(|c: char| c == '"' || c == ';' || c == '[' || c == ']');
+            match token {
+                "1-0" => Some(1.0),
+                "0-1" => Some(0.0),
+                "1/2-1/2" => Some(0.5),
+                // move counters never hold a decimal point
+                _ if token.contains('.') => token.parse::<f64>().ok().filter(|result| (0.0..=1.0).contains(result)),
+                _ => None,
+            }
+        })?;
+        let counters: &[&str] = match tokens.get(4..6) {
+            Some(counters) if counters.iter().all(|c| c.parse::<u32>().is_ok()) => counters,
+            _ => &["0", "1"],
+        };
+        Some((format!("{} {}", tokens[..4].join(" "), counters.join(" ")), result))
+    }
+
+
+    /// Scores of all positions from white's side with the given weights, split over the threads
+    fn scores(&self, params: &EvalParams) -> Vec<i32> {
+        let chunk_size: usize = self.positions.len().div_ceil(self.threads.max(1) as usize).max(1);
+        thread::scope(|scope| {
+            let handles: Vec<thread::ScopedJoinHandle<Vec<i32>>> = self.positions.chunks(chunk_size)
+                .map(|chunk| {
+                    let (mut mm, mut z) = (self.mm.clone(), self.z.clone());
+                    scope.spawn(move || {
+                        let mut bmf: BestMoveFinder = BestMoveFinder::new(1);
+                        bmf.output_mode = SearchOutput::Silent;
+                        bmf.eval_params = params.clone();
+                        let tt: Option<TransTable> = if self.use_quiescence {Some(TransTable::new(Tuner::TT_SIZE_MB))} else {None};
+                        chunk.iter().map(|(pos, _)| match &tt {
+                            Some(tt) => bmf.quiescenceScore(&mut mm, &mut z, tt, &mut pos.clone()),
+                            None => bmf.evaluateBoard(&mut mm, pos.bitboards),
+                        }).collect()
+                    })
+                })
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().expect("tuner thread panicked")).collect()
+        })
+    }
+
+
+    /// Mean squared error between the results and the sigmoid of the scores
+    fn errorOf(&self, scores: &[i32], k: f64) -> f64 {
+        if scores.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = scores.iter().zip(&self.positions)
+            .map(|(&score, (_, result))| {
+                let expected: f64 = 1.0 / (1.0 + 10f64.powf(-k * score as f64 / 400.0));
+                (result - expected).powi(2)
+            })
+            .sum();
+        total / scores.len() as f64
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dataset_lines_are_parsed() {
+        let (fen, result) = Tuner::parseLine("rnb1kbnr/pp1pppp1/7p/2q5/5P2/N1P1P3/P2P2PP/R1BQKBNR w KQkq - c9 \"1/2-1/2\";").unwrap();
+        assert!(fen == "rnb1kbnr/pp1pppp1/7p/2q5/5P2/N1P1P3/P2P2PP/R1BQKBNR w KQkq - 0 1" && result == 0.5);
+        let (fen, result) = Tuner::parseLine("8/8/8/8/8/8/PK5k/8 w - - 3 40 [1.0]").unwrap();
+        assert!(fen == "8/8/8/8/8/8/PK5k/8 w - - 3 40" && result == 1.0);
+        assert!(Tuner::parseLine("8/8/8/8/8/8/PK5k/8 b - - 0-1").unwrap().1 == 0.0);
+        // the move counters are not a result
+        assert!(Tuner::parseLine("8/8/8/8/8/8/PK5k/8 w - - 0 1").is_none());
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        let path: std::path::PathBuf = std::env::temp_dir().join("tuner_positions.epd");
+        fs::write(&path, "
+            # white is a pawn up and wins, black has an extra knight and wins
+            4k3/8/8/8/3P4/8/8/4K3 w - - c9 \"1-0\";
+            4k3/8/8/3P4/8/8/8/4K3 b - - c9 \"1-0\";
+            4k3/8/3n4/8/8/8/8/4K3 w - - c9 \"0-1\";
+            4k3/8/8/8/8/8/8/4K3 w - - c9 \"1/2-1/2\";
+        ").unwrap();
+        let mut tuner: Tuner = Tuner::new(EvalParams::new());
+        assert!(tuner.loadPositions(path.to_str().unwrap()).unwrap() == 4);
+        fs::remove_file(&path).unwrap();
+        tuner.threads = 2;
+        let start_error: f64 = tuner.error();
+        tuner.findK();
+        let fitted_error: f64 = tuner.error();
+        assert!(fitted_error <= start_error);
+        tuner.step = 50;
+        let names: Vec<String> = vec![String::from("piece_scores_eg.P"), String::from("piece_scores_eg.N")];
+        let tuned_error: f64 = tuner.tune(3, Some(names), None).unwrap();
+        assert!(tuned_error < fitted_error && tuned_error == tuner.error());
+        assert!(tuner.params.piece_scores_eg[&'P'] > EvalParams::new().piece_scores_eg[&'P']);
+
+        tuner.use_quiescence = true;
+        assert!(tuner.error() > 0.0);
+        assert!(tuner.tune(1, Some(vec![String::from("no_such_weight")]), None).is_err());
+        assert!(tuner.loadPositions("no/such/file.epd").is_err());
+    }
+}
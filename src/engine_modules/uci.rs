@@ -0,0 +1,382 @@
+//! Module implementing the UCI (Universal Chess Interface) protocol
+
+
+use std::io::{
+    self,
+    BufRead,
+};
+use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use std::thread::{
+    self,
+    JoinHandle,
+};
+use std::time::Duration;
+use crate::{
+    best_move_finder::{
+        BestMoveFinder,
+        SearchOutput,
+    },
+    eval_params::EvalParams,
+    game_state::GameState,
+    moves::Moves,
+    search_context::SearchContext,
+    search_limits::SearchLimits,
+    time_manager::TimeManager,
+    trans_table::TransTable,
+    zobrist::Zobrist,
+};
+
+
+pub struct Uci {
+    pub gs: GameState,
+    history: Vec<u64>, // hash keys of the positions played before the current one
+    context: Option<SearchContext>,
+    search_thread: Option<JoinHandle<SearchContext>>,
+    stop: Arc<AtomicBool>,
+}
+
+
+impl Uci {
+    pub const ENGINE_NAME: &'static str = "ChessProject";
+    pub const ENGINE_AUTHOR: &'static str = "Steven Brown";
+    pub const MAX_SEARCH_DEPTH: u32 = 63; // PV table holds 64 plies
+    pub const DEFAULT_MOVE_TIME_MS: u64 = 3000;
+    pub const MAX_MULTI_PV: u32 = 64;
+    pub const MAX_THREADS: u32 = 256;
+    pub const MAX_EVAL_WEIGHT: i32 = 10000; // bound of the evaluation weight options
+
+    pub fn new() -> Self {
+        let z: Zobrist = Zobrist::new();
+        let gs: GameState = GameState::new(&z);
+        let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(Uci::MAX_SEARCH_DEPTH);
+        bmf.output_mode = SearchOutput::Uci;
+        bmf.stop = Arc::clone(&stop);
+        Uci {
+            gs,
+            history: Vec::new(),
+            context: Some(SearchContext {
+                bmf,
+                mm: Moves::new(),
+                z,
+                tt: TransTable::new(TransTable::DEFAULT_SIZE_MB),
+            }),
+            search_thread: None,
+            stop,
+        }
+    }
+
+
+    /// Read commands from stdin until quit is received
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => if !self.handleCommand(&line) {
+                    break;
+                },
+                Err(_) => break,
+            }
+        }
+        self.stopSearch();
+    }
+
+
+    /// Process a single command, returns false when the engine should quit
+    pub fn handleCommand(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"uci") => {
+                println!("id name {}", Uci::ENGINE_NAME);
+                println!("id author {}", Uci::ENGINE_AUTHOR);
+                println!("option name Hash type spin default {} min 1 max {}", TransTable::DEFAULT_SIZE_MB, TransTable::MAX_SIZE_MB);
+                println!("option name Clear Hash type button");
+                println!("option name MultiPV type spin default 1 min 1 max {}", Uci::MAX_MULTI_PV);
+                println!("option name Threads type spin default 1 min 1 max {}", Uci::MAX_THREADS);
+                println!("option name EvalFile type string default <empty>");
+                let params: EvalParams = EvalParams::new();
+                for name in Uci::evalOptionNames(&params) {
+                    println!(
+                        "option name {} type spin default {} min {} max {}",
+                        name, params.getParam(&name).unwrap(), -Uci::MAX_EVAL_WEIGHT, Uci::MAX_EVAL_WEIGHT,
+                    );
+                }
+                println!("uciok");
+            },
+            Some(&"isready") => println!("readyok"),
+            Some(&"ucinewgame") => {
+                let ctx: &mut SearchContext = self.waitForSearch();
+                ctx.tt.clearTable();
+                let mut bmf: BestMoveFinder = BestMoveFinder::new(Uci::MAX_SEARCH_DEPTH);
+                bmf.output_mode = SearchOutput::Uci;
+                bmf.stop = Arc::clone(&ctx.bmf.stop);
+                bmf.multi_pv = ctx.bmf.multi_pv;
+                bmf.threads = ctx.bmf.threads;
+                bmf.eval_params = ctx.bmf.eval_params.clone();
+                ctx.bmf = bmf;
+            },
+            Some(&"setoption") => self.setOption(&tokens[1..]),
+            Some(&"position") => self.position(&tokens[1..]),
+            Some(&"go") => self.go(&tokens[1..]),
+            Some(&"stop") => self.stopSearch(),
+            Some(&"d") => self.gs.drawGameArray(),
+            Some(&"eval") => {
+                let bitboards: [u64; 13] = self.gs.position.bitboards;
+                let ctx: &mut SearchContext = self.waitForSearch();
+                println!("{}", ctx.bmf.traceEvaluation(&mut ctx.mm, bitboards));
+            },
+            Some(&"quit") => return false,
+            _ => (),
+        }
+        true
+    }
+
+
+    /// Block until the running search (if any) returns the engine components
+    pub fn waitForSearch(&mut self) -> &mut SearchContext {
+        if let Some(handle) = self.search_thread.take() {
+            self.context = Some(handle.join().expect("search thread panicked"));
+        }
+        self.context.as_mut().unwrap()
+    }
+
+
+    /// Signal the running search to finish and wait for its best move
+    fn stopSearch(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.waitForSearch();
+    }
+
+
+    /// Handle "setoption name <id> [value <x>]"
+    fn setOption(&mut self, args: &[&str]) {
+        let value_idx: usize = args.iter().position(|&t| t == "value").unwrap_or(args.len());
+        let name: String = args[1.min(value_idx)..value_idx].join(" ");
+        let value: Option<&str> = args.get(value_idx + 1).copied();
+        let ctx: &mut SearchContext = self.waitForSearch();
+        if name.eq_ignore_ascii_case("Clear Hash") {
+            ctx.tt.clearTable();
+        } else if name.eq_ignore_ascii_case("Hash") {
+            if let Some(size_mb) = value.and_then(|v| v.parse::<usize>().ok()) {
+                ctx.tt.resize(size_mb);
+            }
+        } else if name.eq_ignore_ascii_case("MultiPV") {
+            if let Some(lines) = value.and_then(|v| v.parse::<u32>().ok()) {
+                ctx.bmf.multi_pv = lines.clamp(1, Uci::MAX_MULTI_PV);
+            }
+        } else if name.eq_ignore_ascii_case("Threads") {
+            if let Some(threads) = value.and_then(|v| v.parse::<u32>().ok()) {
+                ctx.bmf.threads = threads.clamp(1, Uci::MAX_THREADS);
+            }
+        } else if name.eq_ignore_ascii_case("EvalFile") {
+            // the path may contain spaces, a bad file keeps the current weights
+            let path: String = args.get(value_idx + 1..).unwrap_or_default().join(" ");
+            match EvalParams::load(&path) {
+                Ok(params) => ctx.bmf.eval_params = params,
+                Err(err) => println!("info string {}", err),
+            }
+        } else if let Some(param) = Uci::evalOptionNames(&ctx.bmf.eval_params).iter().find(|n| n.eq_ignore_ascii_case(&name)) {
+            if let Some(weight) = value.and_then(|v| v.parse::<i32>().ok()) {
+                ctx.bmf.eval_params.setParam(param, weight.clamp(-Uci::MAX_EVAL_WEIGHT, Uci::MAX_EVAL_WEIGHT)).unwrap();
+            }
+        }
+    }
+
+
+    /// Evaluation weights offered as spin options, the piece-square tables are only set through EvalFile
+    fn evalOptionNames(params: &EvalParams) -> Vec<String> {
+        params.paramNames().into_iter()
+            .filter(|name| !name.starts_with("piece_position_scores") && !name.ends_with(".K"))
+            .collect()
+    }
+
+
+    /// Handle "position [startpos | fen <fen>] [moves <m1> ... <mi>]"
+    fn position(&mut self, args: &[&str]) {
+        let moves_idx: usize = args.iter().position(|&t| t == "moves").unwrap_or(args.len());
+        self.waitForSearch();
+        let ctx: &mut SearchContext = self.context.as_mut().unwrap();
+        match args.first() {
+            Some(&"startpos") => self.gs = GameState::new(&ctx.z),
+            Some(&"fen") => {
+                // a bad FEN keeps the current position
+                if let Err(err) = self.gs.importFEN(&ctx.mm.masks, &ctx.z, args[1..moves_idx].join(" ")) {
+                    println!("info string {}", err);
+                    return;
+                }
+            },
+            _ => return,
+        }
+        self.history.clear();
+        for token in args.iter().skip(moves_idx + 1) {
+            match self.gs.findAlgebraMove(&mut ctx.mm, &mut ctx.z, token) {
+                Some(mv) => {
+                    self.history.push(self.gs.position.hash_key);
+                    self.gs.makeMove(&ctx.z, mv);
+                },
+                None => break, // illegal move, ignore the rest of the line
+            }
+        }
+    }
+
+
+    /// Handle "go" and start searching the current position in a new thread
+    fn go(&mut self, args: &[&str]) {
+        self.waitForSearch();
+        let mut ctx: SearchContext = self.context.take().unwrap();
+        let mut search_depth: Option<u32> = None;
+        let mut move_time: Option<u64> = None;
+        let (mut wtime, mut btime, mut winc, mut binc): (Option<u64>, Option<u64>, u64, u64) = (None, None, 0, 0);
+        let mut moves_to_go: Option<u32> = None;
+        let mut nodes: Option<u64> = None;
+        let mut mate: Option<u32> = None;
+        let mut infinite: bool = false;
+        for (i, &arg) in args.iter().enumerate() {
+            let value: Option<u64> = args.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+            match (arg, value) {
+                ("depth", Some(depth)) => search_depth = Some(depth.min(Uci::MAX_SEARCH_DEPTH as u64) as u32),
+                ("movetime", Some(ms)) => move_time = Some(ms),
+                ("wtime", Some(ms)) => wtime = Some(ms),
+                ("btime", Some(ms)) => btime = Some(ms),
+                ("winc", Some(ms)) => winc = ms,
+                ("binc", Some(ms)) => binc = ms,
+                ("movestogo", Some(moves)) => moves_to_go = Some(moves as u32),
+                ("nodes", Some(count)) => nodes = Some(count),
+                ("mate", Some(moves)) => mate = Some(moves as u32),
+                ("infinite", _) => infinite = true,
+                _ => (),
+            }
+        }
+        let (time_left, increment) = if self.gs.position.whites_turn {(wtime, winc)} else {(btime, binc)};
+        let mut limits: SearchLimits = SearchLimits::new(search_depth.unwrap_or(Uci::MAX_SEARCH_DEPTH), nodes, None, mate, infinite);
+        if !infinite {
+            let bare_go: bool = move_time.is_none() && time_left.is_none() && search_depth.is_none() && nodes.is_none() && mate.is_none();
+            limits.time_manager = if bare_go {
+                TimeManager::fixedTime(Uci::DEFAULT_MOVE_TIME_MS)
+            } else {
+                TimeManager::new(time_left, increment, moves_to_go, move_time)
+            };
+        }
+        ctx.bmf.limits = limits;
+        ctx.bmf.setGameHistory(self.history.clone());
+
+        self.stop.store(false, Ordering::Relaxed);
+        let stop: Arc<AtomicBool> = Arc::clone(&self.stop);
+        let (bitboards, castle_rights, hash_key, whites_turn) = (self.gs.position.bitboards, self.gs.position.castle_rights, self.gs.position.hash_key, self.gs.position.whites_turn);
+        self.search_thread = Some(thread::spawn(move || {
+            ctx.bmf.searchPosition(&mut ctx.mm, &mut ctx.z, &ctx.tt, bitboards, castle_rights, hash_key, whites_turn);
+            // infinite searches only report their move once told to stop
+            while infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            // the null move is printed as 0000 when there are no legal moves
+            println!("bestmove {}", ctx.bmf.pv_table[0][0]);
+            ctx
+        }));
+    }
+}
+
+
+impl Default for Uci {
+    fn default() -> Self {
+        Uci::new()
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_modules::{
+        chess_move::Move,
+        piece::Piece,
+    };
+
+    #[test]
+    fn position_startpos_with_moves() {
+        let mut uci: Uci = Uci::new();
+        uci.handleCommand("position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1");
+        assert!(get_bit!(uci.gs.position.bitboards[Piece::WK], 62) == 1);
+        assert!(get_bit!(uci.gs.position.bitboards[Piece::WR], 61) == 1);
+        assert!(!uci.gs.position.whites_turn);
+        assert!(uci.history.len() == 7);
+    }
+
+    #[test]
+    fn position_fen_with_promotion_and_enpassant() {
+        let mut uci: Uci = Uci::new();
+        uci.handleCommand("position fen 4k3/1P6/8/8/5p2/8/4P3/4K3 w - - 0 1 moves e2e4 f4e3 b7b8n");
+        assert!(get_bit!(uci.gs.position.bitboards[Piece::BP], 44) == 1);
+        assert!(uci.gs.position.bitboards[Piece::WP] == 0);
+        assert!(get_bit!(uci.gs.position.bitboards[Piece::WN], 1) == 1);
+    }
+
+    #[test]
+    fn go_depth_returns_valid_move() {
+        let mut uci: Uci = Uci::new();
+        uci.handleCommand("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        uci.handleCommand("go depth 3");
+        let ctx: &mut SearchContext = uci.waitForSearch();
+        assert!(ctx.bmf.pv_table[0][0].to_string() == "a1a8");
+    }
+
+    #[test]
+    fn go_with_clock_uses_side_to_move_time() {
+        let mut uci: Uci = Uci::new();
+        uci.handleCommand("position startpos moves e2e4");
+        uci.handleCommand("go wtime 600000 btime 3030 winc 0 binc 0 movestogo 1");
+        let ctx: &mut SearchContext = uci.waitForSearch();
+        assert!(ctx.bmf.limits.time_manager.hard_limit == Duration::from_millis(2250));
+        assert!(ctx.bmf.pv_table[0][0] != Move::NULL);
+    }
+
+    #[test]
+    fn hash_option_resizes_table() {
+        let mut uci: Uci = Uci::new();
+        uci.handleCommand("setoption name Hash value 16");
+        assert!(uci.waitForSearch().tt.sizeMb() == 16);
+    }
+
+    #[test]
+    fn multi_pv_option_sets_line_count() {
+        let mut uci: Uci = Uci::new();
+        uci.handleCommand("setoption name MultiPV value 3");
+        uci.handleCommand("position startpos");
+        uci.handleCommand("go depth 2");
+        let ctx: &mut SearchContext = uci.waitForSearch();
+        assert!(ctx.bmf.multi_pv == 3 && ctx.bmf.pv_lines.len() == 3);
+        assert!(ctx.bmf.pv_lines[0].moves[0] == ctx.bmf.pv_table[0][0]);
+    }
+
+    #[test]
+    fn eval_options_set_weights() {
+        let mut uci: Uci = Uci::new();
+        uci.handleCommand("setoption name double_pawn_penalty value -30");
+        uci.handleCommand("setoption name piece_scores_eg.P value 99999");
+        uci.handleCommand("setoption name piece_scores.K value 1");
+        let params: &EvalParams = &uci.waitForSearch().bmf.eval_params;
+        assert!(params.double_pawn_penalty == -30);
+        assert!(params.piece_scores_eg[&'P'] == Uci::MAX_EVAL_WEIGHT);
+        assert!(params.piece_scores[&'K'] == EvalParams::new().piece_scores[&'K']);
+
+        let mut file_params: EvalParams = EvalParams::new();
+        file_params.setParam("piece_position_scores_eg.K.d5", 60).unwrap();
+        let path: std::path::PathBuf = std::env::temp_dir().join("uci eval params.toml");
+        file_params.save(path.to_str().unwrap()).unwrap();
+        uci.handleCommand(&format!("setoption name EvalFile value {}", path.to_str().unwrap()));
+        uci.handleCommand("ucinewgame");
+        assert!(uci.waitForSearch().bmf.eval_params == file_params);
+        std::fs::remove_file(path).unwrap();
+        // a missing file keeps the weights
+        uci.handleCommand("setoption name EvalFile value no/such/file.toml");
+        assert!(uci.waitForSearch().bmf.eval_params == file_params);
+    }
+}
+
@@ -0,0 +1,416 @@
+//! Module implementing the xboard / WinBoard (CECP) protocol
+
+
+use std::io::{
+    self,
+    BufRead,
+};
+use std::sync::Arc;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use std::thread::{
+    self,
+    JoinHandle,
+};
+use std::time::Duration;
+use crate::{
+    best_move_finder::{
+        BestMoveFinder,
+        SearchOutput,
+    },
+    chess_move::Move,
+    fen_error::FenError,
+    game_result::GameResult,
+    game_state::GameState,
+    moves::Moves,
+    time_manager::TimeManager,
+    trans_table::TransTable,
+    zobrist::Zobrist,
+};
+
+
+/// Game and engine components that are moved into the search thread while thinking
+pub struct GameContext {
+    pub gs: GameState,
+    pub bmf: BestMoveFinder,
+    pub mm: Moves,
+    pub z: Zobrist,
+    pub tt: TransTable,
+    pub move_history: Vec<Move>, // moves played since the start position
+    pub history: Vec<u64>, // hash keys of the positions played before the current one
+}
+
+
+impl GameContext {
+    /// Play a move on the board and record it for undo and repetition detection
+    fn playMove(&mut self, mv: Move) {
+        self.history.push(self.gs.position.hash_key);
+        self.gs.makeMove(&self.z, mv);
+        self.move_history.push(mv);
+    }
+
+
+    /// Take back the last n moves
+    fn takeBack(&mut self, n: usize) {
+        for _ in 0..n {
+            if self.gs.undoMove().is_none() {
+                break;
+            }
+            self.move_history.pop();
+            self.history.pop();
+        }
+    }
+
+
+    /// Reset the game to the initial position or to a FEN, a bad FEN keeps the current game
+    fn setPosition(&mut self, fen: Option<String>) -> Result<(), FenError> {
+        let mut gs: GameState = GameState::new(&self.z);
+        if let Some(fen_str) = fen {
+            gs.importFEN(&self.mm.masks, &self.z, fen_str)?;
+        }
+        self.gs = gs;
+        self.move_history.clear();
+        self.history.clear();
+        Ok(())
+    }
+
+
+    /// Returns the result string once the game is over
+    fn gameResult(&mut self) -> Option<&'static str> {
+        match self.gs.gameStatus(&mut self.mm, &mut self.z) {
+            GameResult::Ongoing => None,
+            GameResult::Checkmate if self.gs.position.whites_turn => Some("0-1 {Black mates}"),
+            GameResult::Checkmate => Some("1-0 {White mates}"),
+            GameResult::Stalemate => Some("1/2-1/2 {Stalemate}"),
+            GameResult::ThreefoldRepetition | GameResult::FivefoldRepetition => Some("1/2-1/2 {Draw by repetition}"),
+            GameResult::FiftyMoveRule | GameResult::SeventyFiveMoveRule => Some("1/2-1/2 {Draw by fifty move rule}"),
+            GameResult::InsufficientMaterial => Some("1/2-1/2 {Insufficient material}"),
+        }
+    }
+}
+
+
+pub struct XBoard {
+    context: Option<GameContext>,
+    search_thread: Option<JoinHandle<GameContext>>,
+    stop: Arc<AtomicBool>, // ends the search early, the best move so far is played
+    abandon: Arc<AtomicBool>, // ends the search early without playing a move
+    force_mode: bool, // engine plays neither side
+    engine_white: bool, // side the engine plays when not in force mode
+    post: bool,
+    depth_limit: u32,
+    move_time: Option<Duration>, // fixed time per move set by "st"
+    moves_per_session: u32, // 0 when the whole game is played in one session
+    increment: Duration,
+    engine_time: Duration, // remaining clock time of the engine set by "time"
+}
+
+
+impl XBoard {
+    pub const ENGINE_NAME: &'static str = "ChessProject";
+    pub const MAX_SEARCH_DEPTH: u32 = 63; // PV table holds 64 plies
+    pub const MAX_THREADS: u32 = 256;
+    pub const DEFAULT_CLOCK: Duration = Duration::from_secs(300); // engine time until "time" or "level" is received
+
+    pub fn new() -> Self {
+        let z: Zobrist = Zobrist::new();
+        let stop: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let mut bmf: BestMoveFinder = BestMoveFinder::new(XBoard::MAX_SEARCH_DEPTH);
+        bmf.output_mode = SearchOutput::XBoard;
+        bmf.stop = Arc::clone(&stop);
+        XBoard {
+            context: Some(GameContext {
+                gs: GameState::new(&z),
+                bmf,
+                mm: Moves::new(),
+                z,
+                tt: TransTable::new(TransTable::DEFAULT_SIZE_MB),
+                move_history: Vec::new(),
+                history: Vec::new(),
+            }),
+            search_thread: None,
+            stop,
+            abandon: Arc::new(AtomicBool::new(false)),
+            force_mode: false,
+            engine_white: false,
+            post: true,
+            depth_limit: XBoard::MAX_SEARCH_DEPTH,
+            move_time: None,
+            moves_per_session: 0,
+            increment: Duration::ZERO,
+            engine_time: XBoard::DEFAULT_CLOCK,
+        }
+    }
+
+
+    /// Read commands from stdin until quit is received
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            match line {
+                Ok(line) => if !self.handleCommand(&line) {
+                    break;
+                },
+                Err(_) => break,
+            }
+        }
+        self.abandonSearch();
+    }
+
+
+    /// Process a single command, returns false when the engine should quit
+    pub fn handleCommand(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let arg: Option<u64> = tokens.get(1).and_then(|v| v.parse::<u64>().ok());
+        match tokens.first() {
+            Some(&"xboard") => (),
+            Some(&"protover") => println!(
+                "feature myname=\"{}\" ping=1 setboard=1 usermove=1 san=0 colors=0 analyze=0 sigint=0 sigterm=0 smp=1 memory=1 done=1",
+                XBoard::ENGINE_NAME
+            ),
+            Some(&"new") => {
+                let ctx: &mut GameContext = self.abandonSearch();
+                ctx.setPosition(None).unwrap();
+                ctx.tt.clearTable();
+                self.force_mode = false;
+                self.engine_white = false;
+                self.depth_limit = XBoard::MAX_SEARCH_DEPTH;
+                self.move_time = None;
+                self.moves_per_session = 0;
+                self.increment = Duration::ZERO;
+                self.engine_time = XBoard::DEFAULT_CLOCK;
+            },
+            Some(&"setboard") => {
+                let ctx: &mut GameContext = self.abandonSearch();
+                if let Err(err) = ctx.setPosition(Some(tokens[1..].join(" "))) {
+                    println!("tellusererror Illegal position: {}", err);
+                }
+            },
+            Some(&"usermove") => self.userMove(tokens.get(1).copied().unwrap_or("")),
+            Some(&"go") => {
+                self.force_mode = false;
+                self.engine_white = self.waitForSearch().gs.position.whites_turn;
+                self.think();
+            },
+            Some(&"playother") => {
+                self.force_mode = false;
+                self.engine_white = !self.waitForSearch().gs.position.whites_turn;
+            },
+            Some(&"force") | Some(&"result") => {
+                self.abandonSearch();
+                self.force_mode = true;
+            },
+            Some(&"undo") => self.abandonSearch().takeBack(1),
+            Some(&"remove") => self.abandonSearch().takeBack(2),
+            Some(&"?") => self.stopSearch(),
+            Some(&"ping") => println!("pong {}", tokens.get(1).unwrap_or(&"")),
+            Some(&"post") => self.post = true,
+            Some(&"nopost") => self.post = false,
+            Some(&"sd") => if let Some(depth) = arg {
+                self.depth_limit = depth.clamp(1, XBoard::MAX_SEARCH_DEPTH as u64) as u32;
+            },
+            Some(&"st") => if let Some(secs) = arg {
+                self.move_time = Some(Duration::from_secs(secs));
+            },
+            Some(&"level") => self.level(&tokens[1..]),
+            Some(&"memory") => if let Some(size_mb) = arg {
+                self.waitForSearch().tt.resize(size_mb as usize);
+            },
+            Some(&"cores") => if let Some(cores) = arg {
+                self.waitForSearch().bmf.threads = (cores as u32).clamp(1, XBoard::MAX_THREADS);
+            },
+            Some(&"time") => if let Some(centisecs) = arg {
+                self.engine_time = Duration::from_millis(centisecs * 10);
+            },
+            Some(&"d") => self.waitForSearch().gs.drawGameArray(),
+            Some(&"quit") => return false,
+            Some(_) | None => (), // moves always come with the usermove prefix (feature usermove=1)
+        }
+        true
+    }
+
+
+    /// Block until the running search (if any) returns the game components
+    pub fn waitForSearch(&mut self) -> &mut GameContext {
+        if let Some(handle) = self.search_thread.take() {
+            self.context = Some(handle.join().expect("search thread panicked"));
+        }
+        self.context.as_mut().unwrap()
+    }
+
+
+    /// Make the engine move now with the best move found so far
+    fn stopSearch(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.waitForSearch();
+    }
+
+
+    /// Stop thinking without playing the move found
+    fn abandonSearch(&mut self) -> &mut GameContext {
+        self.abandon.store(true, Ordering::Relaxed);
+        self.stop.store(true, Ordering::Relaxed);
+        self.waitForSearch()
+    }
+
+
+    /// Handle "level MPS BASE INC" where BASE is minutes or minutes:seconds
+    fn level(&mut self, args: &[&str]) {
+        if args.len() < 3 {
+            return;
+        }
+        self.moves_per_session = args[0].parse::<u32>().unwrap_or(0);
+        let base_secs: u64 = args[1].split(':')
+            .map(|v| v.parse::<u64>().unwrap_or(0))
+            .fold(0, |acc, v| acc * 60 + v) * if args[1].contains(':') {1} else {60};
+        self.engine_time = Duration::from_secs(base_secs);
+        self.increment = Duration::from_secs_f64(args[2].parse::<f64>().unwrap_or(0.0));
+        self.move_time = None;
+    }
+
+
+    /// Handle a move from the opponent and reply if it is the engines turn
+    fn userMove(&mut self, alg_str: &str) {
+        let (force_mode, engine_white) = (self.force_mode, self.engine_white);
+        let ctx: &mut GameContext = self.abandonSearch();
+        match ctx.gs.findAlgebraMove(&mut ctx.mm, &mut ctx.z, alg_str) {
+            Some(mv) => {
+                ctx.playMove(mv);
+                if let Some(result) = ctx.gameResult() {
+                    println!("{}", result);
+                } else if !force_mode && ctx.gs.position.whites_turn == engine_white {
+                    self.think();
+                }
+            },
+            None => println!("Illegal move: {}", alg_str),
+        }
+    }
+
+
+    /// Time limits for the next move based on the current time control
+    fn timeManager(&self, moves_played: usize) -> TimeManager {
+        let moves_to_go: Option<u32> = if self.moves_per_session > 0 {
+            Some(self.moves_per_session - (moves_played / 2) as u32 % self.moves_per_session)
+        } else {
+            None
+        };
+        TimeManager::new(
+            Some(self.engine_time.as_millis() as u64),
+            self.increment.as_millis() as u64,
+            moves_to_go,
+            self.move_time.map(|move_time| move_time.as_millis() as u64),
+        )
+    }
+
+
+    /// Start searching the current position in a new thread and play the best move
+    fn think(&mut self) {
+        self.waitForSearch();
+        let mut ctx: GameContext = self.context.take().unwrap();
+        ctx.bmf.limits.depth = self.depth_limit;
+        ctx.bmf.limits.time_manager = self.timeManager(ctx.move_history.len());
+        ctx.bmf.output_mode = if self.post {SearchOutput::XBoard} else {SearchOutput::Silent};
+        ctx.bmf.setGameHistory(ctx.history.clone());
+
+        self.stop.store(false, Ordering::Relaxed);
+        self.abandon.store(false, Ordering::Relaxed);
+        let abandon: Arc<AtomicBool> = Arc::clone(&self.abandon);
+        self.search_thread = Some(thread::spawn(move || {
+            let (bitboards, castle_rights, hash_key, whites_turn) = (ctx.gs.position.bitboards, ctx.gs.position.castle_rights, ctx.gs.position.hash_key, ctx.gs.position.whites_turn);
+            ctx.bmf.searchPosition(&mut ctx.mm, &mut ctx.z, &ctx.tt, bitboards, castle_rights, hash_key, whites_turn);
+            let best_move: Move = ctx.bmf.pv_table[0][0];
+            if !abandon.load(Ordering::Relaxed) && best_move != Move::NULL {
+                println!("move {}", best_move);
+                ctx.playMove(best_move);
+                if let Some(result) = ctx.gameResult() {
+                    println!("{}", result);
+                }
+            }
+            ctx
+        }));
+    }
+}
+
+
+impl Default for XBoard {
+    fn default() -> Self {
+        XBoard::new()
+    }
+}
+
+
+/// Tests
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_modules::piece::Piece;
+
+    #[test]
+    fn force_mode_moves_and_undo() {
+        let mut xb: XBoard = XBoard::new();
+        xb.handleCommand("force");
+        for alg_str in ["e2e4", "e7e5", "g1f3", "b8c6"] {
+            xb.handleCommand(&format!("usermove {}", alg_str));
+        }
+        let start_hash: u64 = GameState::new(&Zobrist::new()).position.hash_key;
+        let ctx: &mut GameContext = xb.waitForSearch();
+        assert!(ctx.move_history.len() == 4);
+        assert!(get_bit!(ctx.gs.position.bitboards[Piece::WN], 45) == 1);
+        xb.handleCommand("undo");
+        assert!(xb.waitForSearch().move_history.len() == 3);
+        assert!(xb.waitForSearch().gs.position.whites_turn == false);
+        xb.handleCommand("remove");
+        xb.handleCommand("remove");
+        let ctx: &mut GameContext = xb.waitForSearch();
+        assert!(ctx.move_history.is_empty());
+        assert!(ctx.gs.position.hash_key == start_hash);
+    }
+
+    #[test]
+    fn setboard_then_go_plays_mate() {
+        let mut xb: XBoard = XBoard::new();
+        xb.handleCommand("force");
+        xb.handleCommand("setboard 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        xb.handleCommand("sd 3");
+        xb.handleCommand("go");
+        let ctx: &mut GameContext = xb.waitForSearch();
+        assert!(ctx.move_history.len() == 1);
+        assert!(ctx.move_history[0].to_string() == "a1a8");
+    }
+
+    #[test]
+    fn illegal_move_is_rejected() {
+        let mut xb: XBoard = XBoard::new();
+        xb.handleCommand("force");
+        xb.handleCommand("usermove e2e5");
+        assert!(xb.waitForSearch().move_history.is_empty());
+    }
+
+    #[test]
+    fn unknown_commands_do_not_abandon_the_search() {
+        let mut xb: XBoard = XBoard::new();
+        xb.handleCommand("force");
+        xb.handleCommand("sd 4");
+        xb.handleCommand("go");
+        for command in ["draw", "hint", "computer", "random", "."] {
+            xb.handleCommand(command);
+        }
+        assert!(xb.waitForSearch().move_history.len() == 1);
+    }
+
+    #[test]
+    fn new_resets_time_control() {
+        let mut xb: XBoard = XBoard::new();
+        xb.handleCommand("level 40 0:30 5");
+        xb.handleCommand("st 2");
+        xb.handleCommand("sd 4");
+        assert!(xb.moves_per_session == 40 && xb.increment == Duration::from_secs(5) && xb.move_time.is_some());
+        xb.handleCommand("new");
+        assert!(xb.moves_per_session == 0 && xb.increment == Duration::ZERO && xb.move_time.is_none());
+        assert!(xb.engine_time == XBoard::DEFAULT_CLOCK && xb.depth_limit == XBoard::MAX_SEARCH_DEPTH);
+    }
+}
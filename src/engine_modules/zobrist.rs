@@ -9,6 +9,7 @@ use crate::{
 
 
 #[pyclass(module = "ChessProject", get_all, set_all)]
+#[derive(Clone)]
 pub struct Zobrist {
     random_seed: u32,
     pub piece_keys: [[u64; 64]; 12],
@@ -18,12 +18,26 @@ fn ChessProject(_py: Python, m: &PyModule) -> PyResult<()> {
         m,
         special_bitboards::SpecialBitBoards,
         game_state::GameState,
+        position::Position,
+        game_result::GameResult,
+        pgn::PgnGame,
         moves::Moves,
         perft::Perft,
         best_move_finder::BestMoveFinder,
+        best_move_finder::SearchOutput,
+        eval_params::EvalParams,
+        eval_trace::EvalTrace,
+        tuner::Tuner,
+        time_manager::TimeManager,
+        search_limits::SearchLimits,
+        search_limits::StopFlag,
+        search_info::SearchInfo,
+        async_search::AsyncSearch,
         zobrist::Zobrist,
         trans_table::TransTable,
-        opening_book::OpeningBook
+        opening_book::OpeningBook,
+        polyglot::PolyglotBook,
+        polyglot::BookSelection
     );
     Ok(())
 }
@@ -0,0 +1,70 @@
+//! Engine executable for use with chess GUIs and match runners
+//!
+//! Speaks the xboard protocol when the first command is "xboard", otherwise UCI.
+//! Run as "ChessEngine tune <positions> <output> [passes] [start params]" to tune
+//! the evaluation weights instead.
+
+
+#![allow(non_snake_case)]
+
+
+use std::{
+    env,
+    io,
+    process,
+};
+use ChessProject::engine_modules::{
+    eval_params::EvalParams,
+    tuner::Tuner,
+    uci::Uci,
+    xboard::XBoard,
+};
+
+
+/// Texel tuning from the command line, the tuned weights are saved after every pass
+fn tune(args: &[String]) -> Result<(), String> {
+    let (positions, output) = match args {
+        [positions, output, ..] => (positions, output),
+        _ => return Err(String::from("usage: ChessEngine tune <positions> <output> [passes] [start params]")),
+    };
+    let passes: u32 = match args.get(2) {
+        Some(passes) => passes.parse::<u32>().map_err(|_| format!("bad pass count '{}'", passes))?,
+        None => 100,
+    };
+    let params: EvalParams = match args.get(3) {
+        Some(path) => EvalParams::load(path).map_err(|err| err.to_string())?,
+        None => EvalParams::new(),
+    };
+    let mut tuner: Tuner = Tuner::new(params);
+    tuner.verbose = true;
+    let count: usize = tuner.loadPositions(positions).map_err(|err| err.to_string())?;
+    println!("{} positions, k {:.3}, error {:.8}", count, tuner.findK(), tuner.error());
+    let error: f64 = tuner.tune(passes, None, Some(output)).map_err(|err| err.to_string())?;
+    println!("tuned error {:.8}, weights saved to {}", error, output);
+    Ok(())
+}
+
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(|arg| arg.as_str()) == Some("tune") {
+        if let Err(err) = tune(&args[2..]) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        return;
+    }
+    let mut first_command: String = String::new();
+    if io::stdin().read_line(&mut first_command).is_err() {
+        return;
+    }
+    if first_command.trim() == "xboard" {
+        let mut xboard: XBoard = XBoard::new();
+        xboard.run();
+    } else {
+        let mut uci: Uci = Uci::new();
+        if uci.handleCommand(&first_command) {
+            uci.run();
+        }
+    }
+}